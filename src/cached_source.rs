@@ -1,19 +1,21 @@
 use std::{
   borrow::Cow,
   hash::{Hash, Hasher},
-  sync::{Arc, OnceLock},
+  sync::{Arc, Mutex, OnceLock},
 };
 
 use rustc_hash::FxHasher;
 
 use crate::{
   helpers::{
-    stream_and_get_source_and_map, stream_chunks_of_raw_source,
-    stream_chunks_of_source_map, Chunks, GeneratedInfo, StreamChunks,
+    report_generated_ranges, stream_and_get_source_and_map,
+    stream_chunks_of_raw_source, stream_chunks_of_source_map, Chunks,
+    GeneratedInfo, OnSourceWithExtras, StreamChunks,
   },
   object_pool::ObjectPool,
   source::SourceValue,
-  BoxSource, MapOptions, RawBufferSource, Source, SourceExt, SourceMap,
+  BoxSource, LineIndex, MapOptions, RawBufferSource, Source, SourceExt,
+  SourceKind, SourceMap, SourceMapJsonOptions,
 };
 
 #[derive(Default)]
@@ -23,6 +25,11 @@ struct CachedData {
   chunks: OnceLock<Vec<&'static str>>,
   columns_map: OnceLock<Option<SourceMap>>,
   line_only_map: OnceLock<Option<SourceMap>>,
+  // The map for the last options besides `columns`, see
+  // [MapOptions::changes_map]. Other options are applied to `columns_map`
+  // or `line_only_map`.
+  options_map: Mutex<Option<(MapOptions, Option<SourceMap>)>>,
+  line_index: OnceLock<LineIndex>,
 }
 
 /// It tries to reused cached results from other methods to avoid calculations,
@@ -80,6 +87,16 @@ impl CachedSource {
     }
   }
 
+  /// Get the original [Source].
+  pub fn original(&self) -> &BoxSource {
+    &self.inner
+  }
+
+  /// Get the [LineIndex] of the code, built on first use and cached.
+  pub fn line_index(&self) -> &LineIndex {
+    self.cache.line_index.get_or_init(|| LineIndex::new(self))
+  }
+
   fn get_or_init_chunks(&self) -> &[&str] {
     self.cache.chunks.get_or_init(|| {
       let mut chunks = Vec::new();
@@ -95,6 +112,27 @@ impl CachedSource {
       }
     })
   }
+
+  // The cached map for `options`, if it was generated with the same options.
+  fn cached_options_map(
+    &self,
+    options: &MapOptions,
+  ) -> Option<Option<SourceMap>> {
+    let options_map = self.cache.options_map.lock().unwrap();
+    let (cached_options, map) = options_map.as_ref()?;
+    cached_options
+      .generates_same_map(options)
+      .then(|| map.clone())
+  }
+
+  // The cell of the map generated with only `columns` set.
+  fn plain_map_cell(&self, columns: bool) -> &OnceLock<Option<SourceMap>> {
+    if columns {
+      &self.cache.columns_map
+    } else {
+      &self.cache.line_only_map
+    }
+  }
 }
 
 impl Source for CachedSource {
@@ -140,24 +178,90 @@ impl Source for CachedSource {
     object_pool: &ObjectPool,
     options: &MapOptions,
   ) -> Option<SourceMap> {
-    if options.columns {
-      self
-        .cache
-        .columns_map
-        .get_or_init(|| self.inner.map(object_pool, options))
-        .clone()
-    } else {
-      self
-        .cache
-        .line_only_map
-        .get_or_init(|| self.inner.map(object_pool, options))
-        .clone()
+    if options.changes_map() {
+      if let Some(map) = self.cached_options_map(options) {
+        return map;
+      }
+      // Predicates are compared by identity, so options built per call
+      // miss the cache above, the options are applied to the plain map.
+      let map = self
+        .map(object_pool, &MapOptions::new(options.columns))
+        .map(|mut map| {
+          options.apply_to_map(&mut map);
+          map
+        });
+      *self.cache.options_map.lock().unwrap() =
+        Some((options.clone(), map.clone()));
+      return map;
     }
+    let cache_options = MapOptions::new(options.columns);
+    self
+      .plain_map_cell(options.columns)
+      .get_or_init(|| self.inner.map(object_pool, &cache_options))
+      .clone()
   }
 
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     self.inner.to_writer(writer)
   }
+
+  fn map_to_writer(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+    json_options: &SourceMapJsonOptions,
+    writer: &mut dyn std::io::Write,
+  ) -> crate::Result<bool> {
+    let cell = self.plain_map_cell(options.columns);
+    if options.changes_map() {
+      let map = self.cached_options_map(options).or_else(|| {
+        cell.get().map(|map| {
+          map.clone().map(|mut map| {
+            options.apply_to_map(&mut map);
+            map
+          })
+        })
+      });
+      return match map {
+        Some(Some(map)) => {
+          map.write_json_with_options(writer, json_options)?;
+          Ok(true)
+        }
+        Some(None) => Ok(false),
+        None => {
+          self
+            .inner
+            .map_to_writer(object_pool, options, json_options, writer)
+        }
+      };
+    }
+    match cell.get() {
+      Some(Some(map)) => {
+        map.write_json_with_options(writer, json_options)?;
+        Ok(true)
+      }
+      Some(None) => Ok(false),
+      // Don't fill the cache, the map would be held in memory.
+      None => {
+        self
+          .inner
+          .map_to_writer(object_pool, options, json_options, writer)
+      }
+    }
+  }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Cached
+  }
+
+  fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+    on_child(&self.inner);
+  }
+
+  fn with_children(&self, children: Vec<BoxSource>) -> Option<BoxSource> {
+    let [inner] = <[BoxSource; 1]>::try_from(children).ok()?;
+    Some(CachedSource::new(inner).boxed())
+  }
 }
 
 struct CachedSourceChunks<'source> {
@@ -187,6 +291,32 @@ impl Chunks for CachedSourceChunks<'_> {
     on_source: crate::helpers::OnSource<'_, 'a>,
     on_name: crate::helpers::OnName<'_, 'a>,
   ) -> GeneratedInfo {
+    self.stream_with_extras(
+      object_pool,
+      options,
+      on_chunk,
+      on_source,
+      on_name,
+      &mut |_, _| {},
+      &mut |_, _| {},
+    )
+  }
+
+  fn stream_with_extras<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'a>,
+    on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'a>,
+  ) -> GeneratedInfo {
+    let on_source_with_extras: OnSourceWithExtras<'_, 'a> =
+      &mut |source_index, source, source_content, extras| {
+        on_source_extras(source_index, extras);
+        on_source(source_index, source, source_content);
+      };
     let cell = if options.columns {
       &self.cache.columns_map
     } else {
@@ -195,15 +325,17 @@ impl Chunks for CachedSourceChunks<'_> {
     match cell.get() {
       Some(map) => {
         if let Some(map) = map {
-          stream_chunks_of_source_map(
+          let generated_info = stream_chunks_of_source_map(
             options,
             object_pool,
             self.source.as_ref(),
             map,
             on_chunk,
-            on_source,
+            on_source_with_extras,
             on_name,
-          )
+          );
+          report_generated_ranges(map, on_generated_ranges);
+          generated_info
         } else {
           stream_chunks_of_raw_source(
             self.source.as_ref(),
@@ -220,8 +352,9 @@ impl Chunks for CachedSourceChunks<'_> {
           object_pool,
           self.chunks.as_ref(),
           on_chunk,
-          on_source,
+          on_source_with_extras,
           on_name,
+          on_generated_ranges,
         );
         cell.get_or_init(|| map);
         generated_info
@@ -477,4 +610,73 @@ mod tests {
     let cached_size = cached.size();
     assert_eq!(raw_size, cached_size);
   }
+
+  #[test]
+  fn line_index_is_cached_and_shared() {
+    let cached = CachedSource::new(RawStringSource::from_static("a\nb"));
+    let clone = cached.clone();
+    assert_eq!(cached.line_index().line_count(), 2);
+    assert!(std::ptr::eq(cached.line_index(), clone.line_index()));
+  }
+
+  #[test]
+  fn caches_maps_per_options() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let object_pool = ObjectPool::default();
+    let cached = CachedSource::new(ConcatSource::new([
+      OriginalSource::new("a();\n", "vendor/a.js").boxed(),
+      OriginalSource::new("b();\n", "src/b.js").boxed(),
+    ]));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let options = MapOptions::default()
+      .with_exclude_sources(move |source| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        source.starts_with("vendor/")
+      })
+      .with_sources_content(false);
+
+    let map = cached.map(&object_pool, &options).unwrap();
+    assert_eq!(map.sources(), ["src/b.js"]);
+    assert!(map.sources_content().is_empty());
+    assert_eq!(map.mappings(), ";AAAA");
+    let calls_after_first_map = calls.load(Ordering::Relaxed);
+    assert_eq!(cached.map(&object_pool, &options), Some(map.clone()));
+    let mut json = vec![];
+    assert!(cached
+      .map_to_writer(
+        &object_pool,
+        &options,
+        &SourceMapJsonOptions::default(),
+        &mut json
+      )
+      .unwrap());
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+    assert_eq!(calls.load(Ordering::Relaxed), calls_after_first_map);
+
+    // Other options are not served from that cache.
+    let map = cached.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(map.sources(), ["vendor/a.js", "src/b.js"]);
+
+    // Predicates built per call are applied to the plain map.
+    assert!(cached.cache.columns_map.get().is_some());
+    let per_call = || {
+      MapOptions::default()
+        .with_exclude_sources(|source| source.starts_with("src/"))
+    };
+    let map = cached.map(&object_pool, &per_call()).unwrap();
+    assert_eq!(map.sources(), ["vendor/a.js"]);
+    assert_eq!(map.mappings(), "AAAA");
+    let mut json = vec![];
+    assert!(cached
+      .map_to_writer(
+        &object_pool,
+        &per_call(),
+        &SourceMapJsonOptions::default(),
+        &mut json
+      )
+      .unwrap());
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+  }
 }
@@ -8,12 +8,15 @@ use std::{
 use rustc_hash::FxHashMap as HashMap;
 
 use crate::{
-  helpers::{get_map, Chunks, GeneratedInfo, StreamChunks},
+  helpers::{
+    get_map, stream_chunks_with_extras, utf16_len, Chunks, GeneratedInfo,
+    StreamChunks,
+  },
   linear_map::LinearMap,
   object_pool::ObjectPool,
   source::{Mapping, OriginalLocation},
-  BoxSource, MapOptions, RawStringSource, Source, SourceExt, SourceMap,
-  SourceValue,
+  BoxSource, CachedSource, MapOptions, RawStringSource, Source, SourceExt,
+  SourceKind, SourceMap, SourceValue,
 };
 
 /// Concatenate multiple [Source]s to a single [Source].
@@ -62,24 +65,52 @@ use crate::{
 pub struct ConcatSource {
   children: Mutex<Vec<BoxSource>>,
   is_optimized: OnceLock<Vec<BoxSource>>,
+  child_starts: OnceLock<Vec<Cursor>>,
 }
 
 impl Clone for ConcatSource {
   fn clone(&self) -> Self {
+    if let Some(children) = self.is_optimized.get() {
+      return Self {
+        children: Mutex::default(),
+        is_optimized: OnceLock::from(children.clone()),
+        child_starts: self.child_starts.clone(),
+      };
+    }
+    let children = self.children.lock().unwrap();
     Self {
-      children: Mutex::new(self.children.lock().unwrap().clone()),
-      is_optimized: match self.is_optimized.get() {
-        Some(children) => {
-          let once_lock = OnceLock::new();
-          once_lock.get_or_init(|| children.clone());
-          once_lock
-        }
-        None => OnceLock::default(),
-      },
+      children: Mutex::new(children.clone()),
+      // The children may have been optimized while waiting for the lock.
+      is_optimized: self.is_optimized.clone(),
+      child_starts: self.child_starts.clone(),
     }
   }
 }
 
+/// The child of a [ConcatSource] that produced a generated position, see
+/// [ConcatSource::child_at_offset] and [ConcatSource::child_at_position].
+///
+/// Children are those of [ConcatSource::children], where adjacent
+/// [RawStringSource]s are merged into one child. A position in added raw
+/// code therefore points at the merged child, not at the added one.
+#[derive(Debug, Clone)]
+pub struct ChildPosition {
+  /// Indices into [ConcatSource::children] from the outermost
+  /// [ConcatSource] down to the child, it has more than one item when the
+  /// child is in a nested [ConcatSource].
+  pub path: Vec<usize>,
+  /// The child that produced the position, merged adjacent
+  /// [RawStringSource]s are one child.
+  pub source: BoxSource,
+  /// Byte offset relative to the child.
+  pub offset: usize,
+  /// Line relative to the child, 1-based.
+  pub line: u32,
+  /// Column relative to the child, in UTF-16 code units like the generated
+  /// columns of a [SourceMap].
+  pub column: u32,
+}
+
 impl std::fmt::Debug for ConcatSource {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let indent = f.width().unwrap_or(0);
@@ -87,14 +118,12 @@ impl std::fmt::Debug for ConcatSource {
 
     writeln!(f, "{indent_str}ConcatSource::new(vec![")?;
 
-    let original_children = self.children.lock().unwrap();
-    let children = match self.is_optimized.get() {
-      Some(optimized_children) => optimized_children,
-      None => original_children.as_ref(),
-    };
-    for child in children {
-      writeln!(f, "{:indent$?},", child, indent = indent + 2)?;
-    }
+    self.with_children(|children| {
+      for child in children {
+        writeln!(f, "{:indent$?},", child, indent = indent + 2)?;
+      }
+      Ok(())
+    })?;
     write!(f, "{indent_str}]).boxed()")
   }
 }
@@ -113,7 +142,125 @@ impl ConcatSource {
     concat_source
   }
 
-  fn optimized_children(&self) -> &[BoxSource] {
+  /// Find the child that produced the byte at `offset` of the generated
+  /// code, recursing into nested [ConcatSource]s, also when wrapped in a
+  /// [CachedSource]. The child boundaries are computed on first use and
+  /// cached.
+  ///
+  /// ```
+  /// use rspack_sources::{ConcatSource, OriginalSource, SourceExt};
+  ///
+  /// let source = ConcatSource::new([
+  ///   OriginalSource::new("a;\n", "a.js").boxed(),
+  ///   OriginalSource::new("b;\nc;", "b.js").boxed(),
+  /// ]);
+  /// let position = source.child_at_offset(6).unwrap();
+  /// assert_eq!(position.path, [1]);
+  /// assert_eq!((position.offset, position.line, position.column), (3, 2, 0));
+  /// assert!(source.child_at_offset(8).is_none());
+  /// ```
+  pub fn child_at_offset(&self, offset: usize) -> Option<ChildPosition> {
+    self.child_at(Target::Offset(offset))
+  }
+
+  /// Find the child that produced the generated `line` (1-based) and
+  /// `column` (in UTF-16 code units), recursing into nested
+  /// [ConcatSource]s. A column past the end of a line resolves to the end
+  /// of that line.
+  pub fn child_at_position(
+    &self,
+    line: u32,
+    column: u32,
+  ) -> Option<ChildPosition> {
+    if line == 0 {
+      return None;
+    }
+    self.child_at(Target::Position(line, column))
+  }
+
+  fn child_at(&self, target: Target) -> Option<ChildPosition> {
+    let starts = self.child_starts();
+    if !target.before(starts.last()?) {
+      return None;
+    }
+    let index = starts.partition_point(|start| !target.before(start)) - 1;
+    let target = target.relative_to(&starts[index]);
+    let child = &self.optimized_children()[index];
+    let mut inner = child;
+    while let Some(cached_source) =
+      inner.as_ref().as_any().downcast_ref::<CachedSource>()
+    {
+      inner = cached_source.original();
+    }
+    if let Some(concat_source) =
+      inner.as_ref().as_any().downcast_ref::<ConcatSource>()
+    {
+      let mut position = concat_source.child_at(target)?;
+      position.path.insert(0, index);
+      return Some(position);
+    }
+    let mut cursor = Cursor::default();
+    let mut found = false;
+    child.rope(&mut |chunk| {
+      if !found {
+        found = cursor.advance_until(chunk, target);
+      }
+    });
+    Some(ChildPosition {
+      path: vec![index],
+      source: child.clone(),
+      offset: cursor.offset,
+      line: cursor.line,
+      column: cursor.column,
+    })
+  }
+
+  // The children overlapping the byte `range` and the offset the first of
+  // them starts at.
+  pub(crate) fn children_in(
+    &self,
+    range: std::ops::Range<usize>,
+  ) -> (&[BoxSource], usize) {
+    let starts = self.child_starts();
+    let children = self.optimized_children();
+    let first = starts[1..].partition_point(|end| end.offset <= range.start);
+    let last = starts[..children.len()]
+      .partition_point(|start| start.offset < range.end);
+    if first >= last {
+      return (&[], range.start);
+    }
+    (&children[first..last], starts[first].offset)
+  }
+
+  fn child_starts(&self) -> &[Cursor] {
+    self.child_starts.get_or_init(|| {
+      let children = self.optimized_children();
+      let mut starts = Vec::with_capacity(children.len() + 1);
+      let mut cursor = Cursor::default();
+      for child in children {
+        starts.push(cursor);
+        child.rope(&mut |chunk| cursor.advance(chunk));
+      }
+      starts.push(cursor);
+      starts
+    })
+  }
+
+  // Call `f` with the optimized children if available, the original
+  // children otherwise, without locking for optimized sources.
+  fn with_children<R>(&self, f: impl FnOnce(&[BoxSource]) -> R) -> R {
+    if let Some(children) = self.is_optimized.get() {
+      return f(children);
+    }
+    let children = self.children.lock().unwrap();
+    // Optimization holds the lock, check again now that we have it.
+    match self.is_optimized.get() {
+      Some(optimized_children) => f(optimized_children),
+      None => f(&children),
+    }
+  }
+
+  pub(crate) fn optimized_children(&self) -> &[BoxSource] {
     self.is_optimized.get_or_init(|| {
       let mut children = self.children.lock().unwrap();
       optimize(&mut children)
@@ -122,45 +269,200 @@ impl ConcatSource {
 
   /// Add a [Source] to concat.
   pub fn add<S: Source + 'static>(&mut self, source: S) {
-    let children = &mut *self.children.lock().unwrap();
+    let children = self.children_mut();
+    for_each_flattened(source, |child| children.push(child));
+  }
+
+  /// The children of this [ConcatSource].
+  ///
+  /// Nested [ConcatSource]s are flattened when added and adjacent
+  /// [RawStringSource]s are merged into one child, the indices used by
+  /// [ConcatSource::insert], [ConcatSource::remove] and
+  /// [ConcatSource::replace] refer to this list.
+  ///
+  /// ```
+  /// use rspack_sources::{
+  ///   ConcatSource, OriginalSource, RawStringSource, Source, SourceExt,
+  /// };
+  ///
+  /// let mut source = ConcatSource::new([
+  ///   OriginalSource::new("a();\n", "a.js").boxed(),
+  ///   OriginalSource::new("b();\n", "b.js").boxed(),
+  /// ]);
+  /// source.insert(0, RawStringSource::from_static("/* banner */\n"));
+  /// let removed = source.remove(1);
+  /// assert_eq!(removed.source().into_string_lossy(), "a();\n");
+  /// source.replace(1, OriginalSource::new("c();\n", "c.js"));
+  /// assert_eq!(source.children().len(), 2);
+  /// assert_eq!(source.source().into_string_lossy(), "/* banner */\nc();\n");
+  /// ```
+  pub fn children(&self) -> &[BoxSource] {
+    self.optimized_children()
+  }
+
+  /// Insert a [Source] at `index` of [ConcatSource::children], a
+  /// [ConcatSource] is inserted as its children.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is greater than the number of children.
+  pub fn insert<S: Source + 'static>(&mut self, index: usize, source: S) {
+    let mut inserted = Vec::new();
+    for_each_flattened(source, |child| inserted.push(child));
+    self.optimized_children_mut().splice(index..index, inserted);
+  }
+
+  /// Remove and return the child at `index` of [ConcatSource::children].
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  pub fn remove(&mut self, index: usize) -> BoxSource {
+    self.optimized_children_mut().remove(index)
+  }
+
+  /// Replace the child at `index` of [ConcatSource::children] with a
+  /// [Source] and return the previous child, a [ConcatSource] is replaced
+  /// by its children.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `index` is out of bounds.
+  pub fn replace<S: Source + 'static>(
+    &mut self,
+    index: usize,
+    source: S,
+  ) -> BoxSource {
+    let mut replacement = Vec::new();
+    for_each_flattened(source, |child| replacement.push(child));
+    let children = self.optimized_children_mut();
+    let previous = children[index].clone();
+    children.splice(index..=index, replacement);
+    previous
+  }
 
+  // The children for mutation, the cached optimized children and child
+  // boundaries are invalidated.
+  fn children_mut(&mut self) -> &mut Vec<BoxSource> {
+    let children = self.children.get_mut().unwrap();
     if let Some(optimized_children) = self.is_optimized.take() {
       *children = optimized_children;
     }
+    self.child_starts.take();
+    children
+  }
 
-    // First check if it's already a BoxSource containing a ConcatSource
-    if let Some(box_source) = source.as_any().downcast_ref::<BoxSource>() {
-      if let Some(concat_source) =
-        box_source.as_ref().as_any().downcast_ref::<ConcatSource>()
-      {
-        // Extend with existing children (cheap clone due to Arc)
-        let original_children = concat_source.children.lock().unwrap();
-        let other_children = match concat_source.is_optimized.get() {
-          Some(optimized_children) => optimized_children,
-          None => original_children.as_ref(),
-        };
-        children.extend(other_children.iter().cloned());
-        return;
+  // Like [ConcatSource::children_mut], with the children optimized first so
+  // indices refer to [ConcatSource::children].
+  fn optimized_children_mut(&mut self) -> &mut Vec<BoxSource> {
+    self.optimized_children();
+    self.children_mut()
+  }
+}
+
+// Call `f` with `source`, or with its children if it's a [ConcatSource].
+fn for_each_flattened<S: Source + 'static>(
+  source: S,
+  mut f: impl FnMut(BoxSource),
+) {
+  // First check if it's already a BoxSource containing a ConcatSource
+  let concat_source = match source.as_any().downcast_ref::<BoxSource>() {
+    Some(box_source) => {
+      box_source.as_ref().as_any().downcast_ref::<ConcatSource>()
+    }
+    // Check if the source itself is a ConcatSource
+    None => source.as_any().downcast_ref::<ConcatSource>(),
+  };
+  if let Some(concat_source) = concat_source {
+    // Extend with existing children (cheap clone due to Arc)
+    concat_source
+      .with_children(|children| children.iter().cloned().for_each(f));
+  } else {
+    // Regular source - box it and add to children
+    f(source.boxed());
+  }
+}
+
+/// Builds an immutable [ConcatSource] without locks.
+///
+/// Nested [ConcatSource]s are flattened and adjacent [RawStringSource]s are
+/// merged while adding, so the built [ConcatSource] needs no lazy
+/// optimization and its reads never lock.
+///
+/// ```
+/// use rspack_sources::{
+///   ConcatSourceBuilder, OriginalSource, RawStringSource, Source,
+/// };
+///
+/// let mut builder = ConcatSourceBuilder::new();
+/// builder.add(RawStringSource::from_static("/* a */\n"));
+/// builder.add(RawStringSource::from_static("/* b */\n"));
+/// builder.add(OriginalSource::new("c();\n", "c.js"));
+/// let source = builder.build();
+/// assert_eq!(source.children().len(), 2);
+/// assert_eq!(source.source().into_string_lossy(), "/* a */\n/* b */\nc();\n");
+/// ```
+#[derive(Debug, Default)]
+pub struct ConcatSourceBuilder {
+  children: Vec<BoxSource>,
+  raw_sources: Vec<BoxSource>,
+}
+
+impl ConcatSourceBuilder {
+  /// Create an empty [ConcatSourceBuilder].
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create an empty [ConcatSourceBuilder] with space for `capacity`
+  /// children.
+  pub fn with_capacity(capacity: usize) -> Self {
+    Self {
+      children: Vec::with_capacity(capacity),
+      raw_sources: Vec::new(),
+    }
+  }
+
+  /// Add a [Source], a [ConcatSource] is added as its children.
+  pub fn add<S: Source + 'static>(&mut self, source: S) -> &mut Self {
+    for_each_flattened(source, |child| {
+      if child.as_ref().as_any().is::<RawStringSource>() {
+        self.raw_sources.push(child);
+      } else {
+        merge_raw_sources(&mut self.raw_sources, &mut self.children);
+        self.children.push(child);
       }
+    });
+    self
+  }
+
+  /// Build the [ConcatSource].
+  pub fn build(mut self) -> ConcatSource {
+    merge_raw_sources(&mut self.raw_sources, &mut self.children);
+    ConcatSource {
+      children: Mutex::default(),
+      is_optimized: OnceLock::from(self.children),
+      child_starts: OnceLock::new(),
     }
+  }
+}
 
-    // Check if the source itself is a ConcatSource
-    if let Some(concat_source) = source.as_any().downcast_ref::<ConcatSource>()
-    {
-      // Extend with existing children (cheap clone due to Arc)
-      let original_children = concat_source.children.lock().unwrap();
-      let other_children = match concat_source.is_optimized.get() {
-        Some(optimized_children) => optimized_children,
-        None => original_children.as_ref(),
-      };
-      children.extend(other_children.iter().cloned());
-    } else {
-      // Regular source - box it and add to children
-      children.push(source.boxed());
+impl<S: Source + 'static> Extend<S> for ConcatSourceBuilder {
+  fn extend<I: IntoIterator<Item = S>>(&mut self, sources: I) {
+    for source in sources {
+      self.add(source);
     }
   }
 }
 
+impl<S: Source + 'static> FromIterator<S> for ConcatSourceBuilder {
+  fn from_iter<I: IntoIterator<Item = S>>(sources: I) -> Self {
+    let mut builder = Self::new();
+    builder.extend(sources);
+    builder
+  }
+}
+
 impl Source for ConcatSource {
   fn source(&self) -> SourceValue<'_> {
     let children = self.optimized_children();
@@ -222,6 +524,24 @@ impl Source for ConcatSource {
     }
     Ok(())
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Concat
+  }
+
+  fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+    self.optimized_children().iter().for_each(on_child);
+  }
+
+  fn with_children(&self, children: Vec<BoxSource>) -> Option<BoxSource> {
+    Some(
+      children
+        .into_iter()
+        .collect::<ConcatSourceBuilder>()
+        .build()
+        .boxed(),
+    )
+  }
 }
 
 impl Hash for ConcatSource {
@@ -240,6 +560,91 @@ impl PartialEq for ConcatSource {
 }
 impl Eq for ConcatSource {}
 
+// A position in generated code.
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+  line: u32,
+  column: u32,
+  offset: usize,
+}
+
+impl Default for Cursor {
+  fn default() -> Self {
+    Self {
+      line: 1,
+      column: 0,
+      offset: 0,
+    }
+  }
+}
+
+impl Cursor {
+  fn advance(&mut self, chunk: &str) {
+    self.offset += chunk.len();
+    match memchr::memrchr(b'\n', chunk.as_bytes()) {
+      Some(last_newline) => {
+        self.line +=
+          memchr::memchr_iter(b'\n', chunk.as_bytes()).count() as u32;
+        self.column = utf16_len(&chunk[last_newline + 1..]) as u32;
+      }
+      None => self.column += utf16_len(chunk) as u32,
+    }
+  }
+
+  // Advance through `chunk` until `target` is reached, returns whether it
+  // was reached.
+  fn advance_until(&mut self, chunk: &str, target: Target) -> bool {
+    for c in chunk.chars() {
+      if target.reached(self, c) {
+        return true;
+      }
+      self.advance(c.encode_utf8(&mut [0; 4]));
+    }
+    false
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+  Offset(usize),
+  Position(u32, u32),
+}
+
+impl Target {
+  // Whether the target is before `cursor`.
+  fn before(&self, cursor: &Cursor) -> bool {
+    match *self {
+      Target::Offset(offset) => offset < cursor.offset,
+      Target::Position(line, column) => {
+        (line, column) < (cursor.line, cursor.column)
+      }
+    }
+  }
+
+  fn relative_to(&self, start: &Cursor) -> Target {
+    match *self {
+      Target::Offset(offset) => Target::Offset(offset - start.offset),
+      Target::Position(line, column) if line == start.line => {
+        Target::Position(1, column - start.column)
+      }
+      Target::Position(line, column) => {
+        Target::Position(line - start.line + 1, column)
+      }
+    }
+  }
+
+  // Whether `cursor`, followed by `next`, has reached the target.
+  fn reached(&self, cursor: &Cursor, next: char) -> bool {
+    match *self {
+      Target::Offset(offset) => cursor.offset >= offset,
+      Target::Position(line, column) => {
+        cursor.line > line
+          || (cursor.line == line && (cursor.column >= column || next == '\n'))
+      }
+    }
+  }
+}
+
 struct ConcatSourceChunks<'source> {
   children_chunks: Vec<Box<dyn Chunks + 'source>>,
 }
@@ -263,14 +668,37 @@ impl Chunks for ConcatSourceChunks<'_> {
     on_chunk: crate::helpers::OnChunk<'_, 'b>,
     on_source: crate::helpers::OnSource<'_, 'b>,
     on_name: crate::helpers::OnName<'_, 'b>,
+  ) -> GeneratedInfo {
+    self.stream_with_extras(
+      object_pool,
+      options,
+      on_chunk,
+      on_source,
+      on_name,
+      &mut |_, _| {},
+      &mut |_, _| {},
+    )
+  }
+
+  fn stream_with_extras<'b>(
+    &'b self,
+    object_pool: &'b ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'b>,
+    on_source: crate::helpers::OnSource<'_, 'b>,
+    on_name: crate::helpers::OnName<'_, 'b>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'b>,
+    on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'b>,
   ) -> GeneratedInfo {
     if self.children_chunks.len() == 1 {
-      return self.children_chunks[0].stream(
+      return self.children_chunks[0].stream_with_extras(
         object_pool,
         options,
         on_chunk,
         on_source,
         on_name,
+        on_source_extras,
+        on_generated_ranges,
       );
     }
     let mut current_line_offset = 0;
@@ -291,7 +719,8 @@ impl Chunks for ConcatSourceChunks<'_> {
       let GeneratedInfo {
         generated_line,
         generated_column,
-      } = child_handle.stream(
+      } = stream_chunks_with_extras(
+        child_handle.as_ref(),
         object_pool,
         options,
         &mut |chunk, mapping| {
@@ -378,11 +807,12 @@ impl Chunks for ConcatSourceChunks<'_> {
             );
           }
         },
-        &mut |i, source, source_content| {
+        &mut |i, source, source_content, extras| {
           let mut global_index = source_mapping.get(&source).copied();
           if global_index.is_none() {
             let len = source_mapping.len() as u32;
             source_mapping.insert(source.clone(), len);
+            on_source_extras(len, extras);
             on_source(len, source, source_content);
             global_index = Some(len);
           }
@@ -402,6 +832,16 @@ impl Chunks for ConcatSourceChunks<'_> {
             .borrow_mut()
             .insert(i, global_index.unwrap());
         },
+        &mut |mut ranges, names| {
+          let source_index_mapping = source_index_mapping.borrow();
+          for range in &mut ranges {
+            range.shift(current_line_offset, current_column_offset);
+            range.remap_sources(&|source_index| {
+              source_index_mapping.get(&source_index).copied()
+            });
+          }
+          on_generated_ranges(ranges, names);
+        },
       );
       if need_to_close_mapping && (generated_line != 1 || generated_column != 0)
       {
@@ -489,7 +929,7 @@ fn merge_raw_sources(
 
 #[cfg(test)]
 mod tests {
-  use crate::{OriginalSource, RawBufferSource, RawStringSource};
+  use crate::{CachedSource, OriginalSource, RawBufferSource, RawStringSource};
 
   use super::*;
 
@@ -865,4 +1305,154 @@ mod tests {
 ]).boxed()"#
     );
   }
+
+  #[test]
+  fn finds_child_of_generated_position() {
+    let module = ConcatSource::new([
+      RawStringSource::from_static("// 😀\n").boxed(),
+      OriginalSource::new("b();\nc();", "b.js").boxed(),
+    ]);
+    let mut source = ConcatSource::new([
+      RawStringSource::from_static("a();\n").boxed(),
+      CachedSource::new(module).boxed(),
+      RawStringSource::from_static("\n").boxed(),
+    ]);
+
+    let position = source.child_at_offset(0).unwrap();
+    assert_eq!(position.path, [0]);
+    assert_eq!((position.line, position.column), (1, 0));
+
+    // "c" in the nested module
+    let position = source.child_at_offset(18).unwrap();
+    assert_eq!(position.path, [1, 1]);
+    assert_eq!(position.source.source().into_string_lossy(), "b();\nc();");
+    assert_eq!((position.offset, position.line, position.column), (5, 2, 0));
+
+    // The emoji is two UTF-16 code units wide.
+    let position = source.child_at_position(2, 5).unwrap();
+    assert_eq!(position.path, [1, 0]);
+    assert_eq!((position.offset, position.line, position.column), (7, 1, 5));
+    let position = source.child_at_position(3, 2).unwrap();
+    assert_eq!(position.path, [1, 1]);
+    assert_eq!((position.offset, position.line, position.column), (2, 1, 2));
+    // Columns past the end of a line resolve to its end.
+    let position = source.child_at_position(1, 100).unwrap();
+    assert_eq!(position.path, [0]);
+    assert_eq!((position.offset, position.line, position.column), (4, 1, 4));
+
+    assert!(source.child_at_offset(source.size()).is_none());
+    assert!(source.child_at_position(5, 0).is_none());
+    assert!(source.child_at_position(0, 0).is_none());
+
+    source.add(RawStringSource::from_static("d();"));
+    let position = source.child_at_position(5, 1).unwrap();
+    assert_eq!(position.path, [2]);
+    assert_eq!((position.offset, position.line, position.column), (2, 2, 1));
+  }
+
+  #[test]
+  fn finds_merged_raw_child() {
+    let source = ConcatSource::new([
+      RawStringSource::from_static("a\n").boxed(),
+      RawStringSource::from_static("b\n").boxed(),
+      OriginalSource::new("c\n", "c.js").boxed(),
+    ]);
+    // Adjacent raw sources are one merged child, the offset is relative to
+    // the merged child.
+    let position = source.child_at_offset(2).unwrap();
+    assert_eq!(position.path, [0]);
+    assert_eq!(position.source.source().into_string_lossy(), "a\nb\n");
+    assert_eq!((position.offset, position.line, position.column), (2, 2, 0));
+    let position = source.child_at_offset(4).unwrap();
+    assert_eq!(position.path, [1]);
+    assert_eq!(position.offset, 0);
+  }
+
+  #[test]
+  fn edits_children() {
+    let object_pool = ObjectPool::default();
+    let mut source = ConcatSource::new([
+      RawStringSource::from_static("a\n").boxed(),
+      OriginalSource::new("b\n", "b.js").boxed(),
+      RawStringSource::from_static("c\n").boxed(),
+    ]);
+    assert_eq!(source.children().len(), 3);
+    assert_eq!(source.child_at_offset(4).unwrap().path, [2]);
+
+    // Removing the child between the raw sources merges them.
+    source.remove(1);
+    assert_eq!(source.children().len(), 1);
+    assert_eq!(source.source().into_string_lossy(), "a\nc\n");
+    assert_eq!(source.child_at_offset(2).unwrap().path, [0]);
+
+    source.insert(
+      1,
+      ConcatSource::new([
+        OriginalSource::new("d\n", "d.js").boxed(),
+        OriginalSource::new("e\n", "e.js").boxed(),
+      ]),
+    );
+    source.insert(0, RawStringSource::from_static("// banner\n"));
+    assert_eq!(source.children().len(), 3);
+    let previous = source.replace(1, OriginalSource::new("f\n", "f.js"));
+    assert_eq!(previous.source().into_string_lossy(), "d\n");
+
+    let expected = ConcatSource::new([
+      RawStringSource::from_static("// banner\na\nc\n").boxed(),
+      OriginalSource::new("f\n", "f.js").boxed(),
+      OriginalSource::new("e\n", "e.js").boxed(),
+    ]);
+    assert_eq!(source, expected);
+    assert_eq!(
+      source.map(&object_pool, &MapOptions::default()),
+      expected.map(&object_pool, &MapOptions::default())
+    );
+  }
+
+  #[test]
+  fn edits_optimized_children_before_any_read() {
+    let mut source = ConcatSource::new([
+      RawStringSource::from_static("a\n").boxed(),
+      RawStringSource::from_static("b\n").boxed(),
+      OriginalSource::new("c\n", "c.js").boxed(),
+    ]);
+    let removed = source.remove(1);
+    assert_eq!(removed.source().into_string_lossy(), "c\n");
+    assert_eq!(source.source().into_string_lossy(), "a\nb\n");
+  }
+
+  #[test]
+  fn builder_matches_concat_source() {
+    let object_pool = ObjectPool::default();
+    let sources = || {
+      vec![
+        RawStringSource::from_static("a\n").boxed(),
+        RawStringSource::from_static("b\n").boxed(),
+        ConcatSource::new([
+          RawStringSource::from_static("c\n").boxed(),
+          OriginalSource::new("d\n", "d.js").boxed(),
+        ])
+        .boxed(),
+        RawStringSource::from_static("e\n").boxed(),
+      ]
+    };
+    let built = sources()
+      .into_iter()
+      .collect::<ConcatSourceBuilder>()
+      .build();
+    let expected = ConcatSource::new(sources());
+    assert_eq!(built.children().len(), 3);
+    assert_eq!(built, expected);
+    assert_eq!(built.source(), expected.source());
+    assert_eq!(
+      built.map(&object_pool, &MapOptions::default()),
+      expected.map(&object_pool, &MapOptions::default())
+    );
+    assert_eq!(format!("{built:?}"), format!("{expected:?}"));
+
+    let mut built = built.clone();
+    built.add(RawStringSource::from_static("f\n"));
+    assert_eq!(built.children().len(), 3);
+    assert_eq!(built.source().into_string_lossy(), "a\nb\nc\nd\ne\nf\n");
+  }
 }
@@ -0,0 +1,125 @@
+use std::hash::Hasher;
+
+use twox_hash::{XxHash3_64, XxHash64};
+
+use crate::Source;
+
+/// Algorithm used by [Source::content_hash].
+///
+/// Both algorithms are seeded with `0`, so the hashes are stable across
+/// releases and match other xxHash implementations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ContentHashAlgorithm {
+  /// 64-bit xxHash.
+  #[default]
+  Xxhash64,
+  /// 64-bit XXH3.
+  Xxh3,
+}
+
+enum ContentHasher {
+  Xxhash64(XxHash64),
+  Xxh3(Box<XxHash3_64>),
+}
+
+impl ContentHasher {
+  fn new(algorithm: ContentHashAlgorithm) -> Self {
+    match algorithm {
+      ContentHashAlgorithm::Xxhash64 => Self::Xxhash64(XxHash64::with_seed(0)),
+      ContentHashAlgorithm::Xxh3 => {
+        Self::Xxh3(Box::new(XxHash3_64::with_seed(0)))
+      }
+    }
+  }
+
+  fn finish(&self) -> u64 {
+    match self {
+      Self::Xxhash64(hasher) => hasher.finish(),
+      Self::Xxh3(hasher) => hasher.finish(),
+    }
+  }
+}
+
+impl std::io::Write for ContentHasher {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    match self {
+      Self::Xxhash64(hasher) => hasher.write(buf),
+      Self::Xxh3(hasher) => hasher.write(buf),
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+pub fn content_hash<S: Source + ?Sized>(
+  source: &S,
+  algorithm: ContentHashAlgorithm,
+) -> std::io::Result<u64> {
+  let mut hasher = ContentHasher::new(algorithm);
+  source.to_writer(&mut hasher)?;
+  Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    ConcatSource, OriginalSource, RawBufferSource, RawStringSource,
+    ReplaceSource, SourceExt,
+  };
+
+  use super::*;
+
+  #[test]
+  fn hashes_are_stable() {
+    let empty = RawStringSource::from_static("");
+    assert_eq!(
+      empty.content_hash(ContentHashAlgorithm::Xxhash64).unwrap(),
+      0xef46db3751d8e999
+    );
+    assert_eq!(
+      empty.content_hash(ContentHashAlgorithm::Xxh3).unwrap(),
+      0x2d06800538d394c2
+    );
+  }
+
+  #[test]
+  fn hash_only_depends_on_the_content() {
+    let raw = RawStringSource::from("console.log(1);\nfoo();");
+    let concat = ConcatSource::new([
+      OriginalSource::new("console.log(", "a.js").boxed(),
+      RawBufferSource::from("1);\n".as_bytes()).boxed(),
+      RawStringSource::from_static("foo();").boxed(),
+    ]);
+    let mut replace = ReplaceSource::new(OriginalSource::new(
+      "console.log(2);\nbar();",
+      "b.js",
+    ));
+    replace.replace(12, 13, "1", None);
+    replace.replace(16, 19, "foo", None);
+
+    for algorithm in
+      [ContentHashAlgorithm::Xxhash64, ContentHashAlgorithm::Xxh3]
+    {
+      let expected = raw.content_hash(algorithm).unwrap();
+      assert_eq!(concat.content_hash(algorithm).unwrap(), expected);
+      assert_eq!(replace.content_hash(algorithm).unwrap(), expected);
+      assert_eq!(
+        replace.clone().boxed().content_hash(algorithm).unwrap(),
+        expected
+      );
+    }
+  }
+
+  #[test]
+  fn binary_content_is_hashed_as_is() {
+    let a = RawBufferSource::from(vec![0xff, 0xfe]);
+    let b = RawBufferSource::from(vec![0xfe, 0xff]);
+    assert_ne!(
+      a.content_hash(ContentHashAlgorithm::Xxh3).unwrap(),
+      b.content_hash(ContentHashAlgorithm::Xxh3).unwrap()
+    );
+  }
+}
@@ -0,0 +1,220 @@
+use std::io::Write;
+
+use twox_hash::XxHash3_128;
+
+use crate::{
+  BoxSource, ConcatSource, RawStringSource, Result, Source, SourceExt,
+  SourceMap,
+};
+
+/// A debug ID following the
+/// [TC39 debug ID proposal](https://github.com/tc39/ecma426/blob/main/proposals/debug-id.md),
+/// formatted as a lowercase hyphenated UUID.
+///
+/// The ID is derived from the XXH3-128 hash of the content and the source
+/// map, with the version and variant bits of a random (version 4) UUID, so
+/// the same input always gets the same ID. Like the hash behind it, it is only
+/// available with the `content-hash` feature.
+///
+/// ```
+/// use rspack_sources::{DebugId, RawStringSource};
+///
+/// let id = DebugId::from_source(&RawStringSource::from("a;"), None).unwrap();
+/// assert_eq!(
+///   id,
+///   DebugId::from_source(&RawStringSource::from("a;"), None).unwrap(),
+/// );
+/// assert_eq!(id.to_string().len(), 36);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DebugId([u8; 16]);
+
+struct HashWriter(XxHash3_128);
+
+impl Write for HashWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.0.write(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+impl HashWriter {
+  // Length-prefixed, so adjacent fields can't be confused.
+  fn write_field(&mut self, field: &[u8]) {
+    self.0.write(&(field.len() as u64).to_le_bytes());
+    self.0.write(field);
+  }
+}
+
+impl DebugId {
+  /// Derive the [DebugId] of a source and its source map.
+  ///
+  /// # Errors
+  ///
+  /// Returns the error of [Source::to_writer], for example when the file of
+  /// a [FileSource](crate::FileSource) can't be read.
+  pub fn from_source(
+    source: &dyn Source,
+    source_map: Option<&SourceMap>,
+  ) -> Result<Self> {
+    let mut hasher = HashWriter(XxHash3_128::with_seed(0));
+    source.to_writer(&mut hasher)?;
+    if let Some(source_map) = source_map {
+      hasher.write_field(source_map.mappings().as_bytes());
+      for source in source_map.sources() {
+        hasher.write_field(source.as_bytes());
+      }
+      for content in source_map.sources_content() {
+        hasher.write_field(content.as_bytes());
+      }
+      for name in source_map.names() {
+        hasher.write_field(name.as_bytes());
+      }
+    }
+    let mut bytes = hasher.0.finish_128().to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Ok(Self(bytes))
+  }
+
+  /// The 16 bytes of the UUID.
+  pub fn as_bytes(&self) -> &[u8; 16] {
+    &self.0
+  }
+}
+
+impl std::fmt::Display for DebugId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    for (index, byte) in self.0.iter().enumerate() {
+      if matches!(index, 4 | 6 | 8 | 10) {
+        f.write_str("-")?;
+      }
+      write!(f, "{byte:02x}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Derive the [DebugId] of a source and its source map, set it as the
+/// `debugId` of the map and append a `//# debugId=` comment to the source.
+/// Only available with the `content-hash` feature.
+///
+/// # Errors
+///
+/// Returns the error of [DebugId::from_source].
+///
+/// ```
+/// use rspack_sources::{inject_debug_id, MapOptions, ObjectPool, OriginalSource, Source, SourceExt};
+///
+/// let source = OriginalSource::new("a;", "a.js").boxed();
+/// let map = source.map(&ObjectPool::default(), &MapOptions::default());
+/// let (source, map, debug_id) = inject_debug_id(source, map).unwrap();
+///
+/// assert_eq!(
+///   source.source().into_string_lossy(),
+///   format!("a;\n//# debugId={debug_id}"),
+/// );
+/// assert_eq!(map.unwrap().get_debug_id(), Some(debug_id.to_string().as_str()));
+/// ```
+pub fn inject_debug_id(
+  source: BoxSource,
+  source_map: Option<SourceMap>,
+) -> Result<(BoxSource, Option<SourceMap>, DebugId)> {
+  let debug_id = DebugId::from_source(source.as_ref(), source_map.as_ref())?;
+  let source_map = source_map.map(|mut source_map| {
+    source_map.set_debug_id(Some(debug_id.to_string()));
+    source_map
+  });
+  let mut ends_with_newline = true;
+  source.rope(&mut |chunk| {
+    if !chunk.is_empty() {
+      ends_with_newline = chunk.ends_with('\n');
+    }
+  });
+  let comment = format!(
+    "{}//# debugId={debug_id}",
+    if ends_with_newline { "" } else { "\n" }
+  );
+  let source =
+    ConcatSource::new([source, RawStringSource::from(comment).boxed()]).boxed();
+  Ok((source, source_map, debug_id))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{MapOptions, ObjectPool, OriginalSource};
+
+  use super::*;
+
+  #[test]
+  fn debug_id_is_a_version_4_uuid() {
+    let id = DebugId::from_source(&RawStringSource::from(""), None).unwrap();
+    let id = id.to_string();
+    assert_eq!(id.len(), 36);
+    assert_eq!(&id[14..15], "4");
+    assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+    assert_eq!(
+      id.split('-').map(str::len).collect::<Vec<_>>(),
+      [8, 4, 4, 4, 12]
+    );
+  }
+
+  #[test]
+  fn debug_id_depends_on_content_and_map() {
+    let object_pool = ObjectPool::default();
+    let a = OriginalSource::new("a;", "a.js");
+    let b = OriginalSource::new("a;", "b.js");
+    let a_map = a.map(&object_pool, &MapOptions::default());
+    let b_map = b.map(&object_pool, &MapOptions::default());
+    assert_eq!(
+      DebugId::from_source(&a, None).unwrap(),
+      DebugId::from_source(&b, None).unwrap()
+    );
+    assert_ne!(
+      DebugId::from_source(&a, a_map.as_ref()).unwrap(),
+      DebugId::from_source(&b, b_map.as_ref()).unwrap()
+    );
+    assert_ne!(
+      DebugId::from_source(&a, None).unwrap(),
+      DebugId::from_source(&RawStringSource::from("b;"), None).unwrap()
+    );
+  }
+
+  #[test]
+  fn debug_id_returns_read_errors() {
+    let path = std::env::temp_dir()
+      .join(format!("rspack_sources_{}_debug_id.js", std::process::id()));
+    std::fs::write(&path, "a;").unwrap();
+    let source = crate::FileSource::open(&path).unwrap();
+    std::fs::write(&path, "ab;").unwrap();
+    assert!(DebugId::from_source(&source, None).is_err());
+    assert!(inject_debug_id(source.boxed(), None).is_err());
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn injected_comment_keeps_mappings() {
+    let object_pool = ObjectPool::default();
+    let source = OriginalSource::new("a;\nb;\n", "a.js").boxed();
+    let map = source.map(&object_pool, &MapOptions::default());
+    let (injected, injected_map, debug_id) =
+      inject_debug_id(source, map.clone()).unwrap();
+    assert_eq!(
+      injected.source().into_string_lossy(),
+      format!("a;\nb;\n//# debugId={debug_id}")
+    );
+    let injected_map = injected_map.unwrap();
+    assert_eq!(injected_map.mappings(), map.unwrap().mappings());
+    assert_eq!(
+      injected
+        .map(&object_pool, &MapOptions::default())
+        .unwrap()
+        .mappings(),
+      injected_map.mappings()
+    );
+  }
+}
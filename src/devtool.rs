@@ -0,0 +1,448 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+  encoder::B64_CHARS, transform_source_tree, BoxSource, ConcatSource, Error,
+  MapOptions, ObjectPool, OriginalSource, RawStringSource, Result, SourceExt,
+  SourceKind, SourceMap, SourceMapSource,
+};
+
+/// How the source map of a [Devtool] is referenced by the emitted code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DevtoolMode {
+  /// The map is emitted as a separate file and referenced by a
+  /// `sourceMappingURL` comment, as in `source-map`.
+  #[default]
+  External,
+  /// The map is emitted as a separate file without a comment, as in
+  /// `hidden-source-map`.
+  Hidden,
+  /// The map is inlined into a `sourceMappingURL` data URL comment, as in
+  /// `inline-source-map`.
+  Inline,
+  /// The code is wrapped in `eval` with a `sourceURL` comment and the map
+  /// inlined, as in `eval-source-map`.
+  Eval,
+}
+
+/// Syntax of the comments appended by [Devtool::emit].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CommentStyle {
+  /// `//# sourceMappingURL=...`
+  #[default]
+  Js,
+  /// `/*# sourceMappingURL=... */`
+  Css,
+}
+
+/// A preset of webpack's [`devtool`](https://webpack.js.org/configuration/devtool/)
+/// option, parsed from names like `eval-cheap-module-source-map`.
+///
+/// - `cheap` maps lines only.
+/// - Without `module` the maps of [SourceMapSource]s are not combined,
+///   their code is mapped to itself like an [OriginalSource]. `module` is
+///   always set without `cheap`.
+/// - `nosources` leaves out the sourcesContent.
+/// - The [DevtoolMode] tells how the map is referenced.
+///
+/// ```
+/// use rspack_sources::{Devtool, DevtoolMode};
+///
+/// let devtool: Devtool = "inline-cheap-source-map".parse().unwrap();
+/// assert_eq!(devtool.mode, DevtoolMode::Inline);
+/// assert!(devtool.cheap && !devtool.module);
+/// assert_eq!(devtool.to_string(), "inline-cheap-source-map");
+/// assert!("cheap-eval-source-map".parse::<Devtool>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Devtool {
+  /// How the source map is referenced.
+  pub mode: DevtoolMode,
+  /// Whether the map has lines only.
+  pub cheap: bool,
+  /// Whether the maps of [SourceMapSource]s are combined.
+  pub module: bool,
+  /// Whether the map leaves out the sourcesContent.
+  pub nosources: bool,
+  /// Whether a map is generated, only `false` for the plain `eval` preset.
+  pub source_map: bool,
+}
+
+impl Default for Devtool {
+  fn default() -> Self {
+    Self {
+      mode: DevtoolMode::External,
+      cheap: false,
+      module: true,
+      nosources: false,
+      source_map: true,
+    }
+  }
+}
+
+/// The code and map emitted for a file by [Devtool::emit].
+#[derive(Debug, Clone)]
+pub struct DevtoolAsset {
+  /// The code, with the comments of the preset appended.
+  pub code: BoxSource,
+  /// The map to emit as `{file}.map`, `None` when it is inlined or the
+  /// preset has no map.
+  pub map: Option<SourceMap>,
+}
+
+impl Devtool {
+  /// The [MapOptions] generating the map of this preset for `file`.
+  pub fn map_options(&self, file: &str) -> MapOptions {
+    MapOptions::new(!self.cheap)
+      .with_sources_content(!self.nosources)
+      .with_file(file_name(file))
+  }
+
+  /// Produce the code and map of `source` emitted as `file`.
+  ///
+  /// The `eval` presets wrap all of `source` in a single `eval` named after
+  /// `file`. To get a `sourceURL` per module like webpack, call `emit` for
+  /// every module with its own name and concatenate the emitted code.
+  ///
+  /// ```
+  /// use rspack_sources::{
+  ///   CommentStyle, Devtool, ObjectPool, OriginalSource, SourceExt,
+  /// };
+  ///
+  /// let source = OriginalSource::new("a();\n", "src/a.js").boxed();
+  /// let devtool = "nosources-source-map".parse::<Devtool>().unwrap();
+  /// let asset = devtool
+  ///   .emit(&ObjectPool::default(), &source, "js/main.js", CommentStyle::Js)
+  ///   .unwrap();
+  /// assert_eq!(
+  ///   asset.code.source().into_string_lossy(),
+  ///   "a();\n//# sourceMappingURL=main.js.map\n",
+  /// );
+  /// let map = asset.map.unwrap();
+  /// assert_eq!(map.file(), Some("main.js"));
+  /// assert!(map.sources_content().is_empty());
+  /// ```
+  pub fn emit(
+    &self,
+    object_pool: &ObjectPool,
+    source: &BoxSource,
+    file: &str,
+    comment_style: CommentStyle,
+  ) -> Result<DevtoolAsset> {
+    if self.mode == DevtoolMode::Eval && comment_style == CommentStyle::Css {
+      return Err(Error::BadDevtool("eval can only emit JavaScript".into()));
+    }
+    let map = if self.source_map {
+      let source = if self.module {
+        source.clone()
+      } else {
+        without_module_maps(source)
+      };
+      source.map(object_pool, &self.map_options(file))
+    } else {
+      None
+    };
+
+    let (code, map) = match self.mode {
+      DevtoolMode::Hidden => (source.clone(), map),
+      DevtoolMode::External => {
+        let code = match &map {
+          Some(_) => {
+            let url = format!("{}.map", file_name(file));
+            append_comment(source, &comment(comment_style, &url))
+          }
+          None => source.clone(),
+        };
+        (code, map)
+      }
+      DevtoolMode::Inline => {
+        let code = match &map {
+          Some(map) => {
+            append_comment(source, &comment(comment_style, &data_url(map)?))
+          }
+          None => source.clone(),
+        };
+        (code, None)
+      }
+      DevtoolMode::Eval => {
+        let mut code = source.source().into_string_lossy().into_owned();
+        code.push('\n');
+        if let Some(map) = &map {
+          code.push_str(&comment(CommentStyle::Js, &data_url(map)?));
+        }
+        code.push_str("//# sourceURL=");
+        code.push_str(file);
+        let code = serde_json::to_string(&code)
+          .expect("serializing a string never fails");
+        (
+          RawStringSource::from(format!("eval({code});\n")).boxed(),
+          None,
+        )
+      }
+    };
+    Ok(DevtoolAsset { code, map })
+  }
+}
+
+impl FromStr for Devtool {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    if s == "eval" {
+      return Ok(Self {
+        mode: DevtoolMode::Eval,
+        source_map: false,
+        ..Default::default()
+      });
+    }
+    let bad_devtool = || Error::BadDevtool(format!("unknown preset `{s}`"));
+    let mut rest = s.strip_suffix("source-map").ok_or_else(bad_devtool)?;
+    let mut devtool = Self::default();
+    for (prefix, mode) in [
+      ("inline-", DevtoolMode::Inline),
+      ("hidden-", DevtoolMode::Hidden),
+      ("eval-", DevtoolMode::Eval),
+    ] {
+      if let Some(stripped) = rest.strip_prefix(prefix) {
+        devtool.mode = mode;
+        rest = stripped;
+        break;
+      }
+    }
+    if let Some(stripped) = rest.strip_prefix("nosources-") {
+      devtool.nosources = true;
+      rest = stripped;
+    }
+    if let Some(stripped) = rest.strip_prefix("cheap-") {
+      devtool.cheap = true;
+      devtool.module = false;
+      rest = stripped;
+      if let Some(stripped) = rest.strip_prefix("module-") {
+        devtool.module = true;
+        rest = stripped;
+      }
+    }
+    if !rest.is_empty() {
+      return Err(bad_devtool());
+    }
+    Ok(devtool)
+  }
+}
+
+impl fmt::Display for Devtool {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if !self.source_map {
+      return f.write_str("eval");
+    }
+    f.write_str(match self.mode {
+      DevtoolMode::External => "",
+      DevtoolMode::Hidden => "hidden-",
+      DevtoolMode::Inline => "inline-",
+      DevtoolMode::Eval => "eval-",
+    })?;
+    if self.nosources {
+      f.write_str("nosources-")?;
+    }
+    if self.cheap {
+      f.write_str("cheap-")?;
+      if self.module {
+        f.write_str("module-")?;
+      }
+    }
+    f.write_str("source-map")
+  }
+}
+
+// Map the code of every SourceMapSource to itself instead of combining its
+// map, like webpack does without `module`.
+fn without_module_maps(source: &BoxSource) -> BoxSource {
+  transform_source_tree(source, &mut |node| {
+    if node.kind() != SourceKind::SourceMap {
+      return node;
+    }
+    match node.as_ref().as_any().downcast_ref::<SourceMapSource>() {
+      Some(source) => {
+        OriginalSource::new(source.value().clone(), source.name()).boxed()
+      }
+      None => node,
+    }
+  })
+}
+
+fn file_name(file: &str) -> &str {
+  file.rsplit('/').next().unwrap_or(file)
+}
+
+fn comment(style: CommentStyle, url: &str) -> String {
+  match style {
+    CommentStyle::Js => format!("//# sourceMappingURL={url}\n"),
+    CommentStyle::Css => format!("/*# sourceMappingURL={url} */\n"),
+  }
+}
+
+fn append_comment(source: &BoxSource, comment: &str) -> BoxSource {
+  let mut ends_with_newline = true;
+  source.rope(&mut |chunk| {
+    if !chunk.is_empty() {
+      ends_with_newline = chunk.ends_with('\n');
+    }
+  });
+  let comment =
+    format!("{}{comment}", if ends_with_newline { "" } else { "\n" });
+  ConcatSource::new([source.clone(), RawStringSource::from(comment).boxed()])
+    .boxed()
+}
+
+fn data_url(map: &SourceMap) -> Result<String> {
+  Ok(format!(
+    "data:application/json;charset=utf-8;base64,{}",
+    base64(map.to_json()?.as_bytes())
+  ))
+}
+
+fn base64(bytes: &[u8]) -> String {
+  let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let n = (chunk[0] as u32) << 16
+      | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+      | *chunk.get(2).unwrap_or(&0) as u32;
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(B64_CHARS[(n >> (18 - i * 6)) as usize & 63] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Source, WithoutOriginalOptions};
+
+  use super::*;
+
+  #[test]
+  fn parses_presets() {
+    for mode in ["", "inline-", "hidden-", "eval-"] {
+      for nosources in ["", "nosources-"] {
+        for cheap in ["", "cheap-", "cheap-module-"] {
+          let name = format!("{mode}{nosources}{cheap}source-map");
+          let devtool = name.parse::<Devtool>().unwrap();
+          assert_eq!(devtool.module, cheap != "cheap-");
+          assert_eq!(devtool.to_string(), name);
+        }
+      }
+    }
+    let eval = "eval".parse::<Devtool>().unwrap();
+    assert!(!eval.source_map);
+    assert_eq!(eval.to_string(), "eval");
+    for name in [
+      "",
+      "source",
+      "module-source-map",
+      "cheap-nosources-source-map",
+    ] {
+      assert!(matches!(name.parse::<Devtool>(), Err(Error::BadDevtool(_))));
+    }
+  }
+
+  // Loader output `b();` on the second line of the original `b.ts`.
+  fn source() -> BoxSource {
+    let loader_map = SourceMap::from_json(
+      r#"{
+        "version": 3,
+        "sources": ["b.ts"],
+        "sourcesContent": ["\nb();"],
+        "names": [],
+        "mappings": "AACA,GAAG"
+      }"#,
+    )
+    .unwrap();
+    ConcatSource::new([
+      OriginalSource::new("a();\n", "a.js").boxed(),
+      SourceMapSource::new(WithoutOriginalOptions {
+        value: "b();\n",
+        name: "b.js",
+        source_map: loader_map,
+      })
+      .boxed(),
+    ])
+    .boxed()
+  }
+
+  fn emit(devtool: &str, comment_style: CommentStyle) -> Result<DevtoolAsset> {
+    devtool.parse::<Devtool>().unwrap().emit(
+      &ObjectPool::default(),
+      &source(),
+      "main.js",
+      comment_style,
+    )
+  }
+
+  #[test]
+  fn combines_maps_with_module() {
+    let map = emit("source-map", CommentStyle::Js).unwrap().map.unwrap();
+    assert_eq!(map.sources(), ["a.js", "b.ts"]);
+    assert_eq!(map.mappings(), "AAAA;ACCA,GAAG");
+
+    let map = emit("cheap-module-source-map", CommentStyle::Js)
+      .unwrap()
+      .map
+      .unwrap();
+    assert_eq!(map.sources(), ["a.js", "b.ts"]);
+    assert_eq!(map.mappings(), "AAAA;ACCA");
+
+    let map = emit("cheap-source-map", CommentStyle::Js)
+      .unwrap()
+      .map
+      .unwrap();
+    assert_eq!(map.sources(), ["a.js", "b.js"]);
+    assert_eq!(map.sources_content()[1].as_ref(), "b();\n");
+    assert_eq!(map.mappings(), "AAAA;ACAA");
+  }
+
+  #[test]
+  fn references_maps_per_mode() {
+    let asset = emit("hidden-source-map", CommentStyle::Js).unwrap();
+    assert_eq!(asset.code.source().into_string_lossy(), "a();\nb();\n");
+    assert!(asset.map.is_some());
+
+    let asset = emit("inline-source-map", CommentStyle::Css).unwrap();
+    let code = asset.code.source().into_string_lossy().into_owned();
+    assert!(code.starts_with(
+      "a();\nb();\n/*# sourceMappingURL=data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjoz"
+    ));
+    assert!(code.ends_with(" */\n"));
+    assert!(asset.map.is_none());
+
+    let asset = emit("eval", CommentStyle::Js).unwrap();
+    assert_eq!(
+      asset.code.source().into_string_lossy(),
+      "eval(\"a();\\nb();\\n\\n//# sourceURL=main.js\");\n"
+    );
+    assert!(asset.map.is_none());
+    let asset = emit("eval-source-map", CommentStyle::Js).unwrap();
+    let code = asset.code.source().into_string_lossy().into_owned();
+    assert!(code.contains("\\n//# sourceMappingURL=data:application/json"));
+    assert!(code.ends_with("\\n//# sourceURL=main.js\");\n"));
+    assert!(matches!(
+      emit("eval-source-map", CommentStyle::Css),
+      Err(Error::BadDevtool(_))
+    ));
+  }
+
+  #[test]
+  fn encodes_base64() {
+    for (input, output) in [
+      ("", ""),
+      ("f", "Zg=="),
+      ("fo", "Zm8="),
+      ("foo", "Zm9v"),
+      ("foob", "Zm9vYg=="),
+      ("\u{ff}", "w78="),
+    ] {
+      assert_eq!(base64(input.as_bytes()), output);
+    }
+  }
+}
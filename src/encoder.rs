@@ -1,6 +1,6 @@
 use crate::Mapping;
 
-const B64_CHARS: &[u8] =
+pub(crate) const B64_CHARS: &[u8] =
   b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
 pub fn encode_vlq(out: &mut Vec<u8>, a: u32, b: u32) {
@@ -25,7 +25,10 @@ pub fn encode_vlq(out: &mut Vec<u8>, a: u32, b: u32) {
 
 pub(crate) trait MappingsEncoder {
   fn encode(&mut self, mapping: &Mapping);
+  /// Take the mappings encoded so far, encoding can continue afterwards.
   fn drain(&mut self) -> String;
+  /// Number of encoded bytes not drained yet.
+  fn buffered_len(&self) -> usize;
 }
 
 pub fn create_encoder(columns: bool) -> Box<dyn MappingsEncoder> {
@@ -146,6 +149,10 @@ impl MappingsEncoder for FullMappingsEncoder {
     }
   }
 
+  fn buffered_len(&self) -> usize {
+    self.mappings.len()
+  }
+
   #[allow(unsafe_code)]
   fn drain(&mut self) -> String {
     unsafe {
@@ -226,6 +233,10 @@ impl MappingsEncoder for LinesOnlyMappingsEncoder {
     }
   }
 
+  fn buffered_len(&self) -> usize {
+    self.mappings.len()
+  }
+
   #[allow(unsafe_code)]
   fn drain(&mut self) -> String {
     unsafe {
@@ -1,19 +1,43 @@
-use std::{error, fmt, result};
+use std::{error, fmt, io, path::PathBuf, result};
 
 /// An alias for [std::result::Result<T, rspack_sources::Error>].
 pub type Result<T> = result::Result<T, Error>;
 
 /// Error for this crate.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
   /// a JSON parsing related failure
   BadJson(simd_json::Error),
+  /// an I/O failure
+  Io(io::Error),
+  /// a file was changed after it was opened
+  FileChanged(PathBuf),
+  /// an edit of a [SourceEditor](crate::SourceEditor) that can't be applied
+  InvalidEdit(String),
+  /// a malformed WebAssembly module
+  BadWasm(String),
+  /// an unknown or unsupported [Devtool](crate::Devtool) preset
+  BadDevtool(String),
+  /// replacements that can't be restored, see
+  /// [ReplaceSource::from_replacements](crate::ReplaceSource::from_replacements)
+  BadReplacements(String),
 }
 
 impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Error::BadJson(err) => write!(f, "bad json: {err}"),
+      Error::Io(err) => write!(f, "io error: {err}"),
+      Error::FileChanged(path) => {
+        write!(f, "file changed since it was opened: {}", path.display())
+      }
+      Error::InvalidEdit(message) => write!(f, "invalid edit: {message}"),
+      Error::BadWasm(message) => write!(f, "bad wasm: {message}"),
+      Error::BadDevtool(message) => write!(f, "bad devtool: {message}"),
+      Error::BadReplacements(message) => {
+        write!(f, "bad replacements: {message}")
+      }
     }
   }
 }
@@ -25,3 +49,9 @@ impl From<simd_json::Error> for Error {
     Error::BadJson(err)
   }
 }
+
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error::Io(err)
+  }
+}
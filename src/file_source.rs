@@ -0,0 +1,638 @@
+use std::{
+  borrow::Cow,
+  hash::{Hash, Hasher},
+  path::{Path, PathBuf},
+  sync::{Arc, OnceLock},
+  time::SystemTime,
+};
+
+use crate::{
+  helpers::{
+    get_generated_source_info, stream_chunks_of_raw_source, Chunks,
+    GeneratedInfo, StreamChunks,
+  },
+  object_pool::ObjectPool,
+  Error, MapOptions, Result, Source, SourceKind, SourceMap, SourceValue,
+};
+
+/// Size and modification time of a file when it was opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileStamp {
+  len: u64,
+  modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+  fn of(metadata: &std::fs::Metadata) -> Self {
+    Self {
+      len: metadata.len(),
+      modified: metadata.modified().ok(),
+    }
+  }
+
+  fn validate(&self, path: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    if Self::of(&metadata) != *self {
+      return Err(Error::FileChanged(path.to_path_buf()));
+    }
+    Ok(())
+  }
+}
+
+#[allow(unsafe_code)]
+fn bytes_as_str<'a>(
+  bytes: &'a [u8],
+  value_as_string: &'a OnceLock<Option<String>>,
+) -> &'a str {
+  value_as_string
+    .get_or_init(|| match String::from_utf8_lossy(bytes) {
+      Cow::Owned(s) => Some(s),
+      Cow::Borrowed(_) => None,
+    })
+    .as_deref()
+    .unwrap_or_else(|| unsafe { std::str::from_utf8_unchecked(bytes) })
+}
+
+fn bytes_source_value<'a>(
+  bytes: &'a [u8],
+  value_as_string: &'a OnceLock<Option<String>>,
+) -> SourceValue<'a> {
+  match std::str::from_utf8(bytes) {
+    Ok(s) => SourceValue::String(Cow::Borrowed(s)),
+    Err(_) => {
+      // Keep the lossy string around for `rope`.
+      bytes_as_str(bytes, value_as_string);
+      SourceValue::Buffer(Cow::Borrowed(bytes))
+    }
+  }
+}
+
+/// A source backed by a file, the content is read lazily on first use.
+///
+/// The size and modification time are recorded when the file is opened, use
+/// [FileSource::validate] to check whether the file was changed since.
+///
+/// [Source::size], [Source::to_writer] and the generated positions needed
+/// for the map of a bundle are read through a bounded buffer, and
+/// [Source::source] and [Source::buffer] return a copy which is dropped
+/// after use. Only [Source::rope], streaming chunks and
+/// [FileSource::try_content] keep the content in memory, as their results
+/// borrow it.
+///
+/// # Panics
+///
+/// The methods of [Source] which can't return an error panic with
+/// [Error::FileChanged] or [Error::Io] when the file can't be read or was
+/// changed since it was opened, [Source::to_writer] returns the error
+/// instead. Call [FileSource::try_content] first to handle it, all methods
+/// then use the content kept in memory.
+///
+/// ```
+/// use rspack_sources::{FileSource, Source};
+///
+/// let path = std::env::temp_dir()
+///   .join(format!("rspack_sources_file_source_{}.js", std::process::id()));
+/// std::fs::write(&path, "console.log('hello');").unwrap();
+///
+/// let source = FileSource::open(&path).unwrap();
+/// assert_eq!(source.size(), 21);
+/// source.validate().unwrap();
+/// assert_eq!(source.try_content().unwrap(), b"console.log('hello');");
+/// assert_eq!(source.source().into_string_lossy(), "console.log('hello');");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct FileSource(Arc<FileSourceInner>);
+
+struct FileSourceInner {
+  path: PathBuf,
+  stamp: FileStamp,
+  content: OnceLock<Result<Vec<u8>>>,
+  content_as_string: OnceLock<Option<String>>,
+}
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+impl FileSource {
+  /// Create a [FileSource] without reading the content yet.
+  pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+    let path = path.into();
+    let stamp = FileStamp::of(&std::fs::metadata(&path)?);
+    Ok(Self(Arc::new(FileSourceInner {
+      path,
+      stamp,
+      content: OnceLock::new(),
+      content_as_string: OnceLock::new(),
+    })))
+  }
+
+  /// Path of the file.
+  pub fn path(&self) -> &Path {
+    &self.0.path
+  }
+
+  /// Check that the file has the same size and modification time as when
+  /// it was opened.
+  pub fn validate(&self) -> Result<()> {
+    self.0.stamp.validate(&self.0.path)
+  }
+
+  /// Read the content and keep it in memory, or return why it can't be
+  /// read. The result is cached, so all methods agree on the content after
+  /// this call.
+  pub fn try_content(&self) -> std::result::Result<&[u8], &Error> {
+    self.0.content.get_or_init(|| self.read()).as_deref()
+  }
+
+  fn read(&self) -> Result<Vec<u8>> {
+    self.validate()?;
+    let content = std::fs::read(&self.0.path)?;
+    if content.len() as u64 != self.0.stamp.len {
+      return Err(Error::FileChanged(self.0.path.clone()));
+    }
+    Ok(content)
+  }
+
+  // The content kept in memory.
+  fn content(&self) -> &[u8] {
+    self.try_content().unwrap_or_else(|err| unreadable(err))
+  }
+
+  fn content_as_str(&self) -> &str {
+    bytes_as_str(self.content(), &self.0.content_as_string)
+  }
+
+  // The content kept in memory, or a copy read now.
+  fn read_now(&self) -> Cow<'_, [u8]> {
+    match self.0.content.get() {
+      Some(Ok(content)) => Cow::Borrowed(content),
+      Some(Err(err)) => unreadable(err),
+      None => Cow::Owned(self.read().unwrap_or_else(|err| unreadable(&err))),
+    }
+  }
+
+  // Lines and final column of the content, read through a bounded buffer.
+  fn generated_info(&self) -> Result<GeneratedInfo> {
+    match self.0.content.get() {
+      Some(Ok(content)) => {
+        return Ok(get_generated_source_info(bytes_as_str(
+          content,
+          &self.0.content_as_string,
+        )))
+      }
+      Some(Err(err)) => unreadable(err),
+      None => {}
+    }
+    self.validate()?;
+    let file = std::fs::File::open(&self.0.path)?;
+    let mut reader = std::io::Read::take(file, self.0.stamp.len);
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut len = 0;
+    let mut info = GeneratedInfo {
+      generated_line: 1,
+      generated_column: 0,
+    };
+    loop {
+      let read = std::io::Read::read(&mut reader, &mut buffer)?;
+      if read == 0 {
+        break;
+      }
+      let chunk = &buffer[..read];
+      len += read as u64;
+      let last_line = match memchr::memrchr(b'\n', chunk) {
+        Some(index) => {
+          info.generated_line +=
+            memchr::memchr_iter(b'\n', chunk).count() as u32;
+          info.generated_column = 0;
+          &chunk[index + 1..]
+        }
+        None => chunk,
+      };
+      info.generated_column += utf16_units(last_line);
+    }
+    if len != self.0.stamp.len {
+      return Err(Error::FileChanged(self.0.path.clone()));
+    }
+    Ok(info)
+  }
+}
+
+// The infallible methods of `Source` can't return why a file can't be read,
+// and reading it as empty would silently drop it from the output.
+fn unreadable(err: &Error) -> ! {
+  panic!("{err}")
+}
+
+// UTF-16 code units of UTF-8 bytes, counted without decoding so a char may
+// be split across buffers: every char starts with a non-continuation byte,
+// and chars of four bytes take two units.
+fn utf16_units(bytes: &[u8]) -> u32 {
+  bytes
+    .iter()
+    .map(|byte| (byte & 0xc0 != 0x80) as u32 + (*byte >= 0xf0) as u32)
+    .sum()
+}
+
+impl Source for FileSource {
+  fn source(&self) -> SourceValue<'_> {
+    match self.read_now() {
+      Cow::Borrowed(content) => {
+        bytes_source_value(content, &self.0.content_as_string)
+      }
+      Cow::Owned(content) => match String::from_utf8(content) {
+        Ok(content) => SourceValue::String(Cow::Owned(content)),
+        Err(err) => SourceValue::Buffer(Cow::Owned(err.into_bytes())),
+      },
+    }
+  }
+
+  fn rope<'a>(&'a self, on_chunk: &mut dyn FnMut(&'a str)) {
+    on_chunk(self.content_as_str())
+  }
+
+  fn buffer(&self) -> Cow<'_, [u8]> {
+    self.read_now()
+  }
+
+  fn size(&self) -> usize {
+    self.0.stamp.len as usize
+  }
+
+  fn map(&self, _: &ObjectPool, _: &MapOptions) -> Option<SourceMap> {
+    None
+  }
+
+  fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+    match self.0.content.get() {
+      Some(Ok(content)) => return writer.write_all(content),
+      Some(Err(err)) => return Err(std::io::Error::other(err.to_string())),
+      None => {}
+    }
+    self.validate().map_err(std::io::Error::other)?;
+    let file = std::fs::File::open(&self.0.path)?;
+    let mut reader = std::io::BufReader::with_capacity(
+      READ_BUFFER_SIZE,
+      std::io::Read::take(file, self.0.stamp.len),
+    );
+    let written = std::io::copy(&mut reader, writer)?;
+    if written != self.0.stamp.len {
+      return Err(std::io::Error::other(Error::FileChanged(
+        self.0.path.clone(),
+      )));
+    }
+    Ok(())
+  }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::File
+  }
+}
+
+impl Hash for FileSource {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    "FileSource".hash(state);
+    self.0.path.hash(state);
+    self.0.stamp.hash(state);
+  }
+}
+
+impl PartialEq for FileSource {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+      || (self.0.path == other.0.path && self.0.stamp == other.0.stamp)
+  }
+}
+
+impl Eq for FileSource {}
+
+impl std::fmt::Debug for FileSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let indent = f.width().unwrap_or(0);
+    let indent_str = format!("{:indent$}", "", indent = indent);
+    write!(
+      f,
+      "{indent_str}FileSource::open({:?}).unwrap().boxed()",
+      self.0.path
+    )
+  }
+}
+
+struct FileSourceChunks<'a>(&'a FileSource);
+
+impl Chunks for FileSourceChunks<'_> {
+  fn stream<'a>(
+    &'a self,
+    _object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+  ) -> GeneratedInfo {
+    if options.final_source {
+      // Only the positions are needed, so the content isn't kept.
+      return self
+        .0
+        .generated_info()
+        .unwrap_or_else(|err| unreadable(&err));
+    }
+    let code = self.0.content_as_str();
+    stream_chunks_of_raw_source(code, options, on_chunk, on_source, on_name)
+  }
+}
+
+impl StreamChunks for FileSource {
+  fn stream_chunks<'a>(&'a self) -> Box<dyn Chunks + 'a> {
+    Box::new(FileSourceChunks(self))
+  }
+}
+
+/// A source backed by a memory-mapped file.
+///
+/// The size and modification time are recorded when the file is mapped, use
+/// [MmapSource::validate] to check whether the file was changed since.
+/// The content is paged in by the operating system on access, so the source
+/// keeps no copy of it in memory unless the file is not valid UTF-8 and is
+/// read as text.
+///
+/// Truncating the file while it is mapped is undefined behavior on most
+/// platforms, only map files which are not modified during the build.
+///
+/// ```
+/// use rspack_sources::{MmapSource, Source};
+///
+/// let path = std::env::temp_dir()
+///   .join(format!("rspack_sources_mmap_source_{}.js", std::process::id()));
+/// std::fs::write(&path, "console.log('hello');").unwrap();
+///
+/// let source = MmapSource::open(&path).unwrap();
+/// assert_eq!(source.source().into_string_lossy(), "console.log('hello');");
+/// # drop(source);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[cfg(feature = "mmap")]
+#[derive(Clone)]
+pub struct MmapSource(Arc<MmapSourceInner>);
+
+#[cfg(feature = "mmap")]
+struct MmapSourceInner {
+  path: PathBuf,
+  stamp: FileStamp,
+  mmap: memmap2::Mmap,
+  mmap_as_string: OnceLock<Option<String>>,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapSource {
+  /// Create a [MmapSource] by memory-mapping the file.
+  #[allow(unsafe_code)]
+  pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+    let path = path.into();
+    let file = std::fs::File::open(&path)?;
+    let stamp = FileStamp::of(&file.metadata()?);
+    // SAFETY: the mapping is only sound as long as the file is not modified,
+    // which is documented on the type and checked by `validate`.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Self(Arc::new(MmapSourceInner {
+      path,
+      stamp,
+      mmap,
+      mmap_as_string: OnceLock::new(),
+    })))
+  }
+
+  /// Path of the file.
+  pub fn path(&self) -> &Path {
+    &self.0.path
+  }
+
+  /// Check that the file has the same size and modification time as when
+  /// it was mapped.
+  pub fn validate(&self) -> Result<()> {
+    self.0.stamp.validate(&self.0.path)
+  }
+
+  fn content_as_str(&self) -> &str {
+    bytes_as_str(&self.0.mmap, &self.0.mmap_as_string)
+  }
+}
+
+#[cfg(feature = "mmap")]
+impl Source for MmapSource {
+  fn source(&self) -> SourceValue<'_> {
+    bytes_source_value(&self.0.mmap, &self.0.mmap_as_string)
+  }
+
+  fn rope<'a>(&'a self, on_chunk: &mut dyn FnMut(&'a str)) {
+    on_chunk(self.content_as_str())
+  }
+
+  fn buffer(&self) -> Cow<'_, [u8]> {
+    Cow::Borrowed(&self.0.mmap)
+  }
+
+  fn size(&self) -> usize {
+    self.0.mmap.len()
+  }
+
+  fn map(&self, _: &ObjectPool, _: &MapOptions) -> Option<SourceMap> {
+    None
+  }
+
+  fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+    writer.write_all(&self.0.mmap)
+  }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Mmap
+  }
+}
+
+#[cfg(feature = "mmap")]
+impl Hash for MmapSource {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    "MmapSource".hash(state);
+    self.0.path.hash(state);
+    self.0.stamp.hash(state);
+  }
+}
+
+#[cfg(feature = "mmap")]
+impl PartialEq for MmapSource {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+      || (self.0.path == other.0.path && self.0.stamp == other.0.stamp)
+  }
+}
+
+#[cfg(feature = "mmap")]
+impl Eq for MmapSource {}
+
+#[cfg(feature = "mmap")]
+impl std::fmt::Debug for MmapSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let indent = f.width().unwrap_or(0);
+    let indent_str = format!("{:indent$}", "", indent = indent);
+    write!(
+      f,
+      "{indent_str}MmapSource::open({:?}).unwrap().boxed()",
+      self.0.path
+    )
+  }
+}
+
+#[cfg(feature = "mmap")]
+struct MmapSourceChunks<'a>(&'a MmapSource);
+
+#[cfg(feature = "mmap")]
+impl Chunks for MmapSourceChunks<'_> {
+  fn stream<'a>(
+    &'a self,
+    _object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+  ) -> GeneratedInfo {
+    let code = self.0.content_as_str();
+    if options.final_source {
+      get_generated_source_info(code)
+    } else {
+      stream_chunks_of_raw_source(code, options, on_chunk, on_source, on_name)
+    }
+  }
+}
+
+#[cfg(feature = "mmap")]
+impl StreamChunks for MmapSource {
+  fn stream_chunks<'a>(&'a self) -> Box<dyn Chunks + 'a> {
+    Box::new(MmapSourceChunks(self))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{ConcatSource, OriginalSource, SourceExt};
+
+  use super::*;
+
+  fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir()
+      .join(format!("rspack_sources_{}_{name}", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+  }
+
+  #[test]
+  fn reads_file_lazily() {
+    let path = temp_file("lazy.js", b"const a = 1;\n");
+    let source = FileSource::open(&path).unwrap();
+    assert_eq!(source.size(), 13);
+
+    let mut written = vec![];
+    source.to_writer(&mut written).unwrap();
+    assert_eq!(written, b"const a = 1;\n");
+    assert!(source.0.content.get().is_none());
+
+    let concat = ConcatSource::new([
+      source.clone().boxed(),
+      OriginalSource::new("a;", "b.js").boxed(),
+    ]);
+    assert_eq!(concat.source().into_string_lossy(), "const a = 1;\na;");
+    let map = concat
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.mappings(), ";AAAA");
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn keeps_content_only_when_borrowed() {
+    let path = temp_file("bounded.js", "a;\n🙈b".as_bytes());
+    let source = FileSource::open(&path).unwrap();
+    assert_eq!(source.buffer().as_ref(), "a;\n🙈b".as_bytes());
+    assert_eq!(source.source().into_string_lossy(), "a;\n🙈b");
+    let concat = ConcatSource::new([
+      source.clone().boxed(),
+      OriginalSource::new("c;", "c.js").boxed(),
+    ]);
+    let map = concat
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.mappings(), ";GAAA");
+    assert!(source.0.content.get().is_none());
+
+    let mut rope = String::new();
+    source.rope(&mut |chunk| rope.push_str(chunk));
+    assert_eq!(rope, "a;\n🙈b");
+    assert!(source.0.content.get().is_some());
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn detects_changed_files() {
+    let path = temp_file("changed.js", b"const a = 1;\n");
+    let source = FileSource::open(&path).unwrap();
+    std::fs::write(&path, b"const a = 12;\n").unwrap();
+
+    assert!(matches!(source.validate(), Err(Error::FileChanged(_))));
+    assert!(source.to_writer(&mut vec![]).is_err());
+    #[cfg(feature = "content-hash")]
+    assert!(source
+      .content_hash(crate::ContentHashAlgorithm::Xxh3)
+      .is_err());
+    assert!(matches!(source.try_content(), Err(Error::FileChanged(_))));
+    assert!(source.to_writer(&mut vec![]).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(source.validate(), Err(Error::Io(_))));
+  }
+
+  #[test]
+  #[should_panic(expected = "file changed since it was opened")]
+  fn panics_reading_changed_files() {
+    let path = temp_file("changed_buffer.js", b"const a = 1;\n");
+    let source = FileSource::open(&path).unwrap();
+    std::fs::write(&path, b"const a = 12;\n").unwrap();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      source.buffer()
+    }));
+    std::fs::remove_file(&path).unwrap();
+    std::panic::resume_unwind(result.unwrap_err());
+  }
+
+  #[test]
+  #[should_panic(expected = "file changed since it was opened")]
+  fn panics_roping_changed_files() {
+    let path = temp_file("changed_rope.js", b"const a = 1;\n");
+    let source = FileSource::open(&path).unwrap();
+    std::fs::write(&path, b"const a = 12;\n").unwrap();
+    assert!(source.try_content().is_err());
+    std::fs::remove_file(&path).unwrap();
+    source.rope(&mut |_| {});
+  }
+
+  #[test]
+  fn handles_binary_files() {
+    let path = temp_file("binary.wasm", &[0, 0xff, b'a']);
+    let source = FileSource::open(&path).unwrap();
+    assert!(source.source().is_buffer());
+    assert_eq!(source.buffer().as_ref(), [0, 0xff, b'a']);
+    assert_eq!(source.source().into_string_lossy(), "\0\u{fffd}a");
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[cfg(feature = "mmap")]
+  #[test]
+  fn maps_file() {
+    let path = temp_file("mmap.js", b"const a = 1;\n");
+    let source = MmapSource::open(&path).unwrap();
+    assert_eq!(source.size(), 13);
+    assert_eq!(source.buffer().as_ref(), b"const a = 1;\n");
+    let mut rope = String::new();
+    source.rope(&mut |chunk| rope.push_str(chunk));
+    assert_eq!(rope, "const a = 1;\n");
+    source.validate().unwrap();
+    assert_eq!(source, source.clone());
+    drop(source);
+    std::fs::remove_file(&path).unwrap();
+  }
+}
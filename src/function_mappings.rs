@@ -0,0 +1,247 @@
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{encoder::encode_vlq, helpers::SourceExtras};
+
+/// A function range of an original source, as described by the
+/// `x_com_bloomberg_sourcesFunctionMappings` source map proposal.
+///
+/// Lines and columns are zero-based positions in the original source, the
+/// range is described by its start and end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FunctionMapping {
+  /// Index of the function name in the `names` field.
+  pub name_index: u32,
+  /// Start line.
+  pub start_line: u32,
+  /// Start column.
+  pub start_column: u32,
+  /// End line.
+  pub end_line: u32,
+  /// End column.
+  pub end_column: u32,
+}
+
+/// Decode one entry of `x_com_bloomberg_sourcesFunctionMappings`.
+///
+/// Each function mapping consists of five VLQ fields, each relative to the
+/// same field of the previous function mapping. Returns `None` for
+/// malformed input.
+pub fn decode_function_mappings(encoded: &str) -> Option<Vec<FunctionMapping>> {
+  let mut result = Vec::new();
+  let mut current = [0i64; 5];
+  let mut field = 0;
+  let mut value = 0i64;
+  let mut shift = 0;
+  for byte in encoded.bytes().chain(std::iter::once(b',')) {
+    if byte == b',' {
+      match field {
+        0 if shift == 0 => continue,
+        5 if shift == 0 => {}
+        _ => return None,
+      }
+      let [name_index, start_line, start_column, end_line, end_column] =
+        current.map(u32::try_from);
+      result.push(FunctionMapping {
+        name_index: name_index.ok()?,
+        start_line: start_line.ok()?,
+        start_column: start_column.ok()?,
+        end_line: end_line.ok()?,
+        end_column: end_column.ok()?,
+      });
+      field = 0;
+      continue;
+    }
+    let digit = base64_value(byte)? as i64;
+    if shift > 32 || field == 5 {
+      return None;
+    }
+    value += (digit & 0b11111) << shift;
+    if digit & 0b100000 != 0 {
+      shift += 5;
+      continue;
+    }
+    let delta = if value & 1 == 1 {
+      -(value >> 1)
+    } else {
+      value >> 1
+    };
+    current[field] += delta;
+    field += 1;
+    value = 0;
+    shift = 0;
+  }
+  Some(result)
+}
+
+/// Encode function mappings into one entry of
+/// `x_com_bloomberg_sourcesFunctionMappings`, see
+/// [decode_function_mappings].
+pub fn encode_function_mappings(mappings: &[FunctionMapping]) -> String {
+  let mut out = Vec::with_capacity(mappings.len() * 6);
+  let mut previous = [0u32; 5];
+  for (index, mapping) in mappings.iter().enumerate() {
+    if index > 0 {
+      out.push(b',');
+    }
+    let current = [
+      mapping.name_index,
+      mapping.start_line,
+      mapping.start_column,
+      mapping.end_line,
+      mapping.end_column,
+    ];
+    for (value, previous) in current.iter().zip(previous) {
+      encode_vlq(&mut out, *value, previous);
+    }
+    previous = current;
+  }
+  #[allow(unsafe_code)]
+  // SAFETY: VLQ encoding only produces ASCII characters.
+  unsafe {
+    String::from_utf8_unchecked(out)
+  }
+}
+
+pub(crate) fn base64_value(byte: u8) -> Option<u8> {
+  match byte {
+    b'A'..=b'Z' => Some(byte - b'A'),
+    b'a'..=b'z' => Some(byte - b'a' + 26),
+    b'0'..=b'9' => Some(byte - b'0' + 52),
+    b'+' => Some(62),
+    b'/' => Some(63),
+    _ => None,
+  }
+}
+
+/// Collects the function mappings reported through [SourceExtras] while a
+/// source map is generated, and rewrites their name indices into the names of
+/// the generated map.
+#[derive(Default)]
+pub(crate) struct FunctionMappingsCollector<'a> {
+  sources: Vec<Option<(&'a str, &'a [String])>>,
+}
+
+impl<'a> FunctionMappingsCollector<'a> {
+  pub fn add(&mut self, source_index: u32, extras: &SourceExtras<'a>) {
+    let source_index = source_index as usize;
+    if let Some(function_mappings) = extras.function_mappings {
+      if self.sources.len() <= source_index {
+        self.sources.resize(source_index + 1, None);
+      }
+      self.sources[source_index] = Some((function_mappings, extras.names));
+    } else if let Some(source) = self.sources.get_mut(source_index) {
+      *source = None;
+    }
+  }
+
+  /// Returns one entry per source, or `None` if no source had function
+  /// mappings. Names missing from `names` are appended to it.
+  pub fn finish<N>(
+    self,
+    sources_len: usize,
+    names: &mut Vec<N>,
+  ) -> Option<Vec<String>>
+  where
+    N: AsRef<str> + From<&'a str>,
+  {
+    if self.sources.iter().all(Option::is_none) {
+      return None;
+    }
+    let mut name_indices: HashMap<String, u32> = HashMap::default();
+    for (index, name) in names.iter().enumerate() {
+      name_indices
+        .entry(name.as_ref().to_string())
+        .or_insert(index as u32);
+    }
+    let mut result = vec![String::new(); sources_len.max(self.sources.len())];
+    for (entry, source) in result.iter_mut().zip(self.sources) {
+      let Some((encoded, origin_names)) = source else {
+        continue;
+      };
+      let Some(mut mappings) = decode_function_mappings(encoded) else {
+        continue;
+      };
+      mappings.retain_mut(|mapping| {
+        let Some(name) = origin_names.get(mapping.name_index as usize) else {
+          return false;
+        };
+        mapping.name_index =
+          *name_indices.entry(name.clone()).or_insert_with(|| {
+            names.push(N::from(name.as_str()));
+            names.len() as u32 - 1
+          });
+        true
+      });
+      *entry = encode_function_mappings(&mappings);
+    }
+    Some(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn round_trip() {
+    let mappings = vec![
+      FunctionMapping {
+        name_index: 0,
+        start_line: 0,
+        start_column: 0,
+        end_line: 2,
+        end_column: 1,
+      },
+      FunctionMapping {
+        name_index: 1,
+        start_line: 1,
+        start_column: 2,
+        end_line: 1,
+        end_column: 20,
+      },
+    ];
+    let encoded = encode_function_mappings(&mappings);
+    assert_eq!(encoded, "AAAEC,CCEDmB");
+    assert_eq!(decode_function_mappings(&encoded).unwrap(), mappings);
+    assert_eq!(decode_function_mappings("").unwrap(), vec![]);
+    assert_eq!(decode_function_mappings("AAA"), None);
+    assert_eq!(decode_function_mappings("AA!AA"), None);
+  }
+
+  #[test]
+  fn collector_remaps_names() {
+    let origin_names = vec!["a".to_string(), "b".to_string()];
+    let encoded = encode_function_mappings(&[
+      FunctionMapping {
+        name_index: 1,
+        start_line: 0,
+        start_column: 0,
+        end_line: 0,
+        end_column: 5,
+      },
+      FunctionMapping {
+        name_index: 0,
+        start_line: 1,
+        start_column: 0,
+        end_line: 1,
+        end_column: 5,
+      },
+    ]);
+    let mut collector = FunctionMappingsCollector::default();
+    collector.add(
+      1,
+      &SourceExtras {
+        function_mappings: Some(&encoded),
+        names: &origin_names,
+        ..Default::default()
+      },
+    );
+    let mut names = vec!["b".to_string()];
+    let result = collector.finish(2, &mut names).unwrap();
+    assert_eq!(names, ["b", "a"]);
+    assert_eq!(result[0], "");
+    let decoded = decode_function_mappings(&result[1]).unwrap();
+    assert_eq!(decoded[0].name_index, 0);
+    assert_eq!(decoded[1].name_index, 1);
+  }
+}
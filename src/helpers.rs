@@ -1,7 +1,7 @@
 use core::str;
 use std::{
   borrow::{BorrowMut, Cow},
-  cell::{OnceCell, RefCell},
+  cell::{Cell, OnceCell, RefCell},
   sync::Arc,
 };
 
@@ -9,13 +9,16 @@ use rustc_hash::FxHashMap as HashMap;
 
 use crate::{
   decoder::MappingsDecoder,
-  encoder::create_encoder,
+  encoder::{create_encoder, MappingsEncoder},
+  function_mappings::FunctionMappingsCollector,
   linear_map::LinearMap,
   object_pool::ObjectPool,
+  scopes::{decode_generated_ranges, GeneratedRange, ScopesCollector},
   source::{Mapping, OriginalLocation},
   source_content_lines::SourceContentLines,
+  source_map_json::{write_mappings_end, write_mappings_start},
   with_utf16::WithUtf16,
-  MapOptions, SourceMap,
+  MapOptions, SourceMap, SourceMapJsonOptions,
 };
 
 pub fn get_map<'a>(
@@ -23,29 +26,57 @@ pub fn get_map<'a>(
   chunks: &'a dyn Chunks,
   options: &MapOptions,
 ) -> Option<SourceMap> {
+  let map = collect_map(object_pool, chunks, options, &mut |_| {});
+  (!map.mappings().is_empty()).then_some(map)
+}
+
+// Collects the map of `chunks` with `options` applied. `on_mapping` is called
+// with the encoder after every mapping and may drain it, the map holds the
+// mappings left in the encoder.
+fn collect_map<'a>(
+  object_pool: &'a ObjectPool,
+  chunks: &'a dyn Chunks,
+  options: &MapOptions,
+  on_mapping: &mut dyn FnMut(&mut dyn MappingsEncoder),
+) -> SourceMap {
   let mut mappings_encoder = create_encoder(options.columns);
   let mut sources: Vec<String> = Vec::new();
   let mut sources_content: Vec<Arc<str>> = Vec::new();
   let mut names: Vec<String> = Vec::new();
+  let mut function_mappings = FunctionMappingsCollector::default();
+  let scopes = RefCell::new(ScopesCollector::default());
+  let mut ignored: Vec<bool> = Vec::new();
+  let filter = RefCell::new(MapFilter::new(options));
 
-  chunks.stream(
+  stream_chunks_with_extras(
+    chunks,
     object_pool,
-    &MapOptions {
-      columns: options.columns,
-      final_source: true,
-    },
+    &options.inner(true),
     // on_chunk
     &mut |_, mapping| {
-      mappings_encoder.encode(&mapping);
+      mappings_encoder.encode(&filter.borrow().mapping(mapping));
+      on_mapping(mappings_encoder.as_mut());
     },
     // on_source
-    &mut |source_index, source, source_content| {
+    &mut |source_index, source, source_content, extras| {
+      let Some(source_index) =
+        filter.borrow_mut().source(source_index, &source)
+      else {
+        return;
+      };
+      if options.names {
+        function_mappings.add(source_index, &extras);
+        scopes.borrow_mut().add_source(source_index, &extras);
+      }
+      set_ignored(&mut ignored, source_index, extras.ignored);
       let source_index = source_index as usize;
       if sources.len() <= source_index {
         sources.resize(source_index + 1, "".to_string());
       }
       sources[source_index] = source.to_string();
-      if let Some(source_content) = source_content {
+      if let Some(source_content) =
+        source_content.filter(|_| options.sources_content)
+      {
         if sources_content.len() <= source_index {
           sources_content.resize(source_index + 1, "".into());
         }
@@ -54,16 +85,170 @@ pub fn get_map<'a>(
     },
     // on_name
     &mut |name_index, name| {
+      if !options.names {
+        return;
+      }
       let name_index = name_index as usize;
       if names.len() <= name_index {
         names.resize(name_index + 1, "".to_string());
       }
       names[name_index] = name.to_string();
     },
+    // on_generated_ranges
+    &mut |ranges, names| {
+      if options.names {
+        scopes.borrow_mut().add_ranges(ranges, names);
+      }
+    },
   );
   let mappings = mappings_encoder.drain();
-  (!mappings.is_empty())
-    .then(|| SourceMap::new(mappings, sources, sources_content, names))
+  let function_mappings = function_mappings.finish(sources.len(), &mut names);
+  let filter = filter.into_inner();
+  let (original_scopes, generated_ranges) =
+    scopes
+      .into_inner()
+      .finish(sources.len(), &mut names, |source_index| {
+        filter.new_source_index(source_index)
+      });
+  let mut map = SourceMap::new(mappings, sources, sources_content, names);
+  map.set_sources_function_mappings(function_mappings);
+  map.set_original_scopes(original_scopes);
+  map.set_generated_ranges(generated_ranges);
+  map.set_ignore_list(ignore_list(&ignored));
+  options.apply_to_map(&mut map);
+  map
+}
+
+// Leaves out the sources and names [MapOptions] exclude while a map is
+// collected, sources get new indices when some are excluded.
+struct MapFilter<'a> {
+  options: &'a MapOptions,
+  source_indices: HashMap<u32, Option<u32>>,
+  next_source_index: u32,
+}
+
+impl<'a> MapFilter<'a> {
+  fn new(options: &'a MapOptions) -> Self {
+    Self {
+      options,
+      source_indices: HashMap::default(),
+      next_source_index: 0,
+    }
+  }
+
+  // The new index of a source, `None` if it is excluded.
+  fn source(&mut self, source_index: u32, source: &str) -> Option<u32> {
+    let Some(predicate) = &self.options.exclude_sources else {
+      return Some(source_index);
+    };
+    if let Some(new_index) = self.source_indices.get(&source_index) {
+      return *new_index;
+    }
+    let new_index = (!predicate(source)).then(|| {
+      self.next_source_index += 1;
+      self.next_source_index - 1
+    });
+    self.source_indices.insert(source_index, new_index);
+    new_index
+  }
+
+  // The new index of a source already seen by [MapFilter::source].
+  fn new_source_index(&self, source_index: u32) -> Option<u32> {
+    if self.options.exclude_sources.is_none() {
+      return Some(source_index);
+    }
+    self.source_indices.get(&source_index).copied().flatten()
+  }
+
+  fn mapping(&self, mut mapping: Mapping) -> Mapping {
+    if let Some(original) = &mut mapping.original {
+      if !self.options.names {
+        original.name_index = None;
+      }
+      if self.options.exclude_sources.is_some() {
+        match self.source_indices.get(&original.source_index) {
+          Some(Some(new_index)) => original.source_index = *new_index,
+          _ => mapping.original = None,
+        }
+      }
+    }
+    mapping
+  }
+}
+
+fn set_ignored(ignored: &mut Vec<bool>, source_index: u32, value: bool) {
+  let source_index = source_index as usize;
+  if ignored.len() <= source_index {
+    if !value {
+      return;
+    }
+    ignored.resize(source_index + 1, false);
+  }
+  ignored[source_index] = value;
+}
+
+fn ignore_list(ignored: &[bool]) -> Option<Vec<u32>> {
+  let ignore_list = ignored
+    .iter()
+    .enumerate()
+    .filter(|(_, ignored)| **ignored)
+    .map(|(index, _)| index as u32)
+    .collect::<Vec<_>>();
+  (!ignore_list.is_empty()).then_some(ignore_list)
+}
+
+// Mappings are written out whenever this many bytes are encoded.
+const MAPPINGS_FLUSH_THRESHOLD: usize = 64 * 1024;
+
+/// Like [get_map], but writes the source map JSON into `writer` while the
+/// chunks are streamed, returns `false` and writes nothing when there are no
+/// mappings.
+///
+/// The mappings are written first as they are encoded. Canonical JSON needs
+/// them in key order, so the map is collected first in that case.
+pub fn write_map_json<'a>(
+  object_pool: &'a ObjectPool,
+  chunks: &'a dyn Chunks,
+  options: &MapOptions,
+  json_options: &SourceMapJsonOptions,
+  writer: &mut dyn std::io::Write,
+) -> crate::Result<bool> {
+  if json_options.canonical {
+    let Some(map) = get_map(object_pool, chunks, options) else {
+      return Ok(false);
+    };
+    map.write_json_with_options(writer, json_options)?;
+    return Ok(true);
+  }
+
+  let mut started = false;
+  let mut result = Ok(());
+
+  let mut write_mappings =
+    |mappings: &str, writer: &mut dyn std::io::Write| -> crate::Result<()> {
+      if mappings.is_empty() {
+        return Ok(());
+      }
+      if !started {
+        started = true;
+        write_mappings_start(writer, json_options)?;
+      }
+      writer.write_all(mappings.as_bytes())?;
+      Ok(())
+    };
+
+  let map = collect_map(object_pool, chunks, options, &mut |encoder| {
+    if result.is_ok() && encoder.buffered_len() >= MAPPINGS_FLUSH_THRESHOLD {
+      result = write_mappings(&encoder.drain(), writer);
+    }
+  });
+  result?;
+  write_mappings(map.mappings(), writer)?;
+  if !started {
+    return Ok(false);
+  }
+  write_mappings_end(writer, &map, json_options)?;
+  Ok(true)
 }
 
 /// A trait for processing source code chunks and generating source maps.
@@ -86,6 +271,32 @@ pub trait Chunks {
     on_source: crate::helpers::OnSource<'_, 'a>,
     on_name: crate::helpers::OnName<'_, 'a>,
   ) -> crate::helpers::GeneratedInfo;
+
+  /// Like [Chunks::stream], additionally reporting the [SourceExtras] of every
+  /// source to `on_source_extras` right before `on_source` is called for it,
+  /// and the generated ranges of the scopes proposal to
+  /// `on_generated_ranges`.
+  ///
+  /// The default implementation drops both, so the maps of sources which
+  /// only implement [Chunks::stream] lose the function mappings, the
+  /// original scopes and the ignoreList of the maps they are built from.
+  /// Sources which move generated code around, like a
+  /// [ReplaceSource](crate::ReplaceSource), drop the generated ranges of
+  /// their inner source.
+  #[allow(clippy::too_many_arguments)]
+  fn stream_with_extras<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'a>,
+    on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'a>,
+  ) -> crate::helpers::GeneratedInfo {
+    let _ = (on_source_extras, on_generated_ranges);
+    self.stream(object_pool, options, on_chunk, on_source, on_name)
+  }
 }
 
 /// [StreamChunks] abstraction, see [webpack-sources source.streamChunks](https://github.com/webpack/webpack-sources/blob/9f98066311d53a153fdc7c633422a1d086528027/lib/helpers/streamChunks.js#L13).
@@ -98,10 +309,107 @@ pub trait StreamChunks {
 pub type OnChunk<'a, 'b> = &'a mut dyn FnMut(Option<&'b str>, Mapping);
 
 /// [OnSource] abstraction, see [webpack-sources onSource](https://github.com/webpack/webpack-sources/blob/9f98066311d53a153fdc7c633422a1d086528027/lib/helpers/streamChunks.js#L13).
-///
 pub type OnSource<'a, 'b> =
   &'a mut dyn FnMut(u32, Cow<'b, str>, Option<&'b Arc<str>>);
 
+/// Receives the [SourceExtras] of a source, see [Chunks::stream_with_extras].
+pub type OnSourceExtras<'a, 'b> = &'a mut dyn FnMut(u32, SourceExtras<'b>);
+
+/// Receives generated ranges in the positions and source indices of the
+/// stream, together with the names their name indices refer to, see
+/// [Chunks::stream_with_extras].
+pub type OnGeneratedRanges<'a, 'b> =
+  &'a mut dyn FnMut(Vec<GeneratedRange>, &'b [String]);
+
+// [OnSource] and [OnSourceExtras] in one callback, used inside the crate.
+pub(crate) type OnSourceWithExtras<'a, 'b> =
+  &'a mut dyn FnMut(u32, Cow<'b, str>, Option<&'b Arc<str>>, SourceExtras<'b>);
+
+/// Additional per source information passed to [OnSourceExtras].
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct SourceExtras<'a> {
+  /// The `x_com_bloomberg_sourcesFunctionMappings` entry of the source.
+  pub function_mappings: Option<&'a str>,
+  /// The `originalScopes` entry of the source.
+  pub original_scopes: Option<&'a str>,
+  /// The names the name indices of `function_mappings` and
+  /// `original_scopes` refer to.
+  pub names: &'a [String],
+  /// Whether the source is in the ignoreList.
+  pub ignored: bool,
+}
+
+impl<'a> SourceExtras<'a> {
+  /// Create [SourceExtras] for the source at `source_index` of `source_map`.
+  pub fn from_source_map(
+    source_map: &'a SourceMap,
+    source_index: usize,
+  ) -> Self {
+    Self {
+      function_mappings: source_map
+        .sources_function_mappings()
+        .and_then(|mappings| mappings.get(source_index))
+        .map(|mappings| mappings.as_str())
+        .filter(|mappings| !mappings.is_empty()),
+      original_scopes: source_map
+        .original_scopes()
+        .and_then(|scopes| scopes.get(source_index))
+        .map(|scopes| scopes.as_str())
+        .filter(|scopes| !scopes.is_empty()),
+      names: source_map.names(),
+      ignored: source_map.ignore_list().is_some_and(|ignore_list| {
+        ignore_list.contains(&(source_index as u32))
+      }),
+    }
+  }
+}
+
+// Streams `chunks` handing every source together with its extras to
+// `on_source`.
+pub(crate) fn stream_chunks_with_extras<'a, C: Chunks + ?Sized>(
+  chunks: &'a C,
+  object_pool: &'a ObjectPool,
+  options: &MapOptions,
+  on_chunk: OnChunk<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
+  on_name: OnName<'_, 'a>,
+  on_generated_ranges: OnGeneratedRanges<'_, 'a>,
+) -> GeneratedInfo {
+  let pending_extras: Cell<Option<(u32, SourceExtras<'a>)>> = Cell::new(None);
+  chunks.stream_with_extras(
+    object_pool,
+    options,
+    on_chunk,
+    &mut |source_index, source, source_content| {
+      let extras = match pending_extras.take() {
+        Some((index, extras)) if index == source_index => extras,
+        _ => SourceExtras::default(),
+      };
+      on_source(source_index, source, source_content, extras);
+    },
+    on_name,
+    &mut |source_index, extras| {
+      pending_extras.set(Some((source_index, extras)));
+    },
+    on_generated_ranges,
+  )
+}
+
+// Reports the generated ranges of `source_map` to `on_generated_ranges`.
+pub(crate) fn report_generated_ranges<'a>(
+  source_map: &'a SourceMap,
+  on_generated_ranges: OnGeneratedRanges<'_, 'a>,
+) {
+  if let Some(ranges) = source_map
+    .generated_ranges()
+    .and_then(decode_generated_ranges)
+    .filter(|ranges| !ranges.is_empty())
+  {
+    on_generated_ranges(ranges, source_map.names());
+  }
+}
+
 /// [OnName] abstraction, see [webpack-sources onName](https://github.com/webpack/webpack-sources/blob/9f98066311d53a153fdc7c633422a1d086528027/lib/helpers/streamChunks.js#L13).
 pub type OnName<'a, 'b> = &'a mut dyn FnMut(u32, Cow<'b, str>);
 
@@ -122,7 +430,9 @@ pub fn stream_chunks_default<'a>(
       source,
       map,
       on_chunk,
-      on_source,
+      &mut |source_index, source, source_content, _| {
+        on_source(source_index, source, source_content)
+      },
       on_name,
     )
   } else {
@@ -244,6 +554,178 @@ pub fn split_into_potential_tokens<'a>(text: &'a str) -> PotentialTokens<'a> {
   }
 }
 
+pub struct BoundaryTokens<'a, I: Iterator<Item = usize>> {
+  text: &'a str,
+  boundaries: std::iter::Peekable<I>,
+  index: usize,
+}
+
+impl<'a, I: Iterator<Item = usize>> Iterator for BoundaryTokens<'a, I> {
+  type Item = Token<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let len = self.text.len();
+    if self.index >= len {
+      return None;
+    }
+
+    let start = self.index;
+    let line_end = memchr::memchr(b'\n', &self.text.as_bytes()[start..])
+      .map_or(len, |pos| start + pos + 1);
+    let mut end = line_end;
+    while let Some(&boundary) = self.boundaries.peek() {
+      if boundary <= start || !self.text.is_char_boundary(boundary) {
+        self.boundaries.next();
+        continue;
+      }
+      if boundary < line_end {
+        end = boundary;
+      }
+      break;
+    }
+
+    self.index = end;
+    let text = &self.text[start..end];
+    Some(Token {
+      text,
+      utf16_len: utf16_len(text),
+    })
+  }
+}
+
+/// Split the text at the given ascending byte offsets and after every `\n`.
+/// Offsets which are out of order or not on a char boundary are ignored.
+pub fn split_into_tokens_at<I: Iterator<Item = usize>>(
+  text: &str,
+  boundaries: I,
+) -> BoundaryTokens<'_, I> {
+  BoundaryTokens {
+    text,
+    boundaries: boundaries.peekable(),
+    index: 0,
+  }
+}
+
+pub fn utf16_len(text: &str) -> usize {
+  if text.is_ascii() {
+    text.len()
+  } else {
+    text.encode_utf16().count()
+  }
+}
+
+// Start of every token matching /[^\n;{},]+[;{},\s]*\n?/g
+pub fn css_token_boundaries(text: &str) -> impl Iterator<Item = usize> + '_ {
+  let bytes = text.as_bytes();
+  let mut index = 0;
+  std::iter::from_fn(move || {
+    while index < bytes.len() {
+      if !matches!(bytes[index], b';' | b'{' | b'}' | b',') {
+        index += 1;
+        continue;
+      }
+      while index < bytes.len()
+        && matches!(
+          bytes[index],
+          b';' | b'{' | b'}' | b',' | b' ' | b'\r' | b'\t'
+        )
+      {
+        index += 1;
+      }
+      if index < bytes.len() && bytes[index] != b'\n' {
+        return Some(index);
+      }
+    }
+    None
+  })
+}
+
+fn is_identifier_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_' || c == '$' || c.is_alphanumeric()
+}
+
+/// Byte ranges of the identifier-like words in the text, a word starting
+/// with a digit is a number and not yielded.
+pub fn identifier_ranges(
+  text: &str,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+  let mut chars = text.char_indices().peekable();
+  std::iter::from_fn(move || loop {
+    let (start, c) = chars.next()?;
+    if !is_identifier_char(c) {
+      continue;
+    }
+    let mut end = start + c.len_utf8();
+    while let Some(&(index, c)) = chars.peek() {
+      if !is_identifier_char(c) {
+        break;
+      }
+      end = index + c.len_utf8();
+      chars.next();
+    }
+    if !c.is_ascii_digit() {
+      return Some((start, end));
+    }
+  })
+}
+
+/// Byte ranges of the identifiers in JavaScript code, like
+/// [identifier_ranges] but skipping the contents of strings, template
+/// literals and comments.
+pub fn js_identifier_ranges(
+  text: &str,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+  let bytes = text.as_bytes();
+  let mut pos = 0;
+  std::iter::from_fn(move || {
+    while pos < bytes.len() {
+      let start = pos;
+      match bytes[pos] {
+        quote @ (b'"' | b'\'' | b'`') => pos = skip_string(bytes, pos, quote),
+        b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+          pos = memchr::memchr(b'\n', &bytes[pos..])
+            .map_or(bytes.len(), |index| pos + index);
+        }
+        b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+          pos = memchr::memmem::find(&bytes[pos + 2..], b"*/")
+            .map_or(bytes.len(), |index| pos + 2 + index + 2);
+        }
+        _ => {
+          let mut chars = text[pos..].chars();
+          let c = chars.next().expect("pos is on a char boundary");
+          pos += c.len_utf8();
+          if !is_identifier_char(c) {
+            continue;
+          }
+          pos += chars
+            .take_while(|c| is_identifier_char(*c))
+            .map(char::len_utf8)
+            .sum::<usize>();
+          if !c.is_ascii_digit() {
+            return Some((start, pos));
+          }
+        }
+      }
+    }
+    None
+  })
+}
+
+// Position after the string starting with `quote` at `start`, a line break
+// ends a string which is not a template literal.
+fn skip_string(bytes: &[u8], start: usize, quote: u8) -> usize {
+  let mut pos = start + 1;
+  while pos < bytes.len() {
+    match bytes[pos] {
+      b'\\' => pos += 2,
+      b'\n' if quote != b'`' => return pos,
+      byte if byte == quote => return pos + 1,
+      _ => pos += 1,
+    }
+  }
+  bytes.len()
+}
+
 /// Split the string with a needle, each string will contain the needle.
 ///
 /// Copied and modified from https://github.com/rust-lang/cargo/blob/30efe860c0e4adc1a6d7057ad223dc6e47d34edf/src/cargo/sources/registry/index.rs#L1048-L1072
@@ -344,7 +826,7 @@ pub fn stream_chunks_of_source_map<'a>(
   source: &'a str,
   source_map: &'a SourceMap,
   on_chunk: OnChunk<'_, 'a>,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   on_name: OnName<'_, 'a>,
 ) -> GeneratedInfo {
   match options {
@@ -398,7 +880,7 @@ fn stream_chunks_of_source_map_final<'a>(
   source: &'a str,
   source_map: &'a SourceMap,
   on_chunk: OnChunk,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   on_name: OnName<'_, 'a>,
 ) -> GeneratedInfo {
   let result = get_generated_source_info(source);
@@ -410,6 +892,7 @@ fn stream_chunks_of_source_map_final<'a>(
       i as u32,
       get_source(source_map, source),
       source_map.get_source_content(i),
+      SourceExtras::from_source_map(source_map, i),
     )
   }
   for (i, name) in source_map.names().iter().enumerate() {
@@ -455,7 +938,7 @@ fn stream_chunks_of_source_map_full<'a>(
   source: &'a str,
   source_map: &'a SourceMap,
   on_chunk: OnChunk<'_, 'a>,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   on_name: OnName<'_, 'a>,
 ) -> GeneratedInfo {
   let lines = split_into_lines(source)
@@ -473,6 +956,7 @@ fn stream_chunks_of_source_map_full<'a>(
       i as u32,
       get_source(source_map, source),
       source_map.get_source_content(i),
+      SourceExtras::from_source_map(source_map, i),
     )
   }
   for (i, name) in source_map.names().iter().enumerate() {
@@ -601,7 +1085,7 @@ fn stream_chunks_of_source_map_lines_final<'a>(
   source: &'a str,
   source_map: &'a SourceMap,
   on_chunk: OnChunk,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   _on_name: OnName,
 ) -> GeneratedInfo {
   let result = get_generated_source_info(source);
@@ -616,6 +1100,7 @@ fn stream_chunks_of_source_map_lines_final<'a>(
       i as u32,
       get_source(source_map, source),
       source_map.get_source_content(i),
+      SourceExtras::from_source_map(source_map, i),
     )
   }
   let final_line = if result.generated_column == 0 {
@@ -646,7 +1131,7 @@ fn stream_chunks_of_source_map_lines_full<'a>(
   source: &'a str,
   source_map: &'a SourceMap,
   on_chunk: OnChunk<'_, 'a>,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   _on_name: OnName,
 ) -> GeneratedInfo {
   let lines: Vec<&str> = split_into_lines(source).collect();
@@ -661,6 +1146,7 @@ fn stream_chunks_of_source_map_lines_full<'a>(
       i as u32,
       get_source(source_map, source),
       source_map.get_source_content(i),
+      SourceExtras::from_source_map(source_map, i),
     )
   }
   let mut current_generated_line = 1;
@@ -737,7 +1223,7 @@ struct SourceMapLineData<'a> {
 }
 
 type InnerSourceIndexValueMapping<'a> =
-  LinearMap<(Cow<'a, str>, Option<&'a Arc<str>>)>;
+  LinearMap<(Cow<'a, str>, Option<&'a Arc<str>>, SourceExtras<'a>)>;
 
 #[allow(clippy::too_many_arguments)]
 pub fn stream_chunks_of_combined_source_map<'a>(
@@ -750,7 +1236,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
   inner_source_map: &'a SourceMap,
   remove_inner_source: bool,
   on_chunk: OnChunk<'_, 'a>,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   on_name: OnName<'_, 'a>,
 ) -> GeneratedInfo {
   let on_source = RefCell::new(on_source);
@@ -765,6 +1251,8 @@ pub fn stream_chunks_of_combined_source_map<'a>(
   let name_index_value_mapping: RefCell<LinearMap<Cow<str>>> =
     RefCell::new(LinearMap::default());
   let inner_source_index: RefCell<i64> = RefCell::new(-2);
+  let inner_source_extras: RefCell<SourceExtras> =
+    RefCell::new(SourceExtras::default());
   let inner_source_index_mapping: RefCell<LinearMap<i64>> =
     RefCell::new(LinearMap::default());
   let inner_source_index_value_mapping: RefCell<InnerSourceIndexValueMapping> =
@@ -902,17 +1390,18 @@ pub fn stream_chunks_of_combined_source_map<'a>(
               .copied()
               .unwrap_or(-2);
             if source_index == -2 {
-              let (source, source_content) = inner_source_index_value_mapping
-                .borrow()
-                .get(&inner_source_index)
-                .cloned()
-                .unwrap_or(("".into(), None));
+              let (source, source_content, extras) =
+                inner_source_index_value_mapping
+                  .borrow()
+                  .get(&inner_source_index)
+                  .cloned()
+                  .unwrap_or(("".into(), None, SourceExtras::default()));
               let mut source_mapping = source_mapping.borrow_mut();
               let mut global_index = source_mapping.get(&source).copied();
               if global_index.is_none() {
                 let len = source_mapping.len() as u32;
                 source_mapping.insert(source.clone(), len);
-                on_source.borrow_mut()(len, source, source_content);
+                on_source.borrow_mut()(len, source, source_content, extras);
                 global_index = Some(len);
               }
               source_index = global_index.unwrap() as i64;
@@ -1051,6 +1540,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
                 len,
                 Cow::Borrowed(inner_source_name),
                 *inner_source.borrow(),
+                *inner_source_extras.borrow(),
               );
               global_index = Some(len);
             }
@@ -1119,9 +1609,10 @@ pub fn stream_chunks_of_combined_source_map<'a>(
         );
       }
     },
-    &mut |i, source, mut source_content| {
+    &mut |i, source, mut source_content, extras| {
       if source == inner_source_name {
         *inner_source_index.borrow_mut() = i as i64;
+        *inner_source_extras.borrow_mut() = extras;
         let mut inner_source = inner_source.borrow_mut();
         if let Some(inner_source) = inner_source.as_ref() {
           source_content = Some(inner_source);
@@ -1130,10 +1621,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
         }
         source_index_mapping.borrow_mut().insert(i, -2);
         stream_chunks_of_source_map(
-          &MapOptions {
-            columns: options.columns,
-            final_source: false,
-          },
+          &options.inner(false),
           object_pool,
           source_content.unwrap().as_ref(),
           inner_source_map,
@@ -1188,7 +1676,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
             // SAFETY: final_source is false
             data.chunks.push(chunk.unwrap());
           },
-          &mut |i, source, source_content| {
+          &mut |i, source, source_content, extras| {
             inner_source_contents
               .borrow_mut()
               .insert(i, source_content.cloned());
@@ -1198,7 +1686,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
             inner_source_index_mapping.borrow_mut().insert(i, -2);
             inner_source_index_value_mapping
               .borrow_mut()
-              .insert(i, (source, source_content));
+              .insert(i, (source, source_content, extras));
           },
           &mut |i, name| {
             inner_name_index_mapping.borrow_mut().insert(i, -2);
@@ -1211,7 +1699,7 @@ pub fn stream_chunks_of_combined_source_map<'a>(
         if global_index.is_none() {
           let len = source_mapping.len() as u32;
           source_mapping.insert(source.clone(), len);
-          on_source.borrow_mut()(len, source, source_content);
+          on_source.borrow_mut()(len, source, source_content, extras);
           global_index = Some(len);
         }
         source_index_mapping
@@ -1231,22 +1719,30 @@ pub fn stream_and_get_source_and_map<'a>(
   object_pool: &'a ObjectPool,
   chunks: &'a dyn Chunks,
   on_chunk: OnChunk<'_, 'a>,
-  on_source: OnSource<'_, 'a>,
+  on_source: OnSourceWithExtras<'_, 'a>,
   on_name: OnName<'_, 'a>,
+  on_generated_ranges: OnGeneratedRanges<'_, 'a>,
 ) -> (GeneratedInfo, Option<SourceMap>) {
   let mut mappings_encoder = create_encoder(options.columns);
   let mut sources: Vec<String> = Vec::new();
   let mut sources_content: Vec<Arc<str>> = Vec::new();
   let mut names: Vec<String> = Vec::new();
+  let mut function_mappings = FunctionMappingsCollector::default();
+  let scopes = RefCell::new(ScopesCollector::default());
+  let mut ignored: Vec<bool> = Vec::new();
 
-  let generated_info = chunks.stream(
+  let generated_info = stream_chunks_with_extras(
+    chunks,
     object_pool,
     options,
     &mut |chunk, mapping| {
       mappings_encoder.encode(&mapping);
       on_chunk(chunk, mapping);
     },
-    &mut |source_index, source, source_content| {
+    &mut |source_index, source, source_content, extras| {
+      function_mappings.add(source_index, &extras);
+      scopes.borrow_mut().add_source(source_index, &extras);
+      set_ignored(&mut ignored, source_index, extras.ignored);
       let source_index2 = source_index as usize;
       while sources.len() <= source_index2 {
         sources.push("".into());
@@ -1258,7 +1754,7 @@ pub fn stream_and_get_source_and_map<'a>(
         }
         sources_content[source_index2] = source_content.clone();
       }
-      on_source(source_index, source, source_content);
+      on_source(source_index, source, source_content, extras);
     },
     &mut |name_index, name| {
       let name_index2 = name_index as usize;
@@ -1268,13 +1764,25 @@ pub fn stream_and_get_source_and_map<'a>(
       names[name_index2] = name.to_string();
       on_name(name_index, name);
     },
+    &mut |ranges, names| {
+      scopes.borrow_mut().add_ranges(ranges.clone(), names);
+      on_generated_ranges(ranges, names);
+    },
   );
 
   let mappings = mappings_encoder.drain();
   let map = if mappings.is_empty() {
     None
   } else {
-    Some(SourceMap::new(mappings, sources, sources_content, names))
+    let function_mappings = function_mappings.finish(sources.len(), &mut names);
+    let (original_scopes, generated_ranges) =
+      scopes.into_inner().finish(sources.len(), &mut names, Some);
+    let mut map = SourceMap::new(mappings, sources, sources_content, names);
+    map.set_sources_function_mappings(function_mappings);
+    map.set_original_scopes(original_scopes);
+    map.set_generated_ranges(generated_ranges);
+    map.set_ignore_list(ignore_list(&ignored));
+    Some(map)
   };
   (generated_info, map)
 }
@@ -1292,9 +1800,9 @@ mod tests {
     helpers::Token, Mapping, ObjectPool, OriginalLocation, SourceMap,
   };
 
-  const UTF16_SOURCE: &'static str = "var i18n = JSON.parse('{\"魑魅魍魉\":{\"en-US\":\"Evil spirits\",\"zh-CN\":\"魑魅魍魉\"}}');\nvar __webpack_exports___ = i18n[\"魑魅魍魉\"];\nexport { __webpack_exports___ as 魑魅魍魉 };";
+  const UTF16_SOURCE: &str = "var i18n = JSON.parse('{\"魑魅魍魉\":{\"en-US\":\"Evil spirits\",\"zh-CN\":\"魑魅魍魉\"}}');\nvar __webpack_exports___ = i18n[\"魑魅魍魉\"];\nexport { __webpack_exports___ as 魑魅魍魉 };";
 
-  const UTF16_SOURCE_MAP: LazyLock<SourceMap> = LazyLock::new(|| {
+  static UTF16_SOURCE_MAP: LazyLock<SourceMap> = LazyLock::new(|| {
     SourceMap::from_json("{\"version\":3,\"sources\":[\"i18.js\"],\"sourcesContent\":[\"var i18n = JSON.parse('{\\\"魑魅魍魉\\\":{\\\"en-US\\\":\\\"Evil spirits\\\",\\\"zh-CN\\\":\\\"魑魅魍魉\\\"}}');\\nvar __webpack_exports___ = i18n[\\\"魑魅魍魉\\\"];\\nexport { __webpack_exports___ as 魑魅魍魉 };\\n\"],\"names\":[\"i18n\",\"JSON\",\"__webpack_exports___\",\"魑魅魍魉\"],\"mappings\":\"AAAA,IAAIA,OAAOC,KAAK,KAAK,CAAC;AACtB,IAAIC,uBAAuBF,IAAI,CAAC,OAAO;AACvC,SAASE,wBAAwBC,IAAI,GAAG\"}").unwrap()
   });
 
@@ -1313,7 +1821,7 @@ mod tests {
       &mut |chunk, mapping| {
         chunks.push((chunk.unwrap(), mapping));
       },
-      &mut |_i, _source, _source_content| {},
+      &mut |_i, _source, _source_content, _extras| {},
       &mut |_i, _name| {},
     );
 
@@ -1357,7 +1865,7 @@ mod tests {
       source,
       source_map,
       &mut |_chunk, _mapping| {},
-      &mut |_i, _source, _source_content| {},
+      &mut |_i, _source, _source_content, _extras| {},
       &mut |_i, _name| {},
     );
 
@@ -1379,7 +1887,7 @@ mod tests {
       source,
       source_map,
       &mut |_chunk, _mapping| {},
-      &mut |_i, _source, _source_content| {},
+      &mut |_i, _source, _source_content, _extras| {},
       &mut |_i, _name| {},
     );
 
@@ -1401,7 +1909,7 @@ mod tests {
       source,
       source_map,
       &mut |_chunk, _mapping| {},
-      &mut |_i, _source, _source_content| {},
+      &mut |_i, _source, _source_content, _extras| {},
       &mut |_i, _name| {},
     );
 
@@ -2,42 +2,88 @@
 
 mod cached_source;
 mod concat_source;
+#[cfg(feature = "content-hash")]
+mod content_hash;
+#[cfg(feature = "content-hash")]
+mod debug_id;
 mod decoder;
+mod devtool;
 mod encoder;
 mod error;
+mod file_source;
+mod function_mappings;
 mod helpers;
+mod line_index;
 mod linear_map;
 mod object_pool;
 mod original_source;
 mod raw_source;
 mod replace_source;
+mod scopes;
+mod slice_source;
 mod source;
 mod source_content_lines;
+mod source_editor;
+mod source_map_json;
 mod source_map_source;
+mod source_tree;
+mod wasm;
 mod with_utf16;
 
 pub use cached_source::CachedSource;
-pub use concat_source::ConcatSource;
+pub use concat_source::{ChildPosition, ConcatSource, ConcatSourceBuilder};
+#[cfg(feature = "content-hash")]
+pub use content_hash::ContentHashAlgorithm;
+#[cfg(feature = "content-hash")]
+pub use debug_id::{inject_debug_id, DebugId};
+pub use devtool::{CommentStyle, Devtool, DevtoolAsset, DevtoolMode};
 pub use error::{Error, Result};
-pub use original_source::OriginalSource;
+pub use file_source::FileSource;
+#[cfg(feature = "mmap")]
+pub use file_source::MmapSource;
+pub use function_mappings::{
+  decode_function_mappings, encode_function_mappings, FunctionMapping,
+};
+pub use line_index::{ColumnUnit, LineIndex, LinePosition};
+pub use original_source::{OriginalSource, TokenizeFn, Tokenizer};
 pub use raw_source::{RawBufferSource, RawStringSource};
-pub use replace_source::{ReplaceSource, ReplacementEnforce};
+pub use replace_source::{
+  ReplaceSource, ReplaceSourceTransaction, Replacement, ReplacementEnforce,
+  ReplacementId,
+};
+pub use scopes::{
+  decode_generated_ranges, decode_original_scopes, encode_generated_ranges,
+  encode_original_scopes, Binding, BindingSubRange, Callsite, GeneratedRange,
+  OriginalScope, ScopeDefinition,
+};
+pub use slice_source::SliceSource;
 pub use source::{
   BoxSource, MapOptions, Mapping, OriginalLocation, Source, SourceExt,
-  SourceMap, SourceValue,
+  SourceMap, SourcePredicate, SourceValue,
 };
+pub use source_editor::SourceEditor;
+pub use source_map_json::SourceMapJsonOptions;
 pub use source_map_source::{
-  SourceMapSource, SourceMapSourceOptions, WithoutOriginalOptions,
+  LazySourceMap, LazySourceMapSourceOptions, SourceMapLoader, SourceMapSource,
+  SourceMapSourceOptions, WithoutOriginalOptions,
+};
+pub use source_tree::{
+  source_tree, transform_source_tree, walk_source_tree, SourceKind, SourceTree,
+};
+pub use wasm::{
+  read_wasm_url_section, set_wasm_url_section, strip_wasm_custom_section,
+  wasm_custom_sections, WasmCustomSection, WASM_EXTERNAL_DEBUG_INFO,
+  WASM_SOURCE_MAPPING_URL,
 };
 
 /// Reexport `StreamChunks` related types.
 pub mod stream_chunks {
   pub use super::helpers::{
-    stream_chunks_default, Chunks, GeneratedInfo, OnChunk, OnName, OnSource,
-    StreamChunks,
+    stream_chunks_default, Chunks, GeneratedInfo, OnChunk, OnGeneratedRanges,
+    OnName, OnSource, OnSourceExtras, SourceExtras, StreamChunks,
   };
 }
 
 pub use helpers::{decode_mappings, encode_mappings};
 
-pub use object_pool::ObjectPool;
+pub use object_pool::{ObjectPool, ObjectPoolOptions, ObjectPoolStats};
@@ -0,0 +1,294 @@
+use crate::Source;
+
+/// The unit columns are counted in, see [LineIndex].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnUnit {
+  /// UTF-8 bytes.
+  Byte,
+  /// UTF-16 code units, like the generated columns of a
+  /// [SourceMap](crate::SourceMap).
+  Utf16,
+  /// Unicode scalar values.
+  Char,
+}
+
+/// A position in generated code, with a 1-based line and a 0-based column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LinePosition {
+  /// Line, 1-based.
+  pub line: u32,
+  /// Column, 0-based, in the [ColumnUnit] it was requested in.
+  pub column: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+  offset: usize,
+  len: u8,
+}
+
+impl WideChar {
+  fn end(&self) -> usize {
+    self.offset + self.len as usize
+  }
+
+  /// Code units this char takes in `unit`.
+  fn width(&self, unit: ColumnUnit) -> usize {
+    match unit {
+      ColumnUnit::Byte => self.len as usize,
+      ColumnUnit::Utf16 if self.len == 4 => 2,
+      ColumnUnit::Utf16 | ColumnUnit::Char => 1,
+    }
+  }
+}
+
+/// Converts between byte offsets and line/column positions of the code of a
+/// [Source], with columns in bytes, UTF-16 code units or chars.
+///
+/// Only line starts and the positions of non-ASCII chars are stored, so
+/// conversions don't need the content. Byte offsets refer to
+/// [Source::source]. A [CachedSource](crate::CachedSource) builds its index
+/// once, see [CachedSource::line_index](crate::CachedSource::line_index).
+///
+/// ```
+/// use rspack_sources::{
+///   ColumnUnit, LineIndex, LinePosition, RawStringSource,
+/// };
+///
+/// let index = LineIndex::new(&RawStringSource::from_static("a\n🙈b"));
+/// assert_eq!(index.line_count(), 2);
+/// assert_eq!(index.final_column(), 3);
+/// let position = index.position(6, ColumnUnit::Utf16).unwrap();
+/// assert_eq!(position, LinePosition { line: 2, column: 2 });
+/// assert_eq!(index.position(6, ColumnUnit::Char).unwrap().column, 1);
+/// assert_eq!(index.offset(position, ColumnUnit::Utf16), Some(6));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+  line_starts: Vec<usize>,
+  wide_chars: Vec<WideChar>,
+  len: usize,
+}
+
+impl LineIndex {
+  /// Create a [LineIndex] of the code of `source`.
+  pub fn new(source: &dyn Source) -> Self {
+    let mut line_starts = vec![0];
+    let mut wide_chars = Vec::new();
+    let mut len = 0;
+    source.rope(&mut |chunk| {
+      line_starts.extend(
+        memchr::memchr_iter(b'\n', chunk.as_bytes()).map(|i| len + i + 1),
+      );
+      if !chunk.is_ascii() {
+        wide_chars.extend(
+          chunk
+            .char_indices()
+            .filter(|(_, c)| !c.is_ascii())
+            .map(|(i, c)| WideChar {
+              offset: len + i,
+              len: c.len_utf8() as u8,
+            }),
+        );
+      }
+      len += chunk.len();
+    });
+    Self {
+      line_starts,
+      wide_chars,
+      len,
+    }
+  }
+
+  /// Length of the code in bytes.
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Whether the code is empty.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Number of lines, a trailing newline starts an empty last line.
+  pub fn line_count(&self) -> u32 {
+    self.line_starts.len() as u32
+  }
+
+  /// Column after the last char of the code, in UTF-16 code units.
+  /// Together with [LineIndex::line_count] this is the
+  /// [GeneratedInfo](crate::stream_chunks::GeneratedInfo) of the code.
+  pub fn final_column(&self) -> u32 {
+    let line_start = *self.line_starts.last().unwrap_or(&0);
+    self.column(line_start, self.len, ColumnUnit::Utf16) as u32
+  }
+
+  /// Byte range of `line` (1-based), including its newline.
+  pub fn line_range(&self, line: u32) -> Option<std::ops::Range<usize>> {
+    let index = (line as usize).checked_sub(1)?;
+    let start = *self.line_starts.get(index)?;
+    let end = self.line_starts.get(index + 1).copied().unwrap_or(self.len);
+    Some(start..end)
+  }
+
+  /// Position of the byte `offset`, with the column in `unit`. Returns
+  /// `None` if `offset` is past the end or not on a char boundary.
+  pub fn position(
+    &self,
+    offset: usize,
+    unit: ColumnUnit,
+  ) -> Option<LinePosition> {
+    if offset > self.len || self.inside_char(offset) {
+      return None;
+    }
+    let line = self.line_starts.partition_point(|start| *start <= offset);
+    let column = self.column(self.line_starts[line - 1], offset, unit);
+    Some(LinePosition {
+      line: line as u32,
+      column: column as u32,
+    })
+  }
+
+  /// Byte offset of `position`, with the column in `unit`. Returns `None`
+  /// if the line doesn't exist, the column is past the end of the line or
+  /// inside a char.
+  pub fn offset(
+    &self,
+    position: LinePosition,
+    unit: ColumnUnit,
+  ) -> Option<usize> {
+    let range = self.line_range(position.line)?;
+    let mut remaining = position.column as usize;
+    let mut offset = range.start;
+    for wide_char in self.wide_chars_in(range.start, range.end) {
+      let ascii = wide_char.offset - offset;
+      if remaining < ascii {
+        break;
+      }
+      remaining -= ascii;
+      offset = wide_char.offset;
+      if remaining == 0 {
+        return Some(offset);
+      }
+      let width = wide_char.width(unit);
+      if remaining < width {
+        return None;
+      }
+      remaining -= width;
+      offset = wide_char.end();
+    }
+    let offset = offset + remaining;
+    // The newline belongs to its line, the offset after it to the next.
+    let last = if position.line < self.line_count() {
+      range.end - 1
+    } else {
+      range.end
+    };
+    (offset <= last).then_some(offset)
+  }
+
+  /// Convert the column of `position` from unit `from` to unit `to`.
+  pub fn convert(
+    &self,
+    position: LinePosition,
+    from: ColumnUnit,
+    to: ColumnUnit,
+  ) -> Option<LinePosition> {
+    self.position(self.offset(position, from)?, to)
+  }
+
+  fn inside_char(&self, offset: usize) -> bool {
+    let index = self.wide_chars.partition_point(|c| c.offset < offset);
+    index > 0 && self.wide_chars[index - 1].end() > offset
+  }
+
+  fn wide_chars_in(&self, start: usize, end: usize) -> &[WideChar] {
+    let from = self.wide_chars.partition_point(|c| c.offset < start);
+    let to = self.wide_chars.partition_point(|c| c.offset < end);
+    &self.wide_chars[from..to]
+  }
+
+  fn column(
+    &self,
+    line_start: usize,
+    offset: usize,
+    unit: ColumnUnit,
+  ) -> usize {
+    self.wide_chars_in(line_start, offset).iter().fold(
+      offset - line_start,
+      |column, wide_char| {
+        column - wide_char.len as usize + wide_char.width(unit)
+      },
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    helpers::get_generated_source_info, ConcatSource, RawStringSource,
+    SourceExt,
+  };
+
+  use super::*;
+
+  #[test]
+  fn converts_positions() {
+    let source = ConcatSource::new([
+      RawStringSource::from_static("ab\n").boxed(),
+      RawStringSource::from_static("é🙈c\n").boxed(),
+      RawStringSource::from_static("d").boxed(),
+    ]);
+    let index = LineIndex::new(&source);
+    assert_eq!(index.len(), 12);
+    assert_eq!(index.line_range(2), Some(3..11));
+    assert_eq!(index.line_range(4), None);
+
+    let c = 9;
+    assert_eq!(
+      index.position(c, ColumnUnit::Byte),
+      Some(LinePosition { line: 2, column: 6 })
+    );
+    assert_eq!(index.position(c, ColumnUnit::Utf16).unwrap().column, 3);
+    assert_eq!(index.position(c, ColumnUnit::Char).unwrap().column, 2);
+    assert_eq!(index.position(6, ColumnUnit::Byte), None);
+    assert_eq!(index.position(13, ColumnUnit::Byte), None);
+
+    for unit in [ColumnUnit::Byte, ColumnUnit::Utf16, ColumnUnit::Char] {
+      for offset in [0, 2, 3, 5, 9, 10, 11, 12] {
+        let position = index.position(offset, unit).unwrap();
+        assert_eq!(index.offset(position, unit), Some(offset));
+      }
+    }
+    let position = LinePosition { line: 2, column: 2 };
+    assert_eq!(index.offset(position, ColumnUnit::Byte), Some(5));
+    assert_eq!(index.offset(position, ColumnUnit::Utf16), None);
+    assert_eq!(index.offset(position, ColumnUnit::Char), Some(9));
+    assert_eq!(
+      index.convert(position, ColumnUnit::Char, ColumnUnit::Utf16),
+      Some(LinePosition { line: 2, column: 3 })
+    );
+    assert_eq!(
+      index.offset(LinePosition { line: 2, column: 3 }, ColumnUnit::Byte),
+      None
+    );
+    assert_eq!(
+      index.offset(LinePosition { line: 1, column: 3 }, ColumnUnit::Byte),
+      None
+    );
+    assert_eq!(
+      index.offset(LinePosition { line: 3, column: 2 }, ColumnUnit::Byte),
+      None
+    );
+  }
+
+  #[test]
+  fn matches_generated_info() {
+    for code in ["", "a", "a\n", "a\n\nb🙈", "é\n"] {
+      let index = LineIndex::new(&RawStringSource::from(code.to_string()));
+      let info = get_generated_source_info(code);
+      assert_eq!(index.line_count(), info.generated_line);
+      assert_eq!(index.final_column(), info.generated_column);
+    }
+  }
+}
@@ -1,4 +1,10 @@
-use std::{cell::RefCell, collections::BTreeMap};
+use std::{
+  collections::BTreeMap,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, MutexGuard,
+  },
+};
 
 // Vector pooling minimum capacity threshold
 // Recommended threshold: 64
@@ -9,42 +15,220 @@ use std::{cell::RefCell, collections::BTreeMap};
 // 4. Empirical value: 64 is a proven balance point in real projects
 const MIN_POOL_CAPACITY: usize = 64;
 
+const DEFAULT_SHARDS: usize = 8;
+
+type Shard = BTreeMap<usize, Vec<Vec<usize>>>;
+
+/// Limits of an [ObjectPool].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectPoolOptions {
+  /// Number of independently locked shards, each thread prefers its own
+  /// shard so that threads rarely contend. At least one shard is used.
+  pub shards: usize,
+  /// Objects are no longer retained once the pool holds this many bytes.
+  pub max_retained_bytes: usize,
+  /// Objects are no longer retained once the pool holds this many objects.
+  pub max_retained_objects: usize,
+}
+
+impl Default for ObjectPoolOptions {
+  fn default() -> Self {
+    Self {
+      shards: DEFAULT_SHARDS,
+      max_retained_bytes: usize::MAX,
+      max_retained_objects: usize::MAX,
+    }
+  }
+}
+
+/// Statistics of an [ObjectPool], only requests large enough to be pooled
+/// are counted as hits or misses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjectPoolStats {
+  /// Number of pulls served by a retained object.
+  pub hits: usize,
+  /// Number of pulls which needed a new allocation.
+  pub misses: usize,
+  /// Bytes currently retained by the pool.
+  pub retained_bytes: usize,
+  /// Objects currently retained by the pool.
+  pub retained_objects: usize,
+}
+
 /// A memory pool for reusing `T` allocations to reduce memory allocation overhead.
-#[derive(Default, Debug)]
+///
+/// The pool can be shared between threads, e.g. by the workers of a thread pool.
+///
+/// ```
+/// use rspack_sources::{ObjectPool, ObjectPoolOptions};
+///
+/// let pool = ObjectPool::with_options(ObjectPoolOptions {
+///   max_retained_objects: 16,
+///   ..Default::default()
+/// });
+/// drop(pool.pull(128));
+/// drop(pool.pull(100));
+/// assert_eq!(pool.stats().hits, 1);
+/// assert_eq!(pool.stats().misses, 1);
+/// assert_eq!(pool.stats().retained_objects, 1);
+/// ```
+#[derive(Debug)]
 pub struct ObjectPool {
-  objects: RefCell<BTreeMap<usize, Vec<Vec<usize>>>>,
+  shards: Box<[Mutex<Shard>]>,
+  options: ObjectPoolOptions,
+  hits: AtomicUsize,
+  misses: AtomicUsize,
+  retained_bytes: AtomicUsize,
+  retained_objects: AtomicUsize,
+}
+
+impl Default for ObjectPool {
+  fn default() -> Self {
+    Self::with_options(ObjectPoolOptions::default())
+  }
 }
 
 impl ObjectPool {
+  /// Create a [ObjectPool] with the given limits.
+  pub fn with_options(options: ObjectPoolOptions) -> Self {
+    Self {
+      shards: (0..options.shards.max(1))
+        .map(|_| Mutex::default())
+        .collect(),
+      options,
+      hits: AtomicUsize::new(0),
+      misses: AtomicUsize::new(0),
+      retained_bytes: AtomicUsize::new(0),
+      retained_objects: AtomicUsize::new(0),
+    }
+  }
+
   /// Retrieves a reusable `T` from the pool with at least the requested capacity.
   pub fn pull<'a>(&'a self, requested_capacity: usize) -> Pooled<'a> {
-    if requested_capacity < MIN_POOL_CAPACITY
-      || self.objects.borrow().is_empty()
-    {
+    if requested_capacity < MIN_POOL_CAPACITY {
       return Pooled::new(self, Vec::with_capacity(requested_capacity));
     }
-    let mut objects = self.objects.borrow_mut();
-    if let Some((_, bucket)) = objects.range_mut(requested_capacity..).next() {
-      if let Some(mut object) = bucket.pop() {
-        object.clear();
-        return Pooled::new(self, object);
+    if self.retained_objects.load(Ordering::Relaxed) > 0 {
+      let home = self.home_shard();
+      for offset in 0..self.shards.len() {
+        let index = (home + offset) % self.shards.len();
+        let Some(mut shard) = self.lock_shard(index, offset == 0) else {
+          continue;
+        };
+        if let Some(mut object) = take_object(&mut shard, requested_capacity) {
+          drop(shard);
+          self.release(object.capacity());
+          self.hits.fetch_add(1, Ordering::Relaxed);
+          object.clear();
+          return Pooled::new(self, object);
+        }
       }
     }
+    self.misses.fetch_add(1, Ordering::Relaxed);
     Pooled::new(self, Vec::with_capacity(requested_capacity))
   }
 
+  /// Current statistics of the pool.
+  pub fn stats(&self) -> ObjectPoolStats {
+    ObjectPoolStats {
+      hits: self.hits.load(Ordering::Relaxed),
+      misses: self.misses.load(Ordering::Relaxed),
+      retained_bytes: self.retained_bytes.load(Ordering::Relaxed),
+      retained_objects: self.retained_objects.load(Ordering::Relaxed),
+    }
+  }
+
+  /// Drops all retained objects, the hit and miss counters are kept.
+  pub fn clear(&self) {
+    for index in 0..self.shards.len() {
+      if let Some(mut shard) = self.lock_shard(index, true) {
+        for object in std::mem::take(&mut *shard).into_values().flatten() {
+          self.release(object.capacity());
+        }
+      }
+    }
+  }
+
   /// Returns a `T` to the pool for future reuse.
   fn return_to_pool(&self, object: Vec<usize>) {
-    if object.capacity() < MIN_POOL_CAPACITY {
+    let cap = object.capacity();
+    if cap < MIN_POOL_CAPACITY || !self.reserve(cap) {
       return;
     }
-    let mut objects = self.objects.borrow_mut();
-    let cap = object.capacity();
-    let bucket = objects.entry(cap).or_default();
-    bucket.push(object);
+    match self.lock_shard(self.home_shard(), true) {
+      Some(mut shard) => shard.entry(cap).or_default().push(object),
+      None => self.release(cap),
+    }
+  }
+
+  fn home_shard(&self) -> usize {
+    static NEXT_THREAD_INDEX: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+      static THREAD_INDEX: usize =
+        NEXT_THREAD_INDEX.fetch_add(1, Ordering::Relaxed);
+    }
+    THREAD_INDEX.with(|index| *index) % self.shards.len()
+  }
+
+  fn lock_shard(
+    &self,
+    index: usize,
+    block: bool,
+  ) -> Option<MutexGuard<'_, Shard>> {
+    let shard = &self.shards[index];
+    if block {
+      // A panic while holding the lock can't leave a shard inconsistent.
+      Some(shard.lock().unwrap_or_else(|err| err.into_inner()))
+    } else {
+      shard.try_lock().ok()
+    }
+  }
+
+  /// Accounts for an object about to be retained, fails when a limit is hit.
+  fn reserve(&self, capacity: usize) -> bool {
+    let bytes = capacity * std::mem::size_of::<usize>();
+    let reserved = self
+      .retained_objects
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |objects| {
+        (objects < self.options.max_retained_objects).then_some(objects + 1)
+      })
+      .is_ok();
+    if !reserved {
+      return false;
+    }
+    let reserved = self
+      .retained_bytes
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |retained| {
+        retained
+          .checked_add(bytes)
+          .filter(|total| *total <= self.options.max_retained_bytes)
+      })
+      .is_ok();
+    if !reserved {
+      self.retained_objects.fetch_sub(1, Ordering::Relaxed);
+    }
+    reserved
+  }
+
+  fn release(&self, capacity: usize) {
+    let bytes = capacity * std::mem::size_of::<usize>();
+    self.retained_objects.fetch_sub(1, Ordering::Relaxed);
+    self.retained_bytes.fetch_sub(bytes, Ordering::Relaxed);
   }
 }
 
+fn take_object(
+  shard: &mut Shard,
+  requested_capacity: usize,
+) -> Option<Vec<usize>> {
+  let (&cap, bucket) = shard.range_mut(requested_capacity..).next()?;
+  let object = bucket.pop();
+  if bucket.is_empty() {
+    shard.remove(&cap);
+  }
+  object
+}
+
 /// A smart pointer that holds a pooled object and automatically returns it to the pool when dropped.
 ///
 /// `Pooled<T>` implements RAII (Resource Acquisition Is Initialization) pattern to manage
@@ -94,3 +278,60 @@ impl std::ops::DerefMut for Pooled<'_> {
     self.as_mut()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn small_objects_are_not_pooled() {
+    let pool = ObjectPool::default();
+    drop(pool.pull(8));
+    drop(pool.pull(8));
+    assert_eq!(pool.stats(), ObjectPoolStats::default());
+  }
+
+  #[test]
+  fn respects_retention_limits() {
+    let pool = ObjectPool::with_options(ObjectPoolOptions {
+      max_retained_bytes: 200 * std::mem::size_of::<usize>(),
+      ..Default::default()
+    });
+    let objects = [pool.pull(64), pool.pull(64), pool.pull(100)];
+    drop(objects);
+    let stats = pool.stats();
+    assert_eq!(stats.misses, 3);
+    assert_eq!(stats.retained_objects, 2);
+    assert_eq!(stats.retained_bytes, 128 * std::mem::size_of::<usize>());
+
+    pool.clear();
+    assert_eq!(pool.stats().retained_objects, 0);
+    assert_eq!(pool.stats().retained_bytes, 0);
+  }
+
+  #[test]
+  fn can_be_shared_between_threads() {
+    let pool = ObjectPool::with_options(ObjectPoolOptions {
+      shards: 2,
+      ..Default::default()
+    });
+    std::thread::scope(|scope| {
+      for _ in 0..4 {
+        scope.spawn(|| {
+          for _ in 0..100 {
+            let mut object = pool.pull(64);
+            object.push(1);
+          }
+        });
+      }
+    });
+    let stats = pool.stats();
+    assert_eq!(stats.hits + stats.misses, 400);
+    // Every allocated object is retained again once dropped.
+    assert_eq!(stats.retained_objects, stats.misses);
+    assert_eq!(
+      stats.retained_bytes,
+      stats.misses * 64 * std::mem::size_of::<usize>()
+    );
+  }
+}
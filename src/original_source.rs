@@ -4,14 +4,18 @@ use std::{
   sync::Arc,
 };
 
+use rustc_hash::FxHashMap as HashMap;
+
 use crate::{
   helpers::{
-    get_generated_source_info, get_map, split_into_lines,
-    split_into_potential_tokens, Chunks, GeneratedInfo, StreamChunks,
+    css_token_boundaries, get_generated_source_info, get_map,
+    identifier_ranges, js_identifier_ranges, split_into_lines,
+    split_into_potential_tokens, split_into_tokens_at, utf16_len, Chunks,
+    GeneratedInfo, StreamChunks, Token,
   },
   object_pool::ObjectPool,
   source::{Mapping, OriginalLocation},
-  MapOptions, Source, SourceMap, SourceValue,
+  MapOptions, Source, SourceKind, SourceMap, SourceValue,
 };
 
 /// Represents source code, it will create source map for the source code,
@@ -39,6 +43,153 @@ use crate::{
 pub struct OriginalSource {
   value: Arc<str>,
   name: Box<str>,
+  tokenizer: Tokenizer,
+  names: bool,
+}
+
+/// Closure returning the byte offsets where a [Tokenizer::Custom] splits.
+pub type TokenizeFn = Arc<dyn Fn(&str) -> Vec<usize> + Send + Sync>;
+
+/// Decides where [OriginalSource] splits its content into mappings when
+/// columns are requested. Every line always starts a new token.
+///
+/// ```
+/// use rspack_sources::{MapOptions, ObjectPool, OriginalSource, Source, Tokenizer};
+///
+/// let source = OriginalSource::new("a, b { color: red; }", "file.css")
+///   .with_tokenizer(Tokenizer::Css);
+/// assert_eq!(
+///   source.map(&ObjectPool::default(), &MapOptions::default()).unwrap().mappings(),
+///   "AAAA,GAAG,IAAI",
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub enum Tokenizer {
+  /// Split at typical JavaScript statement borders (`;`, `{`, `}`).
+  #[default]
+  JavaScript,
+  /// Split at CSS selector and declaration borders (`,`, `;`, `{`, `}`).
+  Css,
+  /// Split at the start and the end of every identifier.
+  Identifier,
+  /// Map whole lines only.
+  Line,
+  /// Split at the byte offsets returned by the closure, offsets which are
+  /// not on a char boundary are ignored.
+  ///
+  /// Custom tokenizers are told apart by the identity of the closure, also
+  /// in the hash, which therefore only holds within one process.
+  Custom(TokenizeFn),
+}
+
+impl Tokenizer {
+  fn for_each_token<'a>(&self, text: &'a str, on_token: impl FnMut(Token<'a>)) {
+    match self {
+      Tokenizer::JavaScript => {
+        split_into_potential_tokens(text).for_each(on_token)
+      }
+      Tokenizer::Css => split_into_tokens_at(text, css_token_boundaries(text))
+        .for_each(on_token),
+      Tokenizer::Identifier => split_into_tokens_at(
+        text,
+        identifier_ranges(text).flat_map(|(start, end)| [start, end]),
+      )
+      .for_each(on_token),
+      Tokenizer::Line => {
+        split_into_tokens_at(text, std::iter::empty()).for_each(on_token)
+      }
+      Tokenizer::Custom(split) => {
+        let mut boundaries = split(text);
+        boundaries.sort_unstable();
+        split_into_tokens_at(text, boundaries.into_iter()).for_each(on_token)
+      }
+    }
+  }
+}
+
+fn is_reserved_word(word: &str) -> bool {
+  matches!(
+    word,
+    "await"
+      | "break"
+      | "case"
+      | "catch"
+      | "class"
+      | "const"
+      | "continue"
+      | "debugger"
+      | "default"
+      | "delete"
+      | "do"
+      | "else"
+      | "enum"
+      | "export"
+      | "extends"
+      | "false"
+      | "finally"
+      | "for"
+      | "function"
+      | "if"
+      | "import"
+      | "in"
+      | "instanceof"
+      | "let"
+      | "new"
+      | "null"
+      | "return"
+      | "static"
+      | "super"
+      | "switch"
+      | "this"
+      | "throw"
+      | "true"
+      | "try"
+      | "typeof"
+      | "var"
+      | "void"
+      | "while"
+      | "with"
+      | "yield"
+  )
+}
+
+impl PartialEq for Tokenizer {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Tokenizer::Custom(a), Tokenizer::Custom(b)) => Arc::ptr_eq(a, b),
+      _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+    }
+  }
+}
+
+impl Eq for Tokenizer {}
+
+impl Hash for Tokenizer {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    let tag: u8 = match self {
+      Tokenizer::JavaScript => 0,
+      Tokenizer::Css => 1,
+      Tokenizer::Identifier => 2,
+      Tokenizer::Line => 3,
+      Tokenizer::Custom(_) => 4,
+    };
+    tag.hash(state);
+    if let Tokenizer::Custom(split) = self {
+      (Arc::as_ptr(split) as *const () as usize).hash(state);
+    }
+  }
+}
+
+impl std::fmt::Debug for Tokenizer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Tokenizer::JavaScript => write!(f, "Tokenizer::JavaScript"),
+      Tokenizer::Css => write!(f, "Tokenizer::Css"),
+      Tokenizer::Identifier => write!(f, "Tokenizer::Identifier"),
+      Tokenizer::Line => write!(f, "Tokenizer::Line"),
+      Tokenizer::Custom(_) => write!(f, "Tokenizer::Custom(..)"),
+    }
+  }
 }
 
 impl OriginalSource {
@@ -47,8 +198,35 @@ impl OriginalSource {
     Self {
       value: value.into(),
       name: name.into(),
+      tokenizer: Tokenizer::default(),
+      names: false,
     }
   }
+
+  /// Use the given [Tokenizer] to split the content into mappings.
+  pub fn with_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+    self.tokenizer = tokenizer;
+    self
+  }
+
+  /// Emit a mapping at the start of every identifier and record the
+  /// identifier in `names`, reserved words are mapped without a name.
+  /// Identifiers in strings, template literals and comments are skipped.
+  /// Only applies to [Tokenizer::JavaScript], the default.
+  ///
+  /// ```
+  /// use rspack_sources::{MapOptions, ObjectPool, OriginalSource, Source};
+  ///
+  /// let source = OriginalSource::new("var foo = bar(foo);", "file.js")
+  ///   .with_names(true);
+  /// let map = source.map(&ObjectPool::default(), &MapOptions::default()).unwrap();
+  /// assert_eq!(map.names(), ["foo", "bar"]);
+  /// assert_eq!(map.mappings(), "AAAA,GAAG,CAACA,GAAG,GAAGC,GAAG,CAACD,GAAG");
+  /// ```
+  pub fn with_names(mut self, names: bool) -> Self {
+    self.names = names;
+    self
+  }
 }
 
 impl Source for OriginalSource {
@@ -80,6 +258,10 @@ impl Source for OriginalSource {
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     writer.write_all(self.value.as_bytes())
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Original
+  }
 }
 
 impl Hash for OriginalSource {
@@ -87,12 +269,21 @@ impl Hash for OriginalSource {
     "OriginalSource".hash(state);
     self.buffer().hash(state);
     self.name.hash(state);
+    if self.tokenizer != Tokenizer::JavaScript {
+      self.tokenizer.hash(state);
+    }
+    if self.names {
+      self.names.hash(state);
+    }
   }
 }
 
 impl PartialEq for OriginalSource {
   fn eq(&self, other: &Self) -> bool {
-    self.value == other.value && self.name == other.name
+    self.value == other.value
+      && self.name == other.name
+      && self.tokenizer == other.tokenizer
+      && self.names == other.names
   }
 }
 
@@ -107,7 +298,14 @@ impl std::fmt::Debug for OriginalSource {
     writeln!(f, "{indent_str}OriginalSource::new(")?;
     writeln!(f, "{indent_str}  {:?},", self.value)?;
     writeln!(f, "{indent_str}  {:?},", self.name)?;
-    write!(f, "{indent_str}).boxed()")
+    write!(f, "{indent_str})")?;
+    if self.tokenizer != Tokenizer::JavaScript {
+      write!(f, "\n{indent_str}.with_tokenizer({:?})", self.tokenizer)?;
+    }
+    if self.names {
+      write!(f, "\n{indent_str}.with_names(true)")?;
+    }
+    write!(f, ".boxed()")
   }
 }
 
@@ -126,19 +324,20 @@ impl Chunks for OriginalSourceChunks<'_> {
     options: &MapOptions,
     on_chunk: crate::helpers::OnChunk<'_, 'b>,
     on_source: crate::helpers::OnSource<'_, 'b>,
-    _on_name: crate::helpers::OnName<'_, 'b>,
+    on_name: crate::helpers::OnName<'_, 'b>,
   ) -> GeneratedInfo {
     on_source(0, Cow::Borrowed(&self.0.name), Some(&self.0.value));
     if options.columns {
       // With column info we need to read all lines and split them
       let mut line = 1;
       let mut column = 0;
-      for token in split_into_potential_tokens(self.0.value.as_ref()) {
-        let is_end_of_line = token.text.ends_with("\n");
-        if is_end_of_line && token.text.len() == 1 {
+      let value = self.0.value.as_ref();
+      let mut emit = |text: &'b str, utf16_len: usize, name_index| {
+        let is_end_of_line = text.ends_with("\n");
+        if is_end_of_line && text.len() == 1 {
           if !options.final_source {
             on_chunk(
-              Some(token.text),
+              Some(text),
               Mapping {
                 generated_line: line,
                 generated_column: column,
@@ -148,7 +347,7 @@ impl Chunks for OriginalSourceChunks<'_> {
           }
         } else {
           on_chunk(
-            (!options.final_source).then_some(token.text),
+            (!options.final_source).then_some(text),
             Mapping {
               generated_line: line,
               generated_column: column,
@@ -156,7 +355,7 @@ impl Chunks for OriginalSourceChunks<'_> {
                 source_index: 0,
                 original_line: line,
                 original_column: column,
-                name_index: None,
+                name_index,
               }),
             },
           );
@@ -165,8 +364,47 @@ impl Chunks for OriginalSourceChunks<'_> {
           line += 1;
           column = 0;
         } else {
-          column += token.utf16_len as u32;
+          column += utf16_len as u32;
         }
+      };
+      if self.0.names && self.0.tokenizer == Tokenizer::JavaScript {
+        let mut names: HashMap<&'b str, u32> = HashMap::default();
+        let mut identifiers = js_identifier_ranges(value).peekable();
+        self.0.tokenizer.for_each_token(value, |token| {
+          let token_start =
+            token.text.as_ptr() as usize - value.as_ptr() as usize;
+          let token_end = token_start + token.text.len();
+          let mut last = 0;
+          while let Some((start, end)) =
+            identifiers.next_if(|(start, _)| *start < token_end)
+          {
+            // Tokens only split at `;`, `{`, `}` and line breaks, which never
+            // are part of an identifier.
+            let (start, end) = (start - token_start, end - token_start);
+            if start > last {
+              let text = &token.text[last..start];
+              emit(text, utf16_len(text), None);
+            }
+            let name = &token.text[start..end];
+            let name_index = (!is_reserved_word(name)).then(|| {
+              let len = names.len() as u32;
+              *names.entry(name).or_insert_with(|| {
+                on_name(len, Cow::Borrowed(name));
+                len
+              })
+            });
+            emit(name, utf16_len(name), name_index);
+            last = end;
+          }
+          if last < token.text.len() {
+            let text = &token.text[last..];
+            emit(text, utf16_len(text), None);
+          }
+        });
+      } else {
+        self.0.tokenizer.for_each_token(value, |token| {
+          emit(token.text, token.utf16_len, None)
+        });
       }
       GeneratedInfo {
         generated_line: line,
@@ -480,4 +718,195 @@ mod tests {
       ]
     )
   }
+
+  fn token_columns(source: &OriginalSource) -> Vec<(String, u32, u32)> {
+    let mut tokens = vec![];
+    source.stream_chunks().stream(
+      &ObjectPool::default(),
+      &MapOptions::default(),
+      &mut |chunk, mapping| {
+        tokens.push((
+          chunk.unwrap().to_string(),
+          mapping.generated_line,
+          mapping.generated_column,
+        ));
+      },
+      &mut |_, _, _| {},
+      &mut |_, _| {},
+    );
+    tokens
+  }
+
+  fn texts(tokens: &[(String, u32, u32)]) -> Vec<&str> {
+    tokens.iter().map(|(text, _, _)| text.as_str()).collect()
+  }
+
+  #[test]
+  fn should_split_css_at_selectors_and_declarations() {
+    let source =
+      OriginalSource::new("a, b { color: red; margin: 0 }\n.c{}", "a.css")
+        .with_tokenizer(Tokenizer::Css);
+    assert_eq!(
+      texts(&token_columns(&source)),
+      ["a, ", "b { ", "color: red; ", "margin: 0 }\n", ".c{}"]
+    );
+  }
+
+  #[test]
+  fn should_split_at_identifiers() {
+    let source = OriginalSource::new("foo(bar1, 2);\n魑魅 = 1", "a.js")
+      .with_tokenizer(Tokenizer::Identifier);
+    let tokens = token_columns(&source);
+    assert_eq!(
+      texts(&tokens),
+      ["foo", "(", "bar1", ", 2);\n", "魑魅", " = 1"]
+    );
+    assert_eq!(tokens[5], (" = 1".to_string(), 2, 2));
+  }
+
+  #[test]
+  fn should_map_whole_lines() {
+    let source = OriginalSource::new("a; b;\nc { d }", "a.js")
+      .with_tokenizer(Tokenizer::Line);
+    assert_eq!(texts(&token_columns(&source)), ["a; b;\n", "c { d }"]);
+    assert_eq!(
+      source
+        .map(&ObjectPool::default(), &MapOptions::default())
+        .unwrap()
+        .mappings(),
+      "AAAA;AACA"
+    );
+  }
+
+  #[test]
+  fn should_split_with_custom_tokenizer() {
+    let split =
+      |text: &str| text.match_indices('<').map(|(index, _)| index).collect();
+    let source = OriginalSource::new("<div><p>hi</p>\n</div>", "a.html")
+      .with_tokenizer(Tokenizer::Custom(Arc::new(split)));
+    assert_eq!(
+      texts(&token_columns(&source)),
+      ["<div>", "<p>hi", "</p>\n", "</div>"]
+    );
+  }
+
+  #[test]
+  fn should_ignore_invalid_custom_boundaries() {
+    let split = |_: &str| vec![100, 1, 3, 0, 3, 4];
+    let source = OriginalSource::new("魑魅魍魉", "a.txt")
+      .with_tokenizer(Tokenizer::Custom(Arc::new(split)));
+    assert_eq!(texts(&token_columns(&source)), ["魑", "魅魍魉"]);
+  }
+
+  #[test]
+  fn tokenizer_is_part_of_identity() {
+    let js = OriginalSource::new("a;b", "a.js");
+    let line = js.clone().with_tokenizer(Tokenizer::Line);
+    assert_eq!(js, js.clone().with_tokenizer(Tokenizer::JavaScript));
+    assert_ne!(js, line);
+    assert_ne!(format!("{js:?}"), format!("{line:?}"));
+
+    let hash = |source: &OriginalSource| {
+      let mut hasher = rustc_hash::FxHasher::default();
+      source.hash(&mut hasher);
+      hasher.finish()
+    };
+    let custom = |split: fn(&str) -> Vec<usize>| {
+      js.clone()
+        .with_tokenizer(Tokenizer::Custom(Arc::new(split)))
+    };
+    let a = custom(|_| vec![1]);
+    let b = custom(|_| vec![2]);
+    assert_ne!(a, b);
+    assert_ne!(hash(&a), hash(&b));
+    assert_eq!(hash(&a), hash(&a.clone()));
+  }
+
+  #[test]
+  fn should_emit_names_for_identifiers() {
+    let source =
+      OriginalSource::new("function foo(a) {\n  return a + 魑;\n}", "a.js")
+        .with_names(true);
+    let mut chunks = vec![];
+    let mut names = vec![];
+    let object_pool = ObjectPool::default();
+    let handle = source.stream_chunks();
+    handle.stream(
+      &object_pool,
+      &MapOptions::default(),
+      &mut |chunk, mapping| {
+        let name_index = mapping.original.and_then(|o| o.name_index);
+        chunks.push((chunk.unwrap(), mapping.generated_column, name_index));
+      },
+      &mut |_, _, _| {},
+      &mut |index, name| names.push((index, name.into_owned())),
+    );
+    assert_eq!(
+      names,
+      [
+        (0, "foo".to_string()),
+        (1, "a".to_string()),
+        (2, "魑".to_string())
+      ]
+    );
+    assert_eq!(
+      chunks,
+      [
+        ("function", 0, None),
+        (" ", 8, None),
+        ("foo", 9, Some(0)),
+        ("(", 12, None),
+        ("a", 13, Some(1)),
+        (") {\n", 14, None),
+        ("  ", 0, None),
+        ("return", 2, None),
+        (" ", 8, None),
+        ("a", 9, Some(1)),
+        (" + ", 10, None),
+        ("魑", 13, Some(2)),
+        (";\n", 14, None),
+        ("}", 0, None),
+      ]
+    );
+    assert_eq!(source.source().into_string_lossy(), source.value.as_ref());
+  }
+
+  #[test]
+  fn names_skip_strings_and_comments() {
+    let object_pool = ObjectPool::default();
+    let source =
+      OriginalSource::new("\"foo\" /* bar */ + `baz` // qux\n", "a.js")
+        .with_names(true);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert!(map.names().is_empty());
+    let source =
+      OriginalSource::new("x = 1e5 + 'it\\'s' + y;", "a.js").with_names(true);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(map.names(), ["x", "y"]);
+
+    let source = OriginalSource::new("a { color: red; }", "a.css")
+      .with_tokenizer(Tokenizer::Css);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    let named = source.with_names(true);
+    assert_eq!(
+      named.map(&object_pool, &MapOptions::default()).unwrap(),
+      map
+    );
+    assert!(map.names().is_empty());
+  }
+
+  #[test]
+  fn names_are_opt_in() {
+    let source = OriginalSource::new("foo();", "a.js");
+    let map = source
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert!(map.names().is_empty());
+    let named = source.clone().with_names(true);
+    assert_ne!(source, named);
+    let map = named
+      .map(&ObjectPool::default(), &MapOptions::new(false))
+      .unwrap();
+    assert_eq!(map.mappings(), "AAAA");
+  }
 }
@@ -10,7 +10,7 @@ use crate::{
     GeneratedInfo, StreamChunks,
   },
   object_pool::ObjectPool,
-  MapOptions, Source, SourceMap, SourceValue,
+  MapOptions, Source, SourceKind, SourceMap, SourceValue,
 };
 
 /// A string variant of [RawStringSource].
@@ -83,6 +83,10 @@ impl Source for RawStringSource {
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     writer.write_all(self.0.as_bytes())
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::RawString
+  }
 }
 
 impl std::fmt::Debug for RawStringSource {
@@ -229,6 +233,10 @@ impl Source for RawBufferSource {
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     writer.write_all(&self.value)
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::RawBuffer
+  }
 }
 
 impl std::fmt::Debug for RawBufferSource {
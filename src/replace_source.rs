@@ -2,18 +2,23 @@ use std::{
   borrow::Cow,
   cell::RefCell,
   hash::{Hash, Hasher},
-  sync::Arc,
+  sync::{Arc, OnceLock},
 };
 
-use rustc_hash::FxHashMap as HashMap;
+use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-  helpers::{get_map, split_into_lines, Chunks, GeneratedInfo, StreamChunks},
+  helpers::{
+    get_generated_source_info, get_map, split_into_lines,
+    stream_chunks_of_raw_source, stream_chunks_with_extras, Chunks,
+    GeneratedInfo, StreamChunks,
+  },
   linear_map::LinearMap,
   object_pool::ObjectPool,
   source_content_lines::SourceContentLines,
-  BoxSource, MapOptions, Mapping, OriginalLocation, OriginalSource, Source,
-  SourceExt, SourceMap, SourceValue,
+  BoxSource, Error, MapOptions, Mapping, OriginalLocation, OriginalSource,
+  Source, SourceExt, SourceKind, SourceMap, SourceValue,
 };
 
 /// Decorates a Source with replacements and insertions of source code,
@@ -38,10 +43,26 @@ use crate::{
 pub struct ReplaceSource {
   inner: BoxSource,
   replacements: Vec<Replacement>,
+  next_insertion_order: u32,
+  binary: bool,
+  // Lossy string of the replaced bytes, only used in binary mode.
+  binary_value: OnceLock<String>,
 }
 
 /// Enforce replacement order when two replacement start and end are both equal
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+  Debug,
+  Default,
+  Clone,
+  Copy,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  Hash,
+  Serialize,
+  Deserialize,
+)]
 pub enum ReplacementEnforce {
   /// pre
   Pre,
@@ -52,14 +73,29 @@ pub enum ReplacementEnforce {
   Post,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Replacement {
-  start: u32,
-  end: u32,
-  content: String,
-  name: Option<String>,
-  enforce: ReplacementEnforce,
-  insertion_order: u32,
+/// A replacement of a [ReplaceSource], see [ReplaceSource::replacements] and
+/// [ReplaceSource::from_replacements].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Replacement {
+  /// Start of the replaced range in the inner source.
+  pub start: u32,
+  /// End (exclusive) of the replaced range in the inner source.
+  pub end: u32,
+  /// The content inserted in place of `[start, end)`.
+  pub content: String,
+  /// Name associated with the replacement in the generated source map.
+  pub name: Option<String>,
+  /// Order of replacements with equal `start` and `end`.
+  pub enforce: ReplacementEnforce,
+  /// Order in which the replacement was created.
+  pub insertion_order: u32,
+}
+
+impl Replacement {
+  /// The [ReplacementId] of this replacement.
+  pub fn id(&self) -> ReplacementId {
+    ReplacementId(self.insertion_order)
+  }
 }
 
 impl Ord for Replacement {
@@ -79,19 +115,194 @@ impl PartialOrd for Replacement {
   }
 }
 
+/// A handle to a replacement of a [ReplaceSource], returned when the
+/// replacement is created and used to remove it with [ReplaceSource::remove].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReplacementId(u32);
+
 impl ReplaceSource {
   /// Create a [ReplaceSource].
   pub fn new<T: SourceExt>(source: T) -> Self {
     Self {
       inner: source.boxed(),
       replacements: Vec::new(),
+      next_insertion_order: 0,
+      binary: false,
+      binary_value: OnceLock::new(),
+    }
+  }
+
+  /// Apply the replacements to the bytes of the inner source instead of its
+  /// text, so binary sources like a [crate::RawBufferSource] can be patched.
+  ///
+  /// Offsets are byte offsets into [Source::buffer] and the content of a
+  /// replacement is inserted as its UTF-8 bytes. A binary [ReplaceSource]
+  /// has no source map.
+  ///
+  /// Like for a [crate::RawBufferSource], [Source::rope] yields the replaced
+  /// bytes as lossy UTF-8, which is kept for the lifetime of the source. For
+  /// bytes that aren't UTF-8 its length differs from [Source::size].
+  ///
+  /// ```
+  /// use rspack_sources::{MapOptions, ObjectPool, RawBufferSource, ReplaceSource, Source};
+  ///
+  /// let wasm = RawBufferSource::from(b"\0asm[hash]\xff".to_vec());
+  /// let mut source = ReplaceSource::new(wasm).with_binary(true);
+  /// source.replace(4, 10, "a1b2c3", None);
+  ///
+  /// assert_eq!(source.buffer().as_ref(), b"\0asma1b2c3\xff");
+  /// assert_eq!(source.size(), 11);
+  /// assert!(source.map(&ObjectPool::default(), &MapOptions::default()).is_none());
+  /// ```
+  pub fn with_binary(mut self, binary: bool) -> Self {
+    self.binary = binary;
+    self.binary_value.take();
+    self
+  }
+
+  /// Whether the replacements are applied to bytes, see
+  /// [ReplaceSource::with_binary].
+  pub fn is_binary(&self) -> bool {
+    self.binary
+  }
+
+  /// Create a [ReplaceSource] from an inner source and replacements
+  /// previously taken from [ReplaceSource::replacements].
+  ///
+  /// Insertion orders must be unique to tell replacements apart, a
+  /// replacement repeating an earlier insertion order gets a new one after
+  /// all others.
+  ///
+  /// # Errors
+  ///
+  /// Returns [Error::BadReplacements](crate::Error::BadReplacements) when
+  /// the insertion orders leave no room for the orders of new replacements.
+  ///
+  /// ```
+  /// use rspack_sources::{RawStringSource, Replacement, ReplaceSource, Source};
+  ///
+  /// let mut source = ReplaceSource::new(RawStringSource::from("a + b"));
+  /// source.replace(0, 1, "x", None);
+  ///
+  /// let json = serde_json::to_string(source.replacements()).unwrap();
+  /// let replacements: Vec<Replacement> = serde_json::from_str(&json).unwrap();
+  /// let restored = ReplaceSource::from_replacements(
+  ///   RawStringSource::from("a + b"),
+  ///   replacements,
+  /// )
+  /// .unwrap();
+  /// assert_eq!(restored, source);
+  /// ```
+  pub fn from_replacements<T: SourceExt>(
+    source: T,
+    replacements: impl IntoIterator<Item = Replacement>,
+  ) -> crate::Result<Self> {
+    let out_of_orders = || {
+      Error::BadReplacements(
+        "no insertion order left for new replacements".to_string(),
+      )
+    };
+    let mut replacements = replacements.into_iter().collect::<Vec<_>>();
+    let mut next_insertion_order = match replacements
+      .iter()
+      .map(|replacement| replacement.insertion_order)
+      .max()
+    {
+      Some(max) => max.checked_add(1).ok_or_else(out_of_orders)?,
+      None => 0,
+    };
+    let mut seen = HashSet::default();
+    for replacement in &mut replacements {
+      if !seen.insert(replacement.insertion_order) {
+        replacement.insertion_order = next_insertion_order;
+        next_insertion_order = next_insertion_order
+          .checked_add(1)
+          .ok_or_else(out_of_orders)?;
+      }
+    }
+    replacements.sort();
+    Ok(Self {
+      inner: source.boxed(),
+      replacements,
+      next_insertion_order,
+      binary: false,
+      binary_value: OnceLock::new(),
+    })
+  }
+
+  /// Get the replacements, ordered as they are applied.
+  pub fn replacements(&self) -> &[Replacement] {
+    &self.replacements
+  }
+
+  /// Start a [ReplaceSourceTransaction], all replacements created or removed
+  /// through it are rolled back unless it is committed.
+  ///
+  /// ```
+  /// use rspack_sources::{RawStringSource, ReplaceSource, Source};
+  ///
+  /// let mut source = ReplaceSource::new(RawStringSource::from("a + b"));
+  /// source.replace(0, 1, "x", None);
+  ///
+  /// let mut transaction = source.transaction();
+  /// transaction.replace(4, 5, "y", None);
+  /// assert_eq!(transaction.source().into_string_lossy(), "x + y");
+  /// transaction.rollback();
+  ///
+  /// assert_eq!(source.source().into_string_lossy(), "x + b");
+  /// ```
+  pub fn transaction(&mut self) -> ReplaceSourceTransaction<'_> {
+    ReplaceSourceTransaction {
+      start_insertion_order: self.next_insertion_order,
+      source: self,
+      removed: Vec::new(),
+      finished: false,
+    }
+  }
+
+  /// Remove the replacement created with `id`, returns `false` when it does
+  /// not exist (anymore).
+  pub fn remove(&mut self, id: ReplacementId) -> bool {
+    self.take_replacement(id).is_some()
+  }
+
+  fn take_replacement(&mut self, id: ReplacementId) -> Option<Replacement> {
+    let index = self
+      .replacements
+      .iter()
+      .position(|replacement| replacement.insertion_order == id.0)?;
+    self.binary_value.take();
+    Some(self.replacements.remove(index))
+  }
+
+  fn push_replacement(&mut self, replacement: Replacement) {
+    self.binary_value.take();
+    if let Some(last) = self.replacements.last() {
+      let cmp = replacement.cmp(last);
+      if cmp == std::cmp::Ordering::Greater || cmp == std::cmp::Ordering::Equal
+      {
+        self.replacements.push(replacement);
+      } else {
+        let insert_at = self
+          .replacements
+          .binary_search_by(|other| other.cmp(&replacement))
+          .unwrap_or_else(|e| e);
+        self.replacements.insert(insert_at, replacement);
+      }
+    } else {
+      self.replacements.push(replacement);
     }
   }
 }
 
 impl ReplaceSource {
   /// Insert a content at start.
-  pub fn insert(&mut self, start: u32, content: &str, name: Option<&str>) {
+  pub fn insert(
+    &mut self,
+    start: u32,
+    content: &str,
+    name: Option<&str>,
+  ) -> ReplacementId {
     self.replace(start, start, content, name)
   }
 
@@ -102,7 +313,7 @@ impl ReplaceSource {
     content: &str,
     name: Option<&str>,
     enforce: ReplacementEnforce,
-  ) {
+  ) -> ReplacementId {
     self.replace_with_enforce(start, start, content, name, enforce)
   }
 
@@ -113,14 +324,14 @@ impl ReplaceSource {
     end: u32,
     content: &str,
     name: Option<&str>,
-  ) {
+  ) -> ReplacementId {
     self.replace_with_enforce(
       start,
       end,
       content,
       name,
       ReplacementEnforce::Normal,
-    );
+    )
   }
 
   /// Create a replacement with content at `[start, end)`, with ReplacementEnforce.
@@ -131,31 +342,256 @@ impl ReplaceSource {
     content: &str,
     name: Option<&str>,
     enforce: ReplacementEnforce,
-  ) {
-    let replacement = Replacement {
+  ) -> ReplacementId {
+    let insertion_order = self.next_insertion_order;
+    self.next_insertion_order = self
+      .next_insertion_order
+      .checked_add(1)
+      .expect("more than u32::MAX replacements");
+    self.push_replacement(Replacement {
       start,
       end,
       content: content.into(),
       name: name.map(|s| s.into()),
       enforce,
-      insertion_order: self.replacements.len() as u32,
+      insertion_order,
+    });
+    ReplacementId(insertion_order)
+  }
+}
+
+/// A set of tentative changes to a [ReplaceSource], created by
+/// [ReplaceSource::transaction].
+///
+/// Changes are applied to the source immediately, so the intermediate result
+/// can be inspected through [std::ops::Deref]. Unless [Self::commit] is
+/// called, dropping the transaction restores the replacements exactly as they
+/// were when it started, including their insertion order.
+pub struct ReplaceSourceTransaction<'a> {
+  source: &'a mut ReplaceSource,
+  start_insertion_order: u32,
+  // Replacements created before the transaction and removed through it.
+  removed: Vec<Replacement>,
+  finished: bool,
+}
+
+impl ReplaceSourceTransaction<'_> {
+  /// Insert a content at start, see [ReplaceSource::insert].
+  pub fn insert(
+    &mut self,
+    start: u32,
+    content: &str,
+    name: Option<&str>,
+  ) -> ReplacementId {
+    self.source.insert(start, content, name)
+  }
+
+  /// Insert a content at start, with ReplacementEnforce, see
+  /// [ReplaceSource::insert_with_enforce].
+  pub fn insert_with_enforce(
+    &mut self,
+    start: u32,
+    content: &str,
+    name: Option<&str>,
+    enforce: ReplacementEnforce,
+  ) -> ReplacementId {
+    self
+      .source
+      .insert_with_enforce(start, content, name, enforce)
+  }
+
+  /// Create a replacement with content at `[start, end)`, see
+  /// [ReplaceSource::replace].
+  pub fn replace(
+    &mut self,
+    start: u32,
+    end: u32,
+    content: &str,
+    name: Option<&str>,
+  ) -> ReplacementId {
+    self.source.replace(start, end, content, name)
+  }
+
+  /// Create a replacement with content at `[start, end)`, with
+  /// ReplacementEnforce, see [ReplaceSource::replace_with_enforce].
+  pub fn replace_with_enforce(
+    &mut self,
+    start: u32,
+    end: u32,
+    content: &str,
+    name: Option<&str>,
+    enforce: ReplacementEnforce,
+  ) -> ReplacementId {
+    self
+      .source
+      .replace_with_enforce(start, end, content, name, enforce)
+  }
+
+  /// Remove the replacement created with `id`, see [ReplaceSource::remove].
+  pub fn remove(&mut self, id: ReplacementId) -> bool {
+    let Some(replacement) = self.source.take_replacement(id) else {
+      return false;
     };
+    if replacement.insertion_order < self.start_insertion_order {
+      self.removed.push(replacement);
+    }
+    true
+  }
 
-    if let Some(last) = self.replacements.last() {
-      let cmp = replacement.cmp(last);
-      if cmp == std::cmp::Ordering::Greater || cmp == std::cmp::Ordering::Equal
-      {
-        self.replacements.push(replacement);
-      } else {
-        let insert_at = self
-          .replacements
-          .binary_search_by(|other| other.cmp(&replacement))
-          .unwrap_or_else(|e| e);
-        self.replacements.insert(insert_at, replacement);
+  /// Keep all changes made in this transaction.
+  pub fn commit(mut self) {
+    self.finished = true;
+  }
+
+  /// Discard all changes made in this transaction.
+  pub fn rollback(self) {}
+}
+
+impl std::ops::Deref for ReplaceSourceTransaction<'_> {
+  type Target = ReplaceSource;
+
+  fn deref(&self) -> &Self::Target {
+    self.source
+  }
+}
+
+impl Drop for ReplaceSourceTransaction<'_> {
+  fn drop(&mut self) {
+    if self.finished {
+      return;
+    }
+    let start_insertion_order = self.start_insertion_order;
+    self.source.binary_value.take();
+    self.source.replacements.retain(|replacement| {
+      replacement.insertion_order < start_insertion_order
+    });
+    for replacement in self.removed.drain(..) {
+      self.source.push_replacement(replacement);
+    }
+    // `next_insertion_order` is not reset, so ids handed out inside the
+    // transaction never refer to a later replacement.
+  }
+}
+
+impl ReplaceSource {
+  fn write_binary(
+    &self,
+    on_chunk: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+  ) -> std::io::Result<()> {
+    struct ReplaceWriter<'a, 'b> {
+      replacer: BinaryReplacer<'a>,
+      on_chunk: &'b mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+    }
+
+    impl std::io::Write for ReplaceWriter<'_, '_> {
+      fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.replacer.push(buf, self.on_chunk)?;
+        Ok(buf.len())
+      }
+
+      fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
       }
-    } else {
-      self.replacements.push(replacement);
     }
+
+    let mut writer = ReplaceWriter {
+      replacer: BinaryReplacer::new(&self.replacements),
+      on_chunk,
+    };
+    self.inner.to_writer(&mut writer)?;
+    writer.replacer.finish(writer.on_chunk)
+  }
+
+  // The replaced bytes of the inner buffer, reading the buffer can't fail
+  // unlike writing the inner source.
+  fn binary_bytes(&self) -> Vec<u8> {
+    let inner = self.inner.buffer();
+    let mut bytes = Vec::with_capacity(self.size());
+    let mut on_chunk = |chunk: &[u8]| {
+      bytes.extend_from_slice(chunk);
+      Ok(())
+    };
+    let mut replacer = BinaryReplacer::new(&self.replacements);
+    // Only `on_chunk` can fail, and it never does.
+    let _ = replacer
+      .push(&inner, &mut on_chunk)
+      .and_then(|()| replacer.finish(&mut on_chunk));
+    bytes
+  }
+
+  fn binary_value(&self) -> &str {
+    self.binary_value.get_or_init(|| {
+      String::from_utf8(self.binary_bytes()).unwrap_or_else(|err| {
+        String::from_utf8_lossy(err.as_bytes()).into_owned()
+      })
+    })
+  }
+}
+
+/// Applies sorted replacements to a stream of bytes, the same way
+/// [ReplaceSource::rope] does for text.
+struct BinaryReplacer<'a> {
+  replacements: &'a [Replacement],
+  next: usize,
+  pos: usize,
+  skip_until: usize,
+}
+
+impl<'a> BinaryReplacer<'a> {
+  fn new(replacements: &'a [Replacement]) -> Self {
+    Self {
+      replacements,
+      next: 0,
+      pos: 0,
+      skip_until: 0,
+    }
+  }
+
+  fn push(
+    &mut self,
+    mut chunk: &[u8],
+    on_chunk: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+  ) -> std::io::Result<()> {
+    let end_pos = self.pos + chunk.len();
+    loop {
+      // Skip over what has been replaced
+      if self.skip_until > self.pos {
+        let skip = (self.skip_until - self.pos).min(chunk.len());
+        chunk = &chunk[skip..];
+        self.pos += skip;
+      }
+      let Some(replacement) = self
+        .replacements
+        .get(self.next)
+        .filter(|replacement| (replacement.start as usize) < end_pos)
+      else {
+        break;
+      };
+      let start = (replacement.start as usize).max(self.pos);
+      if start > self.pos {
+        on_chunk(&chunk[..start - self.pos])?;
+        chunk = &chunk[start - self.pos..];
+        self.pos = start;
+      }
+      on_chunk(replacement.content.as_bytes())?;
+      self.skip_until = self.skip_until.max(replacement.end as usize);
+      self.next += 1;
+    }
+    if !chunk.is_empty() {
+      on_chunk(chunk)?;
+    }
+    self.pos = end_pos;
+    Ok(())
+  }
+
+  fn finish(
+    self,
+    on_chunk: &mut dyn FnMut(&[u8]) -> std::io::Result<()>,
+  ) -> std::io::Result<()> {
+    for replacement in &self.replacements[self.next..] {
+      on_chunk(replacement.content.as_bytes())?;
+    }
+    Ok(())
   }
 }
 
@@ -164,6 +600,9 @@ impl Source for ReplaceSource {
     if self.replacements.is_empty() {
       return self.inner.source();
     }
+    if self.binary {
+      return SourceValue::Buffer(Cow::Owned(self.binary_bytes()));
+    }
 
     let mut string = String::with_capacity(self.size());
     self.rope(&mut |chunk| {
@@ -177,6 +616,9 @@ impl Source for ReplaceSource {
     if self.replacements.is_empty() {
       return self.inner.rope(on_chunk);
     }
+    if self.binary {
+      return on_chunk(self.binary_value());
+    }
 
     let mut pos: usize = 0;
     let mut replacement_idx: usize = 0;
@@ -273,6 +715,9 @@ impl Source for ReplaceSource {
   }
 
   fn buffer(&self) -> Cow<'_, [u8]> {
+    if self.binary && !self.replacements.is_empty() {
+      return Cow::Owned(self.binary_bytes());
+    }
     self.source().into_bytes()
   }
 
@@ -318,18 +763,24 @@ impl Source for ReplaceSource {
 
   fn map(
     &self,
-    _: &ObjectPool,
+    object_pool: &ObjectPool,
     options: &crate::MapOptions,
   ) -> Option<SourceMap> {
+    if self.binary {
+      return None;
+    }
     let replacements = &self.replacements;
     if replacements.is_empty() {
-      return self.inner.map(&ObjectPool::default(), options);
+      return self.inner.map(object_pool, options);
     }
     let chunks = self.stream_chunks();
-    get_map(&ObjectPool::default(), chunks.as_ref(), options)
+    get_map(object_pool, chunks.as_ref(), options)
   }
 
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+    if self.binary {
+      return self.write_binary(&mut |chunk| writer.write_all(chunk));
+    }
     let mut result = Ok(());
     self.rope(&mut |chunk| {
       if result.is_err() {
@@ -339,6 +790,24 @@ impl Source for ReplaceSource {
     });
     result
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Replace
+  }
+
+  fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+    on_child(&self.inner);
+  }
+
+  fn with_children(&self, children: Vec<BoxSource>) -> Option<BoxSource> {
+    let [inner] = <[BoxSource; 1]>::try_from(children).ok()?;
+    Some(
+      ReplaceSource::from_replacements(inner, self.replacements().to_vec())
+        .ok()?
+        .with_binary(self.is_binary())
+        .boxed(),
+    )
+  }
 }
 
 impl std::fmt::Debug for ReplaceSource {
@@ -352,7 +821,11 @@ impl std::fmt::Debug for ReplaceSource {
     writeln!(f, "{indent_str}{{")?;
     writeln!(f, "{indent_str}  let mut source = ReplaceSource::new(")?;
     writeln!(f, "{:indent$?}", &self.inner, indent = indent + 4)?;
-    writeln!(f, "{indent_str}  );")?;
+    if self.binary {
+      writeln!(f, "{indent_str}  ).with_binary(true);")?;
+    } else {
+      writeln!(f, "{indent_str}  );")?;
+    }
     for repl in self.replacements.iter() {
       match repl.enforce {
         ReplacementEnforce::Pre => {
@@ -429,6 +902,29 @@ impl Chunks for ReplaceSourceChunks<'_> {
     on_chunk: crate::helpers::OnChunk<'_, 'a>,
     on_source: crate::helpers::OnSource<'_, 'a>,
     on_name: crate::helpers::OnName<'_, 'a>,
+  ) -> crate::helpers::GeneratedInfo {
+    self.stream_with_extras(
+      object_pool,
+      options,
+      on_chunk,
+      on_source,
+      on_name,
+      &mut |_, _| {},
+      &mut |_, _| {},
+    )
+  }
+
+  fn stream_with_extras<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'a>,
+    // Replacements move the generated code, the ranges of the inner source
+    // are dropped.
+    _on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'a>,
   ) -> crate::helpers::GeneratedInfo {
     let on_name = RefCell::new(on_name);
     let repls = &self.replacements;
@@ -501,12 +997,10 @@ impl Chunks for ReplaceSourceChunks<'_> {
         }
       };
 
-    let result = self.chunks.stream(
+    let result = stream_chunks_with_extras(
+      self.chunks.as_ref(),
       object_pool,
-      &MapOptions {
-        columns: options.columns,
-        final_source: false,
-      },
+      &options.inner(false),
       &mut |chunk, mut mapping| {
         // SAFETY: final_source is false in ReplaceSource
         let chunk = chunk.unwrap();
@@ -784,11 +1278,12 @@ impl Chunks for ReplaceSourceChunks<'_> {
         }
         pos = end_pos;
       },
-      &mut |source_index, source, source_content| {
+      &mut |source_index, source, source_content, extras| {
         let mut source_content_lines = source_content_lines.borrow_mut();
         let lines = source_content
           .map(|source_content| SourceContent::Raw(source_content.clone()));
         source_content_lines.insert(source_index, lines);
+        on_source_extras(source_index, extras);
         on_source(source_index, source, source_content);
       },
       &mut |name_index, name| {
@@ -804,6 +1299,7 @@ impl Chunks for ReplaceSourceChunks<'_> {
           .borrow_mut()
           .insert(name_index, global_index.unwrap());
       },
+      &mut |_, _| {},
     );
 
     // Handle remaining replacements one by one
@@ -840,7 +1336,6 @@ impl Chunks for ReplaceSourceChunks<'_> {
           }
         } else {
           // Line ends with newline or not the last line
-          generated_line_offset += 1;
           line += 1;
           generated_column_offset = -(result.generated_column as i64);
           generated_column_offset_line = line;
@@ -862,8 +1357,31 @@ impl Chunks for ReplaceSourceChunks<'_> {
   }
 }
 
+struct BinaryReplaceSourceChunks<'a>(&'a ReplaceSource);
+
+impl Chunks for BinaryReplaceSourceChunks<'_> {
+  fn stream<'a>(
+    &'a self,
+    _object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+  ) -> GeneratedInfo {
+    let code = self.0.binary_value();
+    if options.final_source {
+      get_generated_source_info(code)
+    } else {
+      stream_chunks_of_raw_source(code, options, on_chunk, on_source, on_name)
+    }
+  }
+}
+
 impl StreamChunks for ReplaceSource {
   fn stream_chunks<'a>(&'a self) -> Box<dyn Chunks + 'a> {
+    if self.binary {
+      return Box::new(BinaryReplaceSourceChunks(self));
+    }
     Box::new(ReplaceSourceChunks::new(self))
   }
 }
@@ -873,6 +1391,9 @@ impl Clone for ReplaceSource {
     Self {
       inner: self.inner.clone(),
       replacements: self.replacements.clone(),
+      next_insertion_order: self.next_insertion_order,
+      binary: self.binary,
+      binary_value: self.binary_value.clone(),
     }
   }
 }
@@ -889,6 +1410,9 @@ impl Hash for ReplaceSource {
       repl.name.hash(state);
     }
     self.inner.hash(state);
+    if self.binary {
+      self.binary.hash(state);
+    }
   }
 }
 
@@ -896,6 +1420,7 @@ impl PartialEq for ReplaceSource {
   fn eq(&self, other: &Self) -> bool {
     self.inner.as_ref() == other.inner.as_ref()
       && self.replacements == other.replacements
+      && self.binary == other.binary
   }
 }
 
@@ -906,8 +1431,9 @@ mod tests {
   use rustc_hash::FxHasher;
 
   use crate::{
-    source_map_source::WithoutOriginalOptions, OriginalSource, RawStringSource,
-    ReplacementEnforce, SourceExt, SourceMapSource, SourceMapSourceOptions,
+    source_map_source::WithoutOriginalOptions, ConcatSource, OriginalSource,
+    RawBufferSource, RawStringSource, ReplacementEnforce, SourceExt,
+    SourceMapSource, SourceMapSourceOptions,
   };
 
   use super::*;
@@ -1653,4 +2179,242 @@ return <div>{data.foo}</div>
       ]
     );
   }
+
+  #[test]
+  fn remove_replacement_by_id() {
+    let mut source =
+      ReplaceSource::new(RawStringSource::from_static("hello world").boxed());
+    let hello = source.replace(0, 5, "bye", None);
+    source.replace(6, 11, "moon", None);
+    assert_eq!(source.source().into_string_lossy(), "bye moon");
+
+    assert!(source.remove(hello));
+    assert!(!source.remove(hello));
+    assert_eq!(source.source().into_string_lossy(), "hello moon");
+  }
+
+  #[test]
+  fn transaction_rollback_restores_replacements() {
+    let mut source =
+      ReplaceSource::new(RawStringSource::from_static("a;b;c").boxed());
+    let a = source.replace(0, 1, "x", None);
+    source.insert(2, "1", None);
+    source.insert(2, "2", None);
+    let original = source.clone();
+
+    let mut transaction = source.transaction();
+    assert!(transaction.remove(a));
+    transaction.insert(2, "3", None);
+    transaction.replace(4, 5, "z", None);
+    assert_eq!(transaction.source().into_string_lossy(), "a;123b;z");
+    drop(transaction);
+
+    assert_eq!(source, original);
+    assert_eq!(source.source().into_string_lossy(), "x;12b;c");
+
+    // later insertions are still ordered after the restored ones
+    source.insert(2, "4", None);
+    assert_eq!(source.source().into_string_lossy(), "x;124b;c");
+  }
+
+  #[test]
+  fn transaction_rollback_does_not_reuse_ids() {
+    let mut source =
+      ReplaceSource::new(RawStringSource::from_static("a;b;c").boxed());
+    let mut transaction = source.transaction();
+    let stale = transaction.replace(0, 1, "x", None);
+    transaction.rollback();
+
+    source.replace(4, 5, "y", None);
+    assert!(!source.remove(stale));
+    assert_eq!(source.source().into_string_lossy(), "a;b;y");
+  }
+
+  #[test]
+  fn transaction_commit_keeps_replacements() {
+    let mut source =
+      ReplaceSource::new(RawStringSource::from_static("a;b;c").boxed());
+    let a = source.replace(0, 1, "x", None);
+
+    let mut transaction = source.transaction();
+    let c = transaction.replace(4, 5, "z", None);
+    transaction.remove(a);
+    transaction.commit();
+    assert_eq!(source.source().into_string_lossy(), "a;b;z");
+
+    let mut transaction = source.transaction();
+    transaction.remove(c);
+    transaction.rollback();
+    assert_eq!(source.source().into_string_lossy(), "a;b;z");
+  }
+
+  #[test]
+  fn rebuild_from_serialized_replacements() {
+    let inner = OriginalSource::new("export default foo;aaa", "file.js");
+    let mut source = ReplaceSource::new(inner.clone());
+    source.replace(15, 18, "bar", Some("foo"));
+    source.replace_with_enforce(18, 19, ");", None, ReplacementEnforce::Post);
+    source.replace(18, 19, "))", None);
+    source.insert(0, "/* banner */", None);
+
+    let json = serde_json::to_string(source.replacements()).unwrap();
+    let replacements: Vec<Replacement> = serde_json::from_str(&json).unwrap();
+    let mut restored =
+      ReplaceSource::from_replacements(inner, replacements).unwrap();
+
+    assert_eq!(restored, source);
+    assert_eq!(restored.source(), source.source());
+    assert_eq!(
+      restored.map(&ObjectPool::default(), &MapOptions::default()),
+      source.map(&ObjectPool::default(), &MapOptions::default())
+    );
+    let mut hasher1 = FxHasher::default();
+    source.hash(&mut hasher1);
+    let mut hasher2 = FxHasher::default();
+    restored.hash(&mut hasher2);
+    assert_eq!(hasher1.finish(), hasher2.finish());
+
+    // new replacements are ordered after the restored ones
+    restored.insert(0, "/* another */", None);
+    assert_eq!(
+      restored.source().into_string_lossy(),
+      "/* banner *//* another */export default bar)));aaa"
+    );
+  }
+
+  #[test]
+  fn rebuild_renumbers_duplicate_insertion_orders() {
+    let mut source =
+      ReplaceSource::new(RawStringSource::from_static("a;b;c").boxed());
+    source.replace(0, 1, "x", None);
+    source.replace(4, 5, "z", None);
+    let mut replacements = source.replacements().to_vec();
+    replacements[1].insertion_order = replacements[0].insertion_order;
+
+    let json = serde_json::to_string(&replacements).unwrap();
+    let replacements: Vec<Replacement> = serde_json::from_str(&json).unwrap();
+    let mut restored = ReplaceSource::from_replacements(
+      RawStringSource::from_static("a;b;c"),
+      replacements,
+    )
+    .unwrap();
+    assert_eq!(restored.source().into_string_lossy(), "x;b;z");
+    let ids = restored
+      .replacements()
+      .iter()
+      .map(Replacement::id)
+      .collect::<Vec<_>>();
+    assert_ne!(ids[0], ids[1]);
+
+    assert!(restored.remove(ids[1]));
+    assert_eq!(restored.source().into_string_lossy(), "x;b;c");
+    let y = restored.replace(2, 3, "y", None);
+    assert!(!ids.contains(&y));
+    assert!(restored.remove(ids[0]));
+    assert_eq!(restored.source().into_string_lossy(), "a;y;c");
+
+    // No order is left after u32::MAX.
+    let mut replacements = source.replacements().to_vec();
+    replacements[1].insertion_order = u32::MAX;
+    assert!(matches!(
+      ReplaceSource::from_replacements(
+        RawStringSource::from_static("a;b;c"),
+        replacements.clone(),
+      ),
+      Err(crate::Error::BadReplacements(_))
+    ));
+    replacements[0].insertion_order = u32::MAX - 1;
+    replacements[1].insertion_order = u32::MAX - 1;
+    assert!(ReplaceSource::from_replacements(
+      RawStringSource::from_static("a;b;c"),
+      replacements,
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn map_uses_the_given_object_pool() {
+    let code =
+      "const 魑魅魍魉 = '魑魅魍魉魑魅魍魉魑魅魍魉魑魅魍魉魑魅魍魉魑魅魍魉';";
+    let source_map = OriginalSource::new(code, "a.js")
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    let mut source =
+      ReplaceSource::new(SourceMapSource::new(WithoutOriginalOptions {
+        value: code,
+        name: "a.js",
+        source_map,
+      }));
+    source.replace(0, 5, "let", None);
+
+    let object_pool = ObjectPool::default();
+    source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert!(object_pool.stats().misses > 0);
+  }
+
+  #[test]
+  fn binary_mode_patches_bytes_across_chunks() {
+    let inner = ConcatSource::new([
+      RawBufferSource::from(vec![0xff, b'[', b'h']).boxed(),
+      RawBufferSource::from(b"ash]\xfe".to_vec()).boxed(),
+    ]);
+    let mut source = ReplaceSource::new(inner).with_binary(true);
+    source.insert(0, "<", None);
+    source.replace(1, 7, "1234", None);
+    source.insert(8, ">", None);
+
+    let expected = b"<\xff1234\xfe>";
+    assert_eq!(source.buffer().as_ref(), expected);
+    assert_eq!(source.source().into_bytes().as_ref(), expected);
+    assert_eq!(source.size(), expected.len());
+    let mut written = vec![];
+    source.to_writer(&mut written).unwrap();
+    assert_eq!(written, expected);
+    assert_eq!(
+      source.source().into_string_lossy(),
+      String::from_utf8_lossy(expected)
+    );
+    let mut rope = String::new();
+    source.rope(&mut |chunk| rope.push_str(chunk));
+    assert_eq!(rope, String::from_utf8_lossy(expected));
+    assert_ne!(rope.len(), source.size());
+    assert!(source
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .is_none());
+  }
+
+  #[test]
+  fn binary_mode_matches_text_mode_for_text() {
+    let code = "hello world\nfoo bar baz";
+    let mut text = ReplaceSource::new(RawStringSource::from(code));
+    text.replace(0, 5, "bye", None);
+    text.replace(3, 8, "overlap", None);
+    text.insert(12, "// ", None);
+    text.replace(16, 19, "", None);
+    text.insert(999, "!", None);
+    let binary = text.clone().with_binary(true);
+
+    assert_eq!(binary.buffer(), text.buffer());
+    assert_eq!(binary.size(), text.size());
+    assert_ne!(binary, text);
+
+    let mut text_hasher = FxHasher::default();
+    text.hash(&mut text_hasher);
+    let mut binary_hasher = FxHasher::default();
+    binary.hash(&mut binary_hasher);
+    assert_ne!(text_hasher.finish(), binary_hasher.finish());
+  }
+
+  #[test]
+  fn binary_mode_refreshes_after_mutation() {
+    let mut source = ReplaceSource::new(RawBufferSource::from(b"abc".to_vec()))
+      .with_binary(true);
+    let id = source.replace(0, 1, "x", None);
+    assert_eq!(source.source().into_string_lossy(), "xbc");
+    source.remove(id);
+    source.replace(2, 3, "z", None);
+    let mut rope = String::new();
+    source.rope(&mut |chunk| rope.push_str(chunk));
+    assert_eq!(rope, "abz");
+  }
 }
@@ -0,0 +1,916 @@
+use rustc_hash::FxHashMap as HashMap;
+
+use crate::{
+  encoder::encode_vlq, function_mappings::base64_value, helpers::SourceExtras,
+};
+
+/// A scope of an original source, as described by the `originalScopes` field
+/// of the source map scopes proposal.
+///
+/// Lines and columns are zero-based positions in the original source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OriginalScope {
+  /// Start line.
+  pub start_line: u32,
+  /// Start column.
+  pub start_column: u32,
+  /// End line.
+  pub end_line: u32,
+  /// End column.
+  pub end_column: u32,
+  /// Index of the kind of the scope, such as `function`, in the `names` field.
+  pub kind_index: u32,
+  /// Index of the name of the scope in the `names` field.
+  pub name_index: Option<u32>,
+  /// Whether the scope is a function whose calls show up in stack traces.
+  pub is_stack_frame: bool,
+  /// Whether the scope is hidden from debuggers.
+  pub is_hidden: bool,
+  /// Indices of the variables declared in the scope in the `names` field.
+  pub variables: Vec<u32>,
+  /// The scopes nested in this scope.
+  pub children: Vec<OriginalScope>,
+}
+
+/// A range of the generated code, as described by the `generatedRanges`
+/// field of the source map scopes proposal.
+///
+/// Lines and columns are zero-based positions in the generated code.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GeneratedRange {
+  /// Start line.
+  pub start_line: u32,
+  /// Start column.
+  pub start_column: u32,
+  /// End line.
+  pub end_line: u32,
+  /// End column.
+  pub end_column: u32,
+  /// The original scope the range was generated from.
+  pub definition: Option<ScopeDefinition>,
+  /// The original position the range was inlined at.
+  pub callsite: Option<Callsite>,
+  /// Whether the range is a function whose calls show up in stack traces.
+  pub is_stack_frame: bool,
+  /// Whether the range is hidden from debuggers.
+  pub is_hidden: bool,
+  /// The values of the variables of the definition, in the same order.
+  pub bindings: Vec<Binding>,
+  /// The ranges nested in this range.
+  pub children: Vec<GeneratedRange>,
+}
+
+/// Refers to an [OriginalScope] from a [GeneratedRange].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeDefinition {
+  /// Index of the source in the `sources` field.
+  pub source_index: u32,
+  /// Index of the scope in the scopes of the source, in the order they start.
+  pub scope_index: u32,
+}
+
+/// The original position a [GeneratedRange] was inlined at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Callsite {
+  /// Index of the source in the `sources` field.
+  pub source_index: u32,
+  /// Line in the original source.
+  pub line: u32,
+  /// Column in the original source.
+  pub column: u32,
+}
+
+/// The value of a variable in a [GeneratedRange].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+  /// Index of the expression holding the value in the `names` field, `None`
+  /// when the value is not available.
+  pub name_index: Option<u32>,
+  /// Changes of the expression within the range, ordered by position.
+  pub sub_ranges: Vec<BindingSubRange>,
+}
+
+/// The expression holding the value of a variable from a position on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BindingSubRange {
+  /// Line in the generated code.
+  pub line: u32,
+  /// Column in the generated code.
+  pub column: u32,
+  /// Index of the expression in the `names` field, `None` when the value is
+  /// not available.
+  pub name_index: Option<u32>,
+}
+
+const ORIGINAL_HAS_NAME: i64 = 0x1;
+const ORIGINAL_IS_STACK_FRAME: i64 = 0x2;
+const ORIGINAL_IS_HIDDEN: i64 = 0x4;
+
+const GENERATED_HAS_DEFINITION: i64 = 0x1;
+const GENERATED_HAS_CALLSITE: i64 = 0x2;
+const GENERATED_IS_STACK_FRAME: i64 = 0x4;
+const GENERATED_IS_HIDDEN: i64 = 0x8;
+
+struct VlqReader<'a> {
+  bytes: &'a [u8],
+  index: usize,
+}
+
+impl VlqReader<'_> {
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.index).copied()
+  }
+
+  fn at_separator(&self) -> bool {
+    matches!(self.peek(), None | Some(b',' | b';'))
+  }
+
+  fn next(&mut self) -> Option<i64> {
+    let mut value = 0i64;
+    let mut shift = 0;
+    loop {
+      if self.at_separator() || shift > 32 {
+        return None;
+      }
+      let digit = base64_value(self.bytes[self.index])? as i64;
+      self.index += 1;
+      value += (digit & 0b11111) << shift;
+      if digit & 0b100000 == 0 {
+        break;
+      }
+      shift += 5;
+    }
+    Some(if value & 1 == 1 {
+      -(value >> 1)
+    } else {
+      value >> 1
+    })
+  }
+
+  fn next_u32(&mut self) -> Option<u32> {
+    u32::try_from(self.next()?).ok()
+  }
+
+  // A name index, `-1` stands for no name.
+  fn next_name(&mut self) -> Option<Option<u32>> {
+    match self.next()? {
+      -1 => Some(None),
+      index => Some(Some(u32::try_from(index).ok()?)),
+    }
+  }
+}
+
+/// Decode one entry of `originalScopes`.
+///
+/// Scopes are encoded by their start and end, separated by `,`. Both start
+/// with the line, relative to the previous start or end, and the column. A
+/// start goes on with the kind, relative to the previous kind, the flags, the
+/// name if flagged and the variables. Returns `None` for malformed input.
+pub fn decode_original_scopes(encoded: &str) -> Option<Vec<OriginalScope>> {
+  let mut reader = VlqReader {
+    bytes: encoded.as_bytes(),
+    index: 0,
+  };
+  let mut result = Vec::new();
+  let mut open: Vec<OriginalScope> = Vec::new();
+  let mut line = 0i64;
+  let mut kind = 0i64;
+  while let Some(byte) = reader.peek() {
+    if byte == b',' {
+      reader.index += 1;
+      continue;
+    }
+    line += reader.next()?;
+    let line = u32::try_from(line).ok()?;
+    let column = reader.next_u32()?;
+    if reader.at_separator() {
+      let mut scope = open.pop()?;
+      scope.end_line = line;
+      scope.end_column = column;
+      match open.last_mut() {
+        Some(parent) => parent.children.push(scope),
+        None => result.push(scope),
+      }
+      continue;
+    }
+    kind += reader.next()?;
+    let flags = reader.next()?;
+    if flags
+      & !(ORIGINAL_HAS_NAME | ORIGINAL_IS_STACK_FRAME | ORIGINAL_IS_HIDDEN)
+      != 0
+    {
+      return None;
+    }
+    let name_index = if flags & ORIGINAL_HAS_NAME != 0 {
+      Some(reader.next_u32()?)
+    } else {
+      None
+    };
+    let mut variables = Vec::new();
+    while !reader.at_separator() {
+      variables.push(reader.next_u32()?);
+    }
+    open.push(OriginalScope {
+      start_line: line,
+      start_column: column,
+      end_line: line,
+      end_column: column,
+      kind_index: u32::try_from(kind).ok()?,
+      name_index,
+      is_stack_frame: flags & ORIGINAL_IS_STACK_FRAME != 0,
+      is_hidden: flags & ORIGINAL_IS_HIDDEN != 0,
+      variables,
+      children: Vec::new(),
+    });
+  }
+  open.is_empty().then_some(result)
+}
+
+/// Encode scopes into one entry of `originalScopes`, see
+/// [decode_original_scopes].
+pub fn encode_original_scopes(scopes: &[OriginalScope]) -> String {
+  struct Encoder {
+    out: Vec<u8>,
+    line: u32,
+    kind: u32,
+  }
+
+  impl Encoder {
+    fn position(&mut self, line: u32, column: u32) {
+      if !self.out.is_empty() {
+        self.out.push(b',');
+      }
+      encode_vlq(&mut self.out, line, self.line);
+      encode_vlq(&mut self.out, column, 0);
+      self.line = line;
+    }
+
+    fn scope(&mut self, scope: &OriginalScope) {
+      self.position(scope.start_line, scope.start_column);
+      encode_vlq(&mut self.out, scope.kind_index, self.kind);
+      self.kind = scope.kind_index;
+      let flags = if scope.name_index.is_some() {
+        ORIGINAL_HAS_NAME
+      } else {
+        0
+      } | if scope.is_stack_frame {
+        ORIGINAL_IS_STACK_FRAME
+      } else {
+        0
+      } | if scope.is_hidden {
+        ORIGINAL_IS_HIDDEN
+      } else {
+        0
+      };
+      encode_vlq(&mut self.out, flags as u32, 0);
+      if let Some(name_index) = scope.name_index {
+        encode_vlq(&mut self.out, name_index, 0);
+      }
+      for variable in &scope.variables {
+        encode_vlq(&mut self.out, *variable, 0);
+      }
+      for child in &scope.children {
+        self.scope(child);
+      }
+      self.position(scope.end_line, scope.end_column);
+    }
+  }
+
+  let mut encoder = Encoder {
+    out: Vec::new(),
+    line: 0,
+    kind: 0,
+  };
+  for scope in scopes {
+    encoder.scope(scope);
+  }
+  #[allow(unsafe_code)]
+  // SAFETY: VLQ encoding only produces ASCII characters.
+  unsafe {
+    String::from_utf8_unchecked(encoder.out)
+  }
+}
+
+/// Decode the `generatedRanges` field.
+///
+/// Lines are separated by `;` and ranges are encoded by their start and end,
+/// separated by `,`. Both start with the column, relative to the previous
+/// start or end on the line. A start goes on with the flags, the definition
+/// and the callsite if flagged, and the bindings. Indices of the definition
+/// and the callsite are relative to the previous ones while they refer to the
+/// same source, a binding with sub-ranges starts with their negated count.
+/// Returns `None` for malformed input.
+pub fn decode_generated_ranges(encoded: &str) -> Option<Vec<GeneratedRange>> {
+  let mut reader = VlqReader {
+    bytes: encoded.as_bytes(),
+    index: 0,
+  };
+  let mut result = Vec::new();
+  let mut open: Vec<GeneratedRange> = Vec::new();
+  let mut line = 0u32;
+  let mut column = 0i64;
+  let mut definition = (0i64, 0i64);
+  let mut callsite = (0i64, 0i64, 0i64);
+  while let Some(byte) = reader.peek() {
+    match byte {
+      b';' => {
+        reader.index += 1;
+        line = line.checked_add(1)?;
+        column = 0;
+        continue;
+      }
+      b',' => {
+        reader.index += 1;
+        continue;
+      }
+      _ => {}
+    }
+    column += reader.next()?;
+    let start_column = u32::try_from(column).ok()?;
+    if reader.at_separator() {
+      let mut range = open.pop()?;
+      range.end_line = line;
+      range.end_column = start_column;
+      match open.last_mut() {
+        Some(parent) => parent.children.push(range),
+        None => result.push(range),
+      }
+      continue;
+    }
+    let flags = reader.next()?;
+    if flags
+      & !(GENERATED_HAS_DEFINITION
+        | GENERATED_HAS_CALLSITE
+        | GENERATED_IS_STACK_FRAME
+        | GENERATED_IS_HIDDEN)
+      != 0
+    {
+      return None;
+    }
+    let range_definition = if flags & GENERATED_HAS_DEFINITION != 0 {
+      let source = reader.next()?;
+      let scope = reader.next()?;
+      definition.1 = if source == 0 {
+        definition.1 + scope
+      } else {
+        scope
+      };
+      definition.0 += source;
+      Some(ScopeDefinition {
+        source_index: u32::try_from(definition.0).ok()?,
+        scope_index: u32::try_from(definition.1).ok()?,
+      })
+    } else {
+      None
+    };
+    let range_callsite = if flags & GENERATED_HAS_CALLSITE != 0 {
+      let source = reader.next()?;
+      let callsite_line = reader.next()?;
+      let callsite_column = reader.next()?;
+      callsite.2 = if source == 0 && callsite_line == 0 {
+        callsite.2 + callsite_column
+      } else {
+        callsite_column
+      };
+      callsite.1 = if source == 0 {
+        callsite.1 + callsite_line
+      } else {
+        callsite_line
+      };
+      callsite.0 += source;
+      Some(Callsite {
+        source_index: u32::try_from(callsite.0).ok()?,
+        line: u32::try_from(callsite.1).ok()?,
+        column: u32::try_from(callsite.2).ok()?,
+      })
+    } else {
+      None
+    };
+    let mut bindings = Vec::new();
+    while !reader.at_separator() {
+      let value = reader.next()?;
+      if value >= -1 {
+        bindings.push(Binding {
+          name_index: u32::try_from(value).ok(),
+          sub_ranges: Vec::new(),
+        });
+        continue;
+      }
+      let name_index = reader.next_name()?;
+      let mut sub_ranges = Vec::new();
+      let (mut sub_line, mut sub_column) = (line, start_column);
+      for _ in 1..-value {
+        let line_delta = reader.next()?;
+        let column = reader.next()?;
+        sub_line = u32::try_from(sub_line as i64 + line_delta).ok()?;
+        sub_column = if line_delta == 0 {
+          u32::try_from(sub_column as i64 + column).ok()?
+        } else {
+          u32::try_from(column).ok()?
+        };
+        sub_ranges.push(BindingSubRange {
+          line: sub_line,
+          column: sub_column,
+          name_index: reader.next_name()?,
+        });
+      }
+      bindings.push(Binding {
+        name_index,
+        sub_ranges,
+      });
+    }
+    open.push(GeneratedRange {
+      start_line: line,
+      start_column,
+      end_line: line,
+      end_column: start_column,
+      definition: range_definition,
+      callsite: range_callsite,
+      is_stack_frame: flags & GENERATED_IS_STACK_FRAME != 0,
+      is_hidden: flags & GENERATED_IS_HIDDEN != 0,
+      bindings,
+      children: Vec::new(),
+    });
+  }
+  open.is_empty().then_some(result)
+}
+
+/// Encode ranges into the `generatedRanges` field, see
+/// [decode_generated_ranges]. The ranges are expected in the order of their
+/// positions.
+pub fn encode_generated_ranges(ranges: &[GeneratedRange]) -> String {
+  #[derive(Default)]
+  struct Encoder {
+    out: Vec<u8>,
+    line: u32,
+    column: u32,
+    needs_comma: bool,
+    definition: (u32, u32),
+    callsite: (u32, u32, u32),
+  }
+
+  impl Encoder {
+    fn position(&mut self, line: u32, column: u32) {
+      if line > self.line {
+        for _ in self.line..line {
+          self.out.push(b';');
+        }
+        self.line = line;
+        self.column = 0;
+      } else if self.needs_comma {
+        self.out.push(b',');
+      }
+      self.needs_comma = true;
+      encode_vlq(&mut self.out, column, self.column);
+      self.column = column;
+    }
+
+    fn name(&mut self, name_index: Option<u32>) {
+      match name_index {
+        Some(name_index) => encode_vlq(&mut self.out, name_index, 0),
+        None => encode_vlq(&mut self.out, 0, 1),
+      }
+    }
+
+    fn range(&mut self, range: &GeneratedRange) {
+      self.position(range.start_line, range.start_column);
+      let mut flags = 0;
+      if range.definition.is_some() {
+        flags |= GENERATED_HAS_DEFINITION;
+      }
+      if range.callsite.is_some() {
+        flags |= GENERATED_HAS_CALLSITE;
+      }
+      if range.is_stack_frame {
+        flags |= GENERATED_IS_STACK_FRAME;
+      }
+      if range.is_hidden {
+        flags |= GENERATED_IS_HIDDEN;
+      }
+      encode_vlq(&mut self.out, flags as u32, 0);
+      if let Some(definition) = range.definition {
+        let (source, scope) = self.definition;
+        encode_vlq(&mut self.out, definition.source_index, source);
+        let scope = if definition.source_index == source {
+          scope
+        } else {
+          0
+        };
+        encode_vlq(&mut self.out, definition.scope_index, scope);
+        self.definition = (definition.source_index, definition.scope_index);
+      }
+      if let Some(callsite) = range.callsite {
+        let (source, line, column) = self.callsite;
+        encode_vlq(&mut self.out, callsite.source_index, source);
+        let same_source = callsite.source_index == source;
+        encode_vlq(
+          &mut self.out,
+          callsite.line,
+          if same_source { line } else { 0 },
+        );
+        let same_line = same_source && callsite.line == line;
+        encode_vlq(
+          &mut self.out,
+          callsite.column,
+          if same_line { column } else { 0 },
+        );
+        self.callsite = (callsite.source_index, callsite.line, callsite.column);
+      }
+      for binding in &range.bindings {
+        if !binding.sub_ranges.is_empty() {
+          encode_vlq(&mut self.out, 0, binding.sub_ranges.len() as u32 + 1);
+        }
+        self.name(binding.name_index);
+        let (mut line, mut column) = (range.start_line, range.start_column);
+        for sub_range in &binding.sub_ranges {
+          encode_vlq(&mut self.out, sub_range.line, line);
+          let previous = if sub_range.line == line { column } else { 0 };
+          encode_vlq(&mut self.out, sub_range.column, previous);
+          self.name(sub_range.name_index);
+          (line, column) = (sub_range.line, sub_range.column);
+        }
+      }
+      for child in &range.children {
+        self.range(child);
+      }
+      self.position(range.end_line, range.end_column);
+    }
+  }
+
+  let mut encoder = Encoder::default();
+  for range in ranges {
+    encoder.range(range);
+  }
+  #[allow(unsafe_code)]
+  // SAFETY: VLQ encoding only produces ASCII characters.
+  unsafe {
+    String::from_utf8_unchecked(encoder.out)
+  }
+}
+
+impl GeneratedRange {
+  // Move the range, and the ranges nested in it, behind code ending at
+  // `line`:`column`.
+  pub(crate) fn shift(&mut self, line: u32, column: u32) {
+    let shift = |position: (&mut u32, &mut u32)| {
+      if *position.0 == 0 {
+        *position.1 += column;
+      }
+      *position.0 += line;
+    };
+    shift((&mut self.start_line, &mut self.start_column));
+    shift((&mut self.end_line, &mut self.end_column));
+    for binding in &mut self.bindings {
+      for sub_range in &mut binding.sub_ranges {
+        shift((&mut sub_range.line, &mut sub_range.column));
+      }
+    }
+    for child in &mut self.children {
+      child.shift(line, column);
+    }
+  }
+
+  // Rewrite the source indices of the range and the ranges nested in it,
+  // references to sources without a new index are left out.
+  pub(crate) fn remap_sources(
+    &mut self,
+    new_index: &impl Fn(u32) -> Option<u32>,
+  ) {
+    if let Some(definition) = &mut self.definition {
+      match new_index(definition.source_index) {
+        Some(source_index) => definition.source_index = source_index,
+        None => {
+          self.definition = None;
+          // Bindings belong to the variables of the definition.
+          self.bindings.clear();
+        }
+      }
+    }
+    if let Some(callsite) = &mut self.callsite {
+      match new_index(callsite.source_index) {
+        Some(source_index) => callsite.source_index = source_index,
+        None => self.callsite = None,
+      }
+    }
+    for child in &mut self.children {
+      child.remap_sources(new_index);
+    }
+  }
+
+  // Rewrite the name indices of the bindings, names without a new index
+  // become unavailable.
+  fn remap_names(&mut self, new_index: &mut impl FnMut(u32) -> Option<u32>) {
+    for binding in &mut self.bindings {
+      binding.name_index = binding.name_index.and_then(&mut *new_index);
+      for sub_range in &mut binding.sub_ranges {
+        sub_range.name_index = sub_range.name_index.and_then(&mut *new_index);
+      }
+    }
+    for child in &mut self.children {
+      child.remap_names(new_index);
+    }
+  }
+}
+
+impl OriginalScope {
+  // Rewrite the name indices of the scope and the scopes nested in it,
+  // returns `None` if a name is missing.
+  fn remap_names(
+    &mut self,
+    new_index: &mut impl FnMut(u32) -> Option<u32>,
+  ) -> Option<()> {
+    self.kind_index = new_index(self.kind_index)?;
+    if let Some(name_index) = self.name_index {
+      self.name_index = Some(new_index(name_index)?);
+    }
+    for variable in &mut self.variables {
+      *variable = new_index(*variable)?;
+    }
+    for child in &mut self.children {
+      child.remap_names(new_index)?;
+    }
+    Some(())
+  }
+}
+
+/// Collects the `originalScopes` reported through [SourceExtras] and the
+/// `generatedRanges` reported while a source map is generated, and rewrites
+/// their name indices into the names of the generated map.
+#[derive(Default)]
+pub(crate) struct ScopesCollector<'a> {
+  original_scopes: Vec<Option<(&'a str, &'a [String])>>,
+  generated_ranges: Vec<(Vec<GeneratedRange>, &'a [String])>,
+}
+
+impl<'a> ScopesCollector<'a> {
+  pub fn add_source(&mut self, source_index: u32, extras: &SourceExtras<'a>) {
+    let source_index = source_index as usize;
+    if let Some(original_scopes) = extras.original_scopes {
+      if self.original_scopes.len() <= source_index {
+        self.original_scopes.resize(source_index + 1, None);
+      }
+      self.original_scopes[source_index] =
+        Some((original_scopes, extras.names));
+    } else if let Some(source) = self.original_scopes.get_mut(source_index) {
+      *source = None;
+    }
+  }
+
+  pub fn add_ranges(
+    &mut self,
+    ranges: Vec<GeneratedRange>,
+    names: &'a [String],
+  ) {
+    if !ranges.is_empty() {
+      self.generated_ranges.push((ranges, names));
+    }
+  }
+
+  /// Returns the `originalScopes`, one entry per source, and the
+  /// `generatedRanges` of the generated map. `source_index` gives the index
+  /// in the generated map of the sources the ranges refer to, names missing
+  /// from `names` are appended to it.
+  pub fn finish<N>(
+    self,
+    sources_len: usize,
+    names: &mut Vec<N>,
+    source_index: impl Fn(u32) -> Option<u32>,
+  ) -> (Option<Vec<String>>, Option<String>)
+  where
+    N: AsRef<str> + From<&'a str>,
+  {
+    let mut name_indices: HashMap<&'a str, u32> = HashMap::default();
+    let mut names_len = names.len();
+    let mut known_names: HashMap<String, u32> = HashMap::default();
+    for (index, name) in names.iter().enumerate() {
+      known_names
+        .entry(name.as_ref().to_string())
+        .or_insert(index as u32);
+    }
+    let mut new_names: Vec<&'a str> = Vec::new();
+    let mut name_index = |origin_names: &'a [String], index: u32| {
+      let name = origin_names.get(index as usize)?.as_str();
+      if let Some(index) = known_names.get(name) {
+        return Some(*index);
+      }
+      Some(*name_indices.entry(name).or_insert_with(|| {
+        new_names.push(name);
+        names_len += 1;
+        names_len as u32 - 1
+      }))
+    };
+
+    let original_scopes = (!self.original_scopes.iter().all(Option::is_none))
+      .then(|| {
+        let mut result =
+          vec![String::new(); sources_len.max(self.original_scopes.len())];
+        for (entry, source) in result.iter_mut().zip(&self.original_scopes) {
+          let Some((encoded, origin_names)) = source else {
+            continue;
+          };
+          let Some(mut scopes) = decode_original_scopes(encoded) else {
+            continue;
+          };
+          let remapped = scopes.iter_mut().all(|scope| {
+            scope
+              .remap_names(&mut |index| name_index(origin_names, index))
+              .is_some()
+          });
+          if remapped {
+            *entry = encode_original_scopes(&scopes);
+          }
+        }
+        result
+      });
+
+    let mut ranges = Vec::new();
+    for (mut stream_ranges, origin_names) in self.generated_ranges {
+      for range in &mut stream_ranges {
+        range.remap_sources(&source_index);
+        range.remap_names(&mut |index| name_index(origin_names, index));
+      }
+      ranges.extend(stream_ranges);
+    }
+    let generated_ranges =
+      (!ranges.is_empty()).then(|| encode_generated_ranges(&ranges));
+
+    names.extend(new_names.into_iter().map(N::from));
+    (original_scopes, generated_ranges)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn function_scope() -> OriginalScope {
+    OriginalScope {
+      start_line: 0,
+      start_column: 0,
+      end_line: 4,
+      end_column: 1,
+      kind_index: 0,
+      name_index: None,
+      is_stack_frame: false,
+      is_hidden: false,
+      variables: vec![1],
+      children: vec![OriginalScope {
+        start_line: 1,
+        start_column: 2,
+        end_line: 3,
+        end_column: 3,
+        kind_index: 2,
+        name_index: Some(1),
+        is_stack_frame: true,
+        is_hidden: false,
+        variables: vec![3, 4],
+        children: vec![],
+      }],
+    }
+  }
+
+  fn inlined_range() -> GeneratedRange {
+    GeneratedRange {
+      start_line: 0,
+      start_column: 0,
+      end_line: 2,
+      end_column: 0,
+      definition: Some(ScopeDefinition {
+        source_index: 0,
+        scope_index: 0,
+      }),
+      callsite: None,
+      is_stack_frame: false,
+      is_hidden: false,
+      bindings: vec![Binding {
+        name_index: Some(1),
+        sub_ranges: vec![],
+      }],
+      children: vec![GeneratedRange {
+        start_line: 1,
+        start_column: 4,
+        end_line: 1,
+        end_column: 20,
+        definition: Some(ScopeDefinition {
+          source_index: 0,
+          scope_index: 1,
+        }),
+        callsite: Some(Callsite {
+          source_index: 0,
+          line: 5,
+          column: 2,
+        }),
+        is_stack_frame: false,
+        is_hidden: true,
+        bindings: vec![
+          Binding {
+            name_index: None,
+            sub_ranges: vec![],
+          },
+          Binding {
+            name_index: Some(3),
+            sub_ranges: vec![BindingSubRange {
+              line: 1,
+              column: 10,
+              name_index: Some(4),
+            }],
+          },
+        ],
+        children: vec![],
+      }],
+    }
+  }
+
+  #[test]
+  fn original_scopes_round_trip() {
+    let scopes = vec![function_scope()];
+    let encoded = encode_original_scopes(&scopes);
+    assert_eq!(encoded, "AAAAC,CEEGCGI,EG,CC");
+    assert_eq!(decode_original_scopes(&encoded).unwrap(), scopes);
+    assert_eq!(decode_original_scopes("").unwrap(), vec![]);
+    // A scope that is never closed.
+    assert_eq!(decode_original_scopes("AAAA"), None);
+    // An end without a start.
+    assert_eq!(decode_original_scopes("AA"), None);
+    // Unknown flags.
+    assert_eq!(decode_original_scopes("AAAQ,AA"), None);
+  }
+
+  #[test]
+  fn generated_ranges_round_trip() {
+    let ranges = vec![inlined_range()];
+    let encoded = encode_generated_ranges(&ranges);
+    assert_eq!(decode_generated_ranges(&encoded).unwrap(), ranges);
+    assert_eq!(decode_generated_ranges("").unwrap(), vec![]);
+    assert_eq!(decode_generated_ranges("AA"), None);
+    assert_eq!(decode_generated_ranges("A"), None);
+    assert_eq!(decode_generated_ranges("AgB,A"), None);
+  }
+
+  #[test]
+  fn shifts_ranges() {
+    let mut range = inlined_range();
+    range.shift(3, 7);
+    assert_eq!((range.start_line, range.start_column), (3, 7));
+    assert_eq!((range.end_line, range.end_column), (5, 0));
+    let child = &range.children[0];
+    assert_eq!((child.start_line, child.start_column), (4, 4));
+    let sub_range = child.bindings[1].sub_ranges[0];
+    assert_eq!((sub_range.line, sub_range.column), (4, 10));
+  }
+
+  #[test]
+  fn collector_remaps_names_and_sources() {
+    let origin_names: Vec<String> = ["function", "a", "block", "b", "c"]
+      .map(Into::into)
+      .to_vec();
+    let encoded = encode_original_scopes(&[function_scope()]);
+    let mut collector = ScopesCollector::default();
+    collector.add_source(
+      1,
+      &SourceExtras {
+        original_scopes: Some(&encoded),
+        names: &origin_names,
+        ..Default::default()
+      },
+    );
+    collector.add_ranges(vec![inlined_range()], &origin_names);
+    let mut names = vec!["b".to_string()];
+    let (original_scopes, generated_ranges) =
+      collector.finish(2, &mut names, |index| Some(index + 1));
+    assert_eq!(names, ["b", "function", "a", "block", "c"]);
+
+    let original_scopes = original_scopes.unwrap();
+    assert_eq!(original_scopes[0], "");
+    let scope = &decode_original_scopes(&original_scopes[1]).unwrap()[0];
+    assert_eq!(
+      (scope.kind_index, scope.variables.as_slice()),
+      (1, &[2][..])
+    );
+    let child = &scope.children[0];
+    assert_eq!(child.name_index, Some(2));
+    assert_eq!(child.variables, [0, 4]);
+
+    let range =
+      &decode_generated_ranges(&generated_ranges.unwrap()).unwrap()[0];
+    assert_eq!(range.definition.unwrap().source_index, 1);
+    assert_eq!(range.bindings[0].name_index, Some(2));
+    let child = &range.children[0];
+    assert_eq!(child.callsite.unwrap().source_index, 1);
+    assert_eq!(child.bindings[1].name_index, Some(0));
+    assert_eq!(child.bindings[1].sub_ranges[0].name_index, Some(4));
+  }
+
+  #[test]
+  fn collector_drops_references_to_left_out_sources() {
+    let mut collector = ScopesCollector::default();
+    collector.add_ranges(vec![inlined_range()], &[]);
+    let (_, generated_ranges) =
+      collector.finish(0, &mut Vec::<String>::new(), |_| None);
+    let range =
+      &decode_generated_ranges(&generated_ranges.unwrap()).unwrap()[0];
+    assert_eq!(range.definition, None);
+    assert!(range.bindings.is_empty());
+    assert_eq!(range.children[0].callsite, None);
+  }
+}
@@ -0,0 +1,578 @@
+use std::{
+  borrow::Cow,
+  cell::{Cell, RefCell},
+  hash::{Hash, Hasher},
+  ops::Range,
+  sync::Arc,
+};
+
+use crate::{
+  helpers::{
+    get_map, stream_chunks_with_extras, utf16_len, Chunks, GeneratedInfo,
+    SourceExtras, StreamChunks,
+  },
+  linear_map::LinearMap,
+  object_pool::ObjectPool,
+  source::{Mapping, OriginalLocation},
+  BoxSource, CachedSource, ConcatSource, ConcatSourceBuilder, MapOptions,
+  OriginalSource, Source, SourceExt, SourceKind, SourceMap, SourceValue,
+};
+
+/// The bytes in a range of another [Source], with the mappings of that
+/// range.
+///
+/// Generated positions of the slice start at line 1, column 0, original
+/// positions and names are kept. A chunk cut by the range loses its name and
+/// keeps its original position, unless the sliced source is an
+/// [OriginalSource]. Only sources and names used by the slice end up in its
+/// map.
+///
+/// The range refers to [Source::source] and is clamped to the size of the
+/// source, a start or end inside a char is moved back to the start of that
+/// char.
+/// When the inner source is a [ConcatSource], also in a [CachedSource], only
+/// the children overlapping the range are kept and streamed. Any other inner
+/// source is still streamed to its end, the chunks past the range are
+/// skipped without further work.
+///
+/// ```
+/// use rspack_sources::{
+///   ConcatSource, MapOptions, ObjectPool, OriginalSource, RawStringSource,
+///   SliceSource, Source, SourceExt,
+/// };
+///
+/// let source = ConcatSource::new([
+///   RawStringSource::from_static("a();\n").boxed(),
+///   OriginalSource::new("b();\nc();\n", "b.js").boxed(),
+/// ]);
+/// let slice = SliceSource::new(source, 10..15);
+/// assert_eq!(slice.source().into_string_lossy(), "c();\n");
+/// let map = slice
+///   .map(&ObjectPool::default(), &MapOptions::default())
+///   .unwrap();
+/// assert_eq!(map.sources(), ["b.js"]);
+/// assert_eq!(map.mappings(), "AACA");
+/// ```
+#[derive(Clone)]
+pub struct SliceSource {
+  inner: BoxSource,
+  start: usize,
+  end: usize,
+}
+
+impl SliceSource {
+  /// Create a [SliceSource] of the bytes in `range` of `inner`.
+  pub fn new<T: SourceExt>(inner: T, range: Range<usize>) -> Self {
+    let inner = inner.boxed();
+    let end = range.end.min(inner.size());
+    let start = range.start.min(end);
+
+    if let Some(concat_source) = as_concat_source(inner.as_ref()) {
+      let (children, offset) = concat_source.children_in(start..end);
+      if let [child] = children {
+        return Self::new(child.clone(), start - offset..end - offset);
+      }
+      if children.len() < concat_source.optimized_children().len() {
+        let mut builder = ConcatSourceBuilder::with_capacity(children.len());
+        for child in children {
+          builder.add(child.clone());
+        }
+        let inner = builder.build().boxed();
+        let (start, end) =
+          floor_char_boundaries(inner.as_ref(), start - offset, end - offset);
+        return Self { inner, start, end };
+      }
+    }
+
+    let (start, end) = floor_char_boundaries(inner.as_ref(), start, end);
+    Self { inner, start, end }
+  }
+
+  /// Get the sliced [Source].
+  pub fn original(&self) -> &BoxSource {
+    &self.inner
+  }
+
+  /// The sliced range of the [SliceSource::original] source.
+  pub fn range(&self) -> Range<usize> {
+    self.start..self.end
+  }
+
+  fn is_whole(&self) -> bool {
+    self.start == 0 && self.end == self.inner.size()
+  }
+}
+
+// The part of `chunk` starting at `chunk_start` inside `start..end`, and
+// the offset of that part in `chunk`.
+fn slice_chunk(
+  chunk: &str,
+  chunk_start: usize,
+  start: usize,
+  end: usize,
+) -> Option<(usize, &str)> {
+  let from = floor_char_boundary(chunk, start.saturating_sub(chunk_start));
+  let to = floor_char_boundary(chunk, end.saturating_sub(chunk_start));
+  (from < to).then(|| (from, &chunk[from..to]))
+}
+
+// The [ConcatSource] `source` is or wraps in [CachedSource]s.
+fn as_concat_source(mut source: &dyn Source) -> Option<&ConcatSource> {
+  while let Some(cached_source) = source.as_any().downcast_ref::<CachedSource>()
+  {
+    source = cached_source.original().as_ref();
+  }
+  source.as_any().downcast_ref::<ConcatSource>()
+}
+
+// `start` and `end` moved back to the char boundaries of the code of
+// `inner`. The children of a [ConcatSource] past `end` are not roped.
+fn floor_char_boundaries(
+  inner: &dyn Source,
+  start: usize,
+  end: usize,
+) -> (usize, usize) {
+  let mut boundaries = (start, end);
+  let pos = Cell::new(0);
+  let mut on_chunk = |chunk: &str| {
+    let chunk_start = pos.get();
+    pos.set(chunk_start + chunk.len());
+    let pos = pos.get();
+    if chunk_start > end {
+      return;
+    }
+    if (chunk_start..pos).contains(&start) {
+      boundaries.0 =
+        chunk_start + floor_char_boundary(chunk, start - chunk_start);
+    }
+    if (chunk_start..pos).contains(&end) {
+      boundaries.1 =
+        chunk_start + floor_char_boundary(chunk, end - chunk_start);
+    }
+  };
+  match as_concat_source(inner) {
+    Some(concat_source) => {
+      for child in concat_source.optimized_children() {
+        if pos.get() > end {
+          break;
+        }
+        child.rope(&mut on_chunk);
+      }
+    }
+    None => inner.rope(&mut on_chunk),
+  }
+  boundaries
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+  let mut index = index.min(text.len());
+  while !text.is_char_boundary(index) {
+    index -= 1;
+  }
+  index
+}
+
+impl Source for SliceSource {
+  fn source(&self) -> SourceValue<'_> {
+    let mut string = String::with_capacity(self.size());
+    self.rope(&mut |chunk| string.push_str(chunk));
+    SourceValue::String(Cow::Owned(string))
+  }
+
+  fn rope<'a>(&'a self, on_chunk: &mut dyn FnMut(&'a str)) {
+    let mut pos = 0;
+    self.inner.rope(&mut |chunk| {
+      if pos >= self.end {
+        return;
+      }
+      if let Some((_, chunk)) = slice_chunk(chunk, pos, self.start, self.end) {
+        on_chunk(chunk);
+      }
+      pos += chunk.len();
+    });
+  }
+
+  fn buffer(&self) -> Cow<'_, [u8]> {
+    match self.inner.buffer() {
+      Cow::Borrowed(buffer) => {
+        Cow::Borrowed(buffer.get(self.start..self.end).unwrap_or_default())
+      }
+      Cow::Owned(buffer) => {
+        Cow::Owned(buffer.get(self.start..self.end).unwrap_or_default().into())
+      }
+    }
+  }
+
+  fn size(&self) -> usize {
+    self.end - self.start
+  }
+
+  fn map(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+  ) -> Option<SourceMap> {
+    if self.is_whole() {
+      return self.inner.map(object_pool, options);
+    }
+    let chunks = self.stream_chunks();
+    get_map(object_pool, chunks.as_ref(), options)
+  }
+
+  fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+    let mut result = Ok(());
+    self.rope(&mut |chunk| {
+      if result.is_err() {
+        return;
+      }
+      result = writer.write_all(chunk.as_bytes());
+    });
+    result
+  }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::Slice
+  }
+
+  fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+    on_child(&self.inner);
+  }
+
+  fn with_children(&self, children: Vec<BoxSource>) -> Option<BoxSource> {
+    let [inner] = <[BoxSource; 1]>::try_from(children).ok()?;
+    Some(SliceSource::new(inner, self.range()).boxed())
+  }
+}
+
+impl Hash for SliceSource {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    "SliceSource".hash(state);
+    self.start.hash(state);
+    self.end.hash(state);
+    self.inner.hash(state);
+  }
+}
+
+impl PartialEq for SliceSource {
+  fn eq(&self, other: &Self) -> bool {
+    self.start == other.start
+      && self.end == other.end
+      && self.inner.as_ref() == other.inner.as_ref()
+  }
+}
+
+impl Eq for SliceSource {}
+
+impl std::fmt::Debug for SliceSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let indent = f.width().unwrap_or(0);
+    let indent_str = format!("{:indent$}", "", indent = indent);
+
+    writeln!(f, "{indent_str}SliceSource::new(")?;
+    writeln!(f, "{:indent$?},", self.inner, indent = indent + 2)?;
+    writeln!(f, "{indent_str}  {}..{},", self.start, self.end)?;
+    write!(f, "{indent_str}).boxed()")
+  }
+}
+
+type PendingSource<'a> = (Cow<'a, str>, Option<&'a Arc<str>>, SourceExtras<'a>);
+
+struct SliceSourceChunks<'a> {
+  is_original_source: bool,
+  chunks: Box<dyn Chunks + 'a>,
+  start: usize,
+  end: usize,
+}
+
+impl Chunks for SliceSourceChunks<'_> {
+  fn stream<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+  ) -> GeneratedInfo {
+    self.stream_with_extras(
+      object_pool,
+      options,
+      on_chunk,
+      on_source,
+      on_name,
+      &mut |_, _| {},
+      &mut |_, _| {},
+    )
+  }
+
+  fn stream_with_extras<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'a>,
+    // Ranges can't be cut to the slice, they are dropped.
+    _on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'a>,
+  ) -> GeneratedInfo {
+    // Sources and names are only passed on once a chunk of the slice uses
+    // them, with new indices.
+    let pending_sources: RefCell<LinearMap<Option<PendingSource<'a>>>> =
+      RefCell::new(LinearMap::default());
+    let pending_names: RefCell<LinearMap<Option<Cow<'a, str>>>> =
+      RefCell::new(LinearMap::default());
+    let mut source_index_mapping: LinearMap<Option<u32>> = LinearMap::default();
+    let mut name_index_mapping: LinearMap<Option<u32>> = LinearMap::default();
+    let mut next_source_index = 0;
+    let mut next_name_index = 0;
+
+    let mut pos = 0;
+    let mut line = 1;
+    let mut column = 0;
+    stream_chunks_with_extras(
+      self.chunks.as_ref(),
+      object_pool,
+      &options.inner(false),
+      &mut |chunk, mapping| {
+        let Some(chunk) = chunk.filter(|_| pos < self.end) else {
+          return;
+        };
+        let chunk_start = pos;
+        pos += chunk.len();
+        let Some((skipped, slice)) =
+          slice_chunk(chunk, chunk_start, self.start, self.end)
+        else {
+          return;
+        };
+        let is_cut = slice.len() < chunk.len();
+        // The chunks of an OriginalSource are its original code, so a cut
+        // chunk can be mapped to where the slice starts.
+        let skipped = &chunk[..skipped];
+        let column_offset =
+          if self.is_original_source && !skipped.contains('\n') {
+            utf16_len(skipped) as u32
+          } else {
+            0
+          };
+
+        let original = mapping.original.and_then(|original| {
+          let source_index = match source_index_mapping
+            .get(&original.source_index)
+            .copied()
+            .flatten()
+          {
+            Some(source_index) => source_index,
+            None => {
+              let (source, source_content, extras) = pending_sources
+                .borrow_mut()
+                .get_mut(&original.source_index)?
+                .take()?;
+              let source_index = next_source_index;
+              next_source_index += 1;
+              on_source_extras(source_index, extras);
+              on_source(source_index, source, source_content);
+              source_index_mapping
+                .insert(original.source_index, Some(source_index));
+              source_index
+            }
+          };
+          let name_index =
+            original
+              .name_index
+              .filter(|_| !is_cut)
+              .and_then(|name_index| {
+                if let Some(Some(name_index)) =
+                  name_index_mapping.get(&name_index)
+                {
+                  return Some(*name_index);
+                }
+                let name =
+                  pending_names.borrow_mut().get_mut(&name_index)?.take()?;
+                let new_name_index = next_name_index;
+                next_name_index += 1;
+                on_name(new_name_index, name);
+                name_index_mapping.insert(name_index, Some(new_name_index));
+                Some(new_name_index)
+              });
+          Some(OriginalLocation {
+            source_index,
+            original_line: original.original_line,
+            original_column: original.original_column + column_offset,
+            name_index,
+          })
+        });
+
+        on_chunk(
+          Some(slice),
+          Mapping {
+            generated_line: line,
+            generated_column: column,
+            original,
+          },
+        );
+        match memchr::memrchr(b'\n', slice.as_bytes()) {
+          Some(last_newline) => {
+            line += memchr::memchr_iter(b'\n', slice.as_bytes()).count() as u32;
+            column = utf16_len(&slice[last_newline + 1..]) as u32;
+          }
+          None => column += utf16_len(slice) as u32,
+        }
+      },
+      &mut |source_index, source, source_content, extras| {
+        pending_sources
+          .borrow_mut()
+          .insert(source_index, Some((source, source_content, extras)));
+      },
+      &mut |name_index, name| {
+        pending_names.borrow_mut().insert(name_index, Some(name));
+      },
+      &mut |_, _| {},
+    );
+
+    GeneratedInfo {
+      generated_line: line,
+      generated_column: column,
+    }
+  }
+}
+
+impl StreamChunks for SliceSource {
+  fn stream_chunks<'a>(&'a self) -> Box<dyn Chunks + 'a> {
+    Box::new(SliceSourceChunks {
+      is_original_source: self.inner.as_ref().as_any().is::<OriginalSource>(),
+      chunks: self.inner.stream_chunks(),
+      start: self.start,
+      end: self.end,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    RawStringSource, ReplaceSource, SourceMapSource, SourceMapSourceOptions,
+  };
+
+  use super::*;
+
+  #[test]
+  fn slices_mappings_and_names() {
+    let map = SourceMap::from_json(
+      r#"{
+        "version": 3,
+        "sources": ["a.js", "b.js"],
+        "names": ["foo", "bar"],
+        "mappings": "AAAAA,IAAI;ACAJC,IAAI"
+      }"#,
+    )
+    .unwrap();
+    let source = SourceMapSource::new(SourceMapSourceOptions {
+      value: "foo bar\nbar foo\n",
+      name: "out.js",
+      source_map: map,
+      original_source: None,
+      inner_source_map: None,
+      remove_original_source: false,
+    });
+    let slice = SliceSource::new(source.clone(), 8..16);
+    assert_eq!(slice.source().into_string_lossy(), "bar foo\n");
+    assert_eq!(slice.size(), 8);
+    let map = slice
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.sources(), ["b.js"]);
+    assert_eq!(map.names(), ["bar"]);
+    assert_eq!(map.mappings(), "AAAAA,IAAI");
+
+    // The cut chunk keeps its position but not its name.
+    let slice = SliceSource::new(source, 1..5);
+    assert_eq!(slice.source().into_string_lossy(), "oo b");
+    let map = slice
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.names().len(), 0);
+    assert_eq!(map.mappings(), "AAAA,GAAI");
+  }
+
+  #[test]
+  fn remaps_sources_in_order_of_use() {
+    let map = SourceMap::from_json(
+      r#"{
+        "version": 3,
+        "sources": ["a.js", "b.js"],
+        "names": ["foo", "bar"],
+        "mappings": "ACAAC,IAAI;ADAJD,IAAI"
+      }"#,
+    )
+    .unwrap();
+    let source = SourceMapSource::new(SourceMapSourceOptions {
+      value: "bar foo\nfoo bar\n",
+      name: "out.js",
+      source_map: map,
+      original_source: None,
+      inner_source_map: None,
+      remove_original_source: false,
+    });
+    let slice = SliceSource::new(source, 4..16);
+    let map = slice
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.sources(), ["b.js", "a.js"]);
+    assert_eq!(map.names(), ["foo"]);
+    assert_eq!(map.mappings(), "AAAI;ACAJA,IAAI");
+  }
+
+  #[test]
+  fn narrows_concat_sources() {
+    let b = OriginalSource::new("b();\n", "b.js").boxed();
+    let source = CachedSource::new(ConcatSource::new([
+      OriginalSource::new("a();\n", "a.js").boxed(),
+      b.clone(),
+      OriginalSource::new("c();\n", "c.js").boxed(),
+    ]));
+    let slice = SliceSource::new(source.clone(), 5..10);
+    assert!(BoxSource::ptr_eq(slice.original(), &b));
+    assert_eq!(slice.range(), 0..5);
+
+    let slice = SliceSource::new(source.clone(), 7..12);
+    assert_eq!(slice.original().kind(), SourceKind::Concat);
+    assert_eq!(slice.range(), 2..7);
+    assert_eq!(slice.source().into_string_lossy(), ");\nc(");
+    let map = slice
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.sources(), ["b.js", "c.js"]);
+
+    let empty = SliceSource::new(source, 20..30);
+    assert_eq!(empty.size(), 0);
+    assert!(empty
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .is_none());
+  }
+
+  #[test]
+  fn slices_replaced_source() {
+    let mut source = ReplaceSource::new(RawStringSource::from_static("abc"));
+    source.replace(1, 2, "🙈", None);
+    let slice = SliceSource::new(source.clone(), 1..5);
+    assert_eq!(slice.source().into_string_lossy(), "🙈");
+    assert_eq!(&*slice.buffer(), "🙈".as_bytes());
+
+    // A start inside a char moves back to its start.
+    let slice = SliceSource::new(source, 3..6);
+    assert_eq!(slice.range(), 1..6);
+    assert_eq!(slice.source().into_string_lossy(), "🙈c");
+    assert_eq!(&*slice.buffer(), "🙈c".as_bytes());
+    assert_eq!(slice.size(), slice.buffer().len());
+
+    // So does an end inside a char.
+    let source = ConcatSource::new([
+      OriginalSource::new("a\n", "a.js").boxed(),
+      RawStringSource::from_static("🙈").boxed(),
+      OriginalSource::new("b\n", "b.js").boxed(),
+    ]);
+    let slice = SliceSource::new(source, 0..4);
+    assert_eq!(slice.range(), 0..2);
+    assert_eq!(slice.source().into_string_lossy(), "a\n");
+  }
+}
@@ -1,6 +1,7 @@
 use std::{
   any::{Any, TypeId},
   borrow::Cow,
+  collections::BTreeMap,
   convert::{TryFrom, TryInto},
   fmt,
   hash::{Hash, Hasher},
@@ -8,12 +9,19 @@ use std::{
 };
 
 use dyn_clone::DynClone;
-use serde::{Deserialize, Serialize};
+use serde::{
+  de::{self, IgnoredAny, MapAccess, Visitor},
+  Deserialize, Deserializer, Serialize,
+};
 
 use crate::{
-  helpers::{decode_mappings, Chunks, StreamChunks},
+  function_mappings::{decode_function_mappings, FunctionMapping},
+  helpers::{
+    decode_mappings, encode_mappings, write_map_json, Chunks, StreamChunks,
+  },
   object_pool::ObjectPool,
-  Result,
+  scopes::{decode_generated_ranges, encode_generated_ranges},
+  Result, SourceKind, SourceMapJsonOptions,
 };
 
 /// An alias for `Box<dyn Source>`.
@@ -137,6 +145,63 @@ pub trait Source:
 
   /// Writes the source into a writer, preferably a `std::io::BufWriter<std::io::Write>`.
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()>;
+
+  /// Writes the JSON of the [SourceMap] into a writer while it is generated,
+  /// so the map is never fully held in memory. Returns `false` without
+  /// writing anything when the source has no map.
+  ///
+  /// With canonical [SourceMapJsonOptions] the map is collected before it is
+  /// written, as its mappings can't come first.
+  fn map_to_writer(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+    json_options: &SourceMapJsonOptions,
+    writer: &mut dyn std::io::Write,
+  ) -> Result<bool> {
+    write_map_json(
+      object_pool,
+      self.stream_chunks().as_ref(),
+      options,
+      json_options,
+      writer,
+    )
+  }
+
+  /// The kind of this source node.
+  fn kind(&self) -> SourceKind {
+    SourceKind::Other
+  }
+
+  /// Call `on_child` with the direct children of this source node, such as
+  /// the children of a `ConcatSource` or the inner source of a
+  /// `ReplaceSource`. A `ConcatSource` visits `ConcatSource::children`, where
+  /// adjacent `RawStringSource`s are merged into one child.
+  fn visit_children(&self, _on_child: &mut dyn FnMut(&BoxSource)) {}
+
+  /// Create a copy of this source node with `children` in place of the
+  /// children passed to [Source::visit_children]. Returns `None` for sources
+  /// without children or when the number of children doesn't match.
+  fn with_children(&self, _children: Vec<BoxSource>) -> Option<BoxSource> {
+    None
+  }
+
+  /// Hash the emitted bytes of the source with a stable algorithm.
+  ///
+  /// Unlike [Source::update_hash], the result only depends on the content,
+  /// not on how the source is composed, and is stable across releases.
+  ///
+  /// # Errors
+  ///
+  /// Returns the error of [Source::to_writer] when the content can't be
+  /// read, like for a `FileSource` whose file changed.
+  #[cfg(feature = "content-hash")]
+  fn content_hash(
+    &self,
+    algorithm: crate::ContentHashAlgorithm,
+  ) -> std::io::Result<u64> {
+    crate::content_hash::content_hash(self, algorithm)
+  }
 }
 
 impl Source for BoxSource {
@@ -173,6 +238,34 @@ impl Source for BoxSource {
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     self.as_ref().to_writer(writer)
   }
+
+  #[inline]
+  fn map_to_writer(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+    json_options: &SourceMapJsonOptions,
+    writer: &mut dyn std::io::Write,
+  ) -> Result<bool> {
+    self
+      .as_ref()
+      .map_to_writer(object_pool, options, json_options, writer)
+  }
+
+  #[inline]
+  fn kind(&self) -> SourceKind {
+    self.as_ref().kind()
+  }
+
+  #[inline]
+  fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+    self.as_ref().visit_children(on_child)
+  }
+
+  #[inline]
+  fn with_children(&self, children: Vec<BoxSource>) -> Option<BoxSource> {
+    self.as_ref().with_children(children)
+  }
 }
 
 dyn_clone::clone_trait_object!(Source);
@@ -255,13 +348,47 @@ impl<T: Source + 'static> SourceExt for T {
   }
 }
 
+/// A predicate on the source names of a [SourceMap].
+pub type SourcePredicate = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
 /// Options for [Source::map].
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct MapOptions {
   /// Whether have columns info in generated [SourceMap] mappings.
   pub columns: bool,
   /// Whether the source will have changes, internal used for `ReplaceSource`, etc.
   pub(crate) final_source: bool,
+  /// Sources matching this predicate are added to the ignoreList.
+  pub(crate) ignore_list: Option<SourcePredicate>,
+  /// Whether the generated [SourceMap] has sourcesContent.
+  pub(crate) sources_content: bool,
+  /// Whether the generated [SourceMap] has names.
+  pub(crate) names: bool,
+  /// The file of the generated [SourceMap].
+  pub(crate) file: Option<Arc<str>>,
+  /// The sourceRoot of the generated [SourceMap].
+  pub(crate) source_root: Option<Arc<str>>,
+  /// Sources matching this predicate are left out of the generated
+  /// [SourceMap].
+  pub(crate) exclude_sources: Option<SourcePredicate>,
+}
+
+impl fmt::Debug for MapOptions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("MapOptions")
+      .field("columns", &self.columns)
+      .field("final_source", &self.final_source)
+      .field("ignore_list", &self.ignore_list.as_ref().map(|_| ".."))
+      .field("sources_content", &self.sources_content)
+      .field("names", &self.names)
+      .field("file", &self.file)
+      .field("source_root", &self.source_root)
+      .field(
+        "exclude_sources",
+        &self.exclude_sources.as_ref().map(|_| ".."),
+      )
+      .finish()
+  }
 }
 
 impl Default for MapOptions {
@@ -269,6 +396,12 @@ impl Default for MapOptions {
     Self {
       columns: true,
       final_source: false,
+      ignore_list: None,
+      sources_content: true,
+      names: true,
+      file: None,
+      source_root: None,
+      exclude_sources: None,
     }
   }
 }
@@ -281,6 +414,255 @@ impl MapOptions {
       ..Default::default()
     }
   }
+
+  /// Add the sources whose name matches `predicate` to the ignoreList of the
+  /// generated [SourceMap], in addition to the sources already ignored by
+  /// the maps of the composed sources.
+  ///
+  /// ```
+  /// use rspack_sources::{
+  ///   ConcatSource, MapOptions, ObjectPool, OriginalSource, Source,
+  ///   SourceExt,
+  /// };
+  ///
+  /// let source = ConcatSource::new([
+  ///   OriginalSource::new("a;\n", "src/a.js").boxed(),
+  ///   OriginalSource::new("b;\n", "node_modules/b/index.js").boxed(),
+  /// ]);
+  /// let options = MapOptions::default()
+  ///   .with_ignore_list(|source| source.starts_with("node_modules/"));
+  /// let map = source.map(&ObjectPool::default(), &options).unwrap();
+  /// assert_eq!(map.ignore_list(), Some(&[1][..]));
+  /// ```
+  pub fn with_ignore_list<F>(mut self, predicate: F) -> Self
+  where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+  {
+    self.ignore_list = Some(Arc::new(predicate));
+    self
+  }
+
+  /// Whether the generated [SourceMap] has sourcesContent, `true` by
+  /// default. Without it the map is like a `nosources-source-map`.
+  pub fn with_sources_content(mut self, sources_content: bool) -> Self {
+    self.sources_content = sources_content;
+    self
+  }
+
+  /// Whether the generated [SourceMap] has names and name indices in its
+  /// mappings, `true` by default.
+  pub fn with_names(mut self, names: bool) -> Self {
+    self.names = names;
+    self
+  }
+
+  /// Set the file of the generated [SourceMap].
+  pub fn with_file<T: Into<Arc<str>>>(mut self, file: T) -> Self {
+    self.file = Some(file.into());
+    self
+  }
+
+  /// Set the sourceRoot of the generated [SourceMap].
+  pub fn with_source_root<T: Into<Arc<str>>>(mut self, source_root: T) -> Self {
+    self.source_root = Some(source_root.into());
+    self
+  }
+
+  /// Leave the sources whose name matches `predicate` out of the generated
+  /// [SourceMap], code from them is not mapped.
+  ///
+  /// ```
+  /// use rspack_sources::{
+  ///   ConcatSource, MapOptions, ObjectPool, OriginalSource, Source,
+  ///   SourceExt,
+  /// };
+  ///
+  /// let source = ConcatSource::new([
+  ///   OriginalSource::new("a;\n", "webpack/runtime").boxed(),
+  ///   OriginalSource::new("b;\n", "src/b.js").boxed(),
+  /// ]);
+  /// let options = MapOptions::default()
+  ///   .with_exclude_sources(|source| source.starts_with("webpack/"))
+  ///   .with_sources_content(false)
+  ///   .with_file("main.js");
+  /// let map = source.map(&ObjectPool::default(), &options).unwrap();
+  /// assert_eq!(map.sources(), ["src/b.js"]);
+  /// assert!(map.sources_content().is_empty());
+  /// assert_eq!(map.file(), Some("main.js"));
+  /// assert_eq!(map.mappings(), ";AAAA");
+  /// ```
+  pub fn with_exclude_sources<F>(mut self, predicate: F) -> Self
+  where
+    F: Fn(&str) -> bool + Send + Sync + 'static,
+  {
+    self.exclude_sources = Some(Arc::new(predicate));
+    self
+  }
+
+  /// Options for streaming inner sources, which only keep `columns`.
+  pub(crate) fn inner(&self, final_source: bool) -> Self {
+    Self {
+      columns: self.columns,
+      final_source,
+      ..Default::default()
+    }
+  }
+
+  /// Whether [MapOptions::apply_to_map] changes a map generated with only
+  /// `columns` set.
+  pub(crate) fn changes_map(&self) -> bool {
+    self.ignore_list.is_some()
+      || !self.sources_content
+      || !self.names
+      || self.file.is_some()
+      || self.source_root.is_some()
+      || self.exclude_sources.is_some()
+  }
+
+  /// Whether maps generated with `self` and `other` are the same, predicates
+  /// are compared by identity.
+  pub(crate) fn generates_same_map(&self, other: &MapOptions) -> bool {
+    fn same_predicate(
+      a: &Option<SourcePredicate>,
+      b: &Option<SourcePredicate>,
+    ) -> bool {
+      match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+      }
+    }
+    self.columns == other.columns
+      && self.sources_content == other.sources_content
+      && self.names == other.names
+      && self.file == other.file
+      && self.source_root == other.source_root
+      && same_predicate(&self.ignore_list, &other.ignore_list)
+      && same_predicate(&self.exclude_sources, &other.exclude_sources)
+  }
+
+  /// Apply the options besides `columns` to a map generated without them.
+  /// The mappings are re-encoded once, only when names or sources are left
+  /// out.
+  pub(crate) fn apply_to_map(&self, map: &mut SourceMap) {
+    let new_source_indices = self
+      .exclude_sources
+      .as_ref()
+      .and_then(|predicate| new_source_indices(map, predicate));
+    let remove_names = !self.names && !map.names.is_empty();
+    if new_source_indices.is_some() || remove_names {
+      let mappings =
+        encode_mappings(map.decoded_mappings().map(|mut mapping| {
+          if remove_names {
+            if let Some(original) = &mut mapping.original {
+              original.name_index = None;
+            }
+          }
+          if let Some(new_indices) = &new_source_indices {
+            mapping.original = mapping.original.and_then(|mut original| {
+              original.source_index =
+                (*new_indices.get(original.source_index as usize)?)?;
+              Some(original)
+            });
+          }
+          mapping
+        }));
+      map.mappings = mappings.into();
+    }
+    if let Some(new_indices) = &new_source_indices {
+      retain_sources(map, new_indices);
+    }
+    if remove_names {
+      map.names = Arc::from([]);
+      // Function mappings and scopes refer to names.
+      map.sources_function_mappings = None;
+      map.original_scopes = None;
+      map.generated_ranges = None;
+    }
+    if !self.sources_content {
+      map.sources_content = Arc::from([]);
+    }
+    if let Some(file) = &self.file {
+      map.file = Some(file.clone());
+    }
+    if let Some(source_root) = &self.source_root {
+      map.source_root = Some(source_root.clone());
+    }
+    self.apply_ignore_list(map);
+  }
+
+  /// Add the sources of `map` matching the ignore list predicate to its
+  /// ignoreList.
+  fn apply_ignore_list(&self, map: &mut SourceMap) {
+    let Some(predicate) = &self.ignore_list else {
+      return;
+    };
+    let mut ignore_list = map.ignore_list().unwrap_or_default().to_vec();
+    ignore_list.extend(
+      (0..map.sources().len() as u32)
+        .filter(|index| predicate(&map.sources()[*index as usize])),
+    );
+    ignore_list.sort_unstable();
+    ignore_list.dedup();
+    map.set_ignore_list((!ignore_list.is_empty()).then_some(ignore_list));
+  }
+}
+
+// The new index of every source of `map`, `None` for the sources matching
+// `predicate`. Returns `None` when no source matches.
+fn new_source_indices(
+  map: &SourceMap,
+  predicate: &SourcePredicate,
+) -> Option<Vec<Option<u32>>> {
+  let mut new_indices = Vec::with_capacity(map.sources.len());
+  let mut kept = 0;
+  for source in map.sources.iter() {
+    if predicate(source) {
+      new_indices.push(None);
+    } else {
+      new_indices.push(Some(kept));
+      kept += 1;
+    }
+  }
+  (kept as usize != map.sources.len()).then_some(new_indices)
+}
+
+// Keep only the per source fields of `map` for sources with a new index.
+fn retain_sources(map: &mut SourceMap, new_indices: &[Option<u32>]) {
+  let keep = |index: usize| new_indices.get(index).is_some_and(Option::is_some);
+  fn retain<T: Clone>(items: &[T], keep: impl Fn(usize) -> bool) -> Vec<T> {
+    items
+      .iter()
+      .enumerate()
+      .filter(|(index, _)| keep(*index))
+      .map(|(_, item)| item.clone())
+      .collect()
+  }
+  map.sources = retain(&map.sources, keep).into();
+  map.sources_content = retain(&map.sources_content, keep).into();
+  if let Some(function_mappings) = &map.sources_function_mappings {
+    map.sources_function_mappings =
+      Some(retain(function_mappings, keep).into());
+  }
+  if let Some(original_scopes) = &map.original_scopes {
+    map.original_scopes = Some(retain(original_scopes, keep).into());
+  }
+  if let Some(generated_ranges) = &map.generated_ranges {
+    map.generated_ranges =
+      decode_generated_ranges(generated_ranges).map(|mut ranges| {
+        for range in &mut ranges {
+          range.remap_sources(&|index| *new_indices.get(index as usize)?);
+        }
+        encode_generated_ranges(&ranges).into()
+      });
+  }
+  if let Some(ignore_list) = &map.ignore_list {
+    let ignore_list = ignore_list
+      .iter()
+      .filter_map(|index| *new_indices.get(*index as usize)?)
+      .collect::<Vec<_>>();
+    map.ignore_list = (!ignore_list.is_empty()).then(|| Arc::new(ignore_list));
+  }
 }
 
 fn is_all_empty(val: &[Arc<str>]) -> bool {
@@ -307,8 +689,35 @@ pub struct SourceMap {
   debug_id: Option<Arc<str>>,
   #[serde(rename = "ignoreList", skip_serializing_if = "Option::is_none")]
   ignore_list: Option<Arc<Vec<u32>>>,
+  #[serde(rename = "originalScopes", skip_serializing_if = "Option::is_none")]
+  original_scopes: Option<Arc<[String]>>,
+  #[serde(rename = "generatedRanges", skip_serializing_if = "Option::is_none")]
+  generated_ranges: Option<Arc<str>>,
+  #[serde(
+    rename = "x_com_bloomberg_sourcesFunctionMappings",
+    skip_serializing_if = "Option::is_none"
+  )]
+  sources_function_mappings: Option<Arc<[String]>>,
+  #[serde(flatten, skip_serializing_if = "Option::is_none")]
+  extensions: Option<Arc<BTreeMap<String, serde_json::Value>>>,
 }
 
+// Top-level fields with a dedicated representation in [SourceMap].
+const KNOWN_FIELDS: &[&str] = &[
+  "version",
+  "file",
+  "sources",
+  "sourcesContent",
+  "names",
+  "mappings",
+  "sourceRoot",
+  "debugId",
+  "ignoreList",
+  "originalScopes",
+  "generatedRanges",
+  "x_com_bloomberg_sourcesFunctionMappings",
+];
+
 impl std::fmt::Debug for SourceMap {
   fn fmt(
     &self,
@@ -335,6 +744,18 @@ impl Hash for SourceMap {
     self.names.hash(state);
     self.source_root.hash(state);
     self.ignore_list.hash(state);
+    if let Some(original_scopes) = &self.original_scopes {
+      original_scopes.hash(state);
+    }
+    if let Some(generated_ranges) = &self.generated_ranges {
+      generated_ranges.hash(state);
+    }
+    if let Some(function_mappings) = &self.sources_function_mappings {
+      function_mappings.hash(state);
+    }
+    if let Some(extensions) = &self.extensions {
+      extensions.hash(state);
+    }
   }
 }
 
@@ -362,6 +783,10 @@ impl SourceMap {
       source_root: None,
       debug_id: None,
       ignore_list: None,
+      original_scopes: None,
+      generated_ranges: None,
+      sources_function_mappings: None,
+      extensions: None,
     }
   }
 
@@ -385,6 +810,106 @@ impl SourceMap {
     self.ignore_list = ignore_list.map(|v| Arc::new(v.into()));
   }
 
+  /// Get the originalScopes field of the scopes proposal in [SourceMap].
+  ///
+  /// It is preserved as is, but not carried over into source maps generated
+  /// by composing sources.
+  pub fn original_scopes(&self) -> Option<&[String]> {
+    self.original_scopes.as_deref()
+  }
+
+  /// Set the originalScopes field of the scopes proposal in [SourceMap].
+  pub fn set_original_scopes<T: Into<Arc<[String]>>>(
+    &mut self,
+    original_scopes: Option<T>,
+  ) {
+    self.original_scopes = original_scopes.map(Into::into);
+  }
+
+  /// Get the generatedRanges field of the scopes proposal in [SourceMap].
+  ///
+  /// It is preserved as is, but not carried over into source maps generated
+  /// by composing sources.
+  pub fn generated_ranges(&self) -> Option<&str> {
+    self.generated_ranges.as_deref()
+  }
+
+  /// Set the generatedRanges field of the scopes proposal in [SourceMap].
+  pub fn set_generated_ranges<T: Into<Arc<str>>>(
+    &mut self,
+    generated_ranges: Option<T>,
+  ) {
+    self.generated_ranges = generated_ranges.map(Into::into);
+  }
+
+  /// Get the x_com_bloomberg_sourcesFunctionMappings field in [SourceMap].
+  pub fn sources_function_mappings(&self) -> Option<&[String]> {
+    self.sources_function_mappings.as_deref()
+  }
+
+  /// Set the x_com_bloomberg_sourcesFunctionMappings field in [SourceMap].
+  pub fn set_sources_function_mappings<T: Into<Arc<[String]>>>(
+    &mut self,
+    sources_function_mappings: Option<T>,
+  ) {
+    self.sources_function_mappings = sources_function_mappings.map(Into::into);
+  }
+
+  /// Get the decoded function mappings of the source at `index`, see
+  /// [decode_function_mappings](crate::decode_function_mappings).
+  pub fn get_function_mappings(
+    &self,
+    index: usize,
+  ) -> Option<Vec<FunctionMapping>> {
+    decode_function_mappings(self.sources_function_mappings()?.get(index)?)
+  }
+
+  /// Get the unrecognized top-level fields in [SourceMap], such as
+  /// `x_google_ignoreList` or `x_facebook_sources`.
+  pub fn extensions(
+    &self,
+  ) -> impl Iterator<Item = (&str, &serde_json::Value)> + '_ {
+    self
+      .extensions
+      .iter()
+      .flat_map(|extensions| extensions.iter())
+      .map(|(key, value)| (key.as_str(), value))
+  }
+
+  /// Get an unrecognized top-level field by key in [SourceMap].
+  pub fn get_extension(&self, key: &str) -> Option<&serde_json::Value> {
+    self.extensions.as_ref()?.get(key)
+  }
+
+  /// Set an unrecognized top-level field in [SourceMap], `None` removes it.
+  ///
+  /// Keys of fields that [SourceMap] represents itself, like `mappings`, are
+  /// ignored, use the dedicated setters for them.
+  pub fn set_extension<K: Into<String>>(
+    &mut self,
+    key: K,
+    value: Option<serde_json::Value>,
+  ) {
+    let key = key.into();
+    if KNOWN_FIELDS.contains(&key.as_str()) {
+      return;
+    }
+    let mut extensions = self
+      .extensions
+      .take()
+      .map(Arc::unwrap_or_clone)
+      .unwrap_or_default();
+    match value {
+      Some(value) => {
+        extensions.insert(key, value);
+      }
+      None => {
+        extensions.remove(&key);
+      }
+    }
+    self.extensions = (!extensions.is_empty()).then(|| Arc::new(extensions));
+  }
+
   /// Get the decoded mappings in [SourceMap].
   pub fn decoded_mappings(&self) -> impl Iterator<Item = Mapping> + '_ {
     decode_mappings(self)
@@ -464,20 +989,73 @@ impl SourceMap {
   }
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default)]
 struct RawSourceMap {
   pub file: Option<String>,
   pub sources: Option<Vec<Option<String>>>,
-  #[serde(rename = "sourceRoot")]
   pub source_root: Option<String>,
-  #[serde(rename = "sourcesContent")]
   pub sources_content: Option<Vec<Option<String>>>,
   pub names: Option<Vec<Option<String>>>,
   pub mappings: String,
-  #[serde(rename = "debugId")]
   pub debug_id: Option<String>,
-  #[serde(rename = "ignoreList")]
   pub ignore_list: Option<Vec<u32>>,
+  pub original_scopes: Option<Vec<Option<String>>>,
+  pub generated_ranges: Option<String>,
+  pub sources_function_mappings: Option<Vec<Option<String>>>,
+  pub extensions: BTreeMap<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for RawSourceMap {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> std::result::Result<Self, D::Error> {
+    struct RawSourceMapVisitor;
+
+    impl<'de> Visitor<'de> for RawSourceMapVisitor {
+      type Value = RawSourceMap;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a source map object")
+      }
+
+      fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+      ) -> std::result::Result<RawSourceMap, A::Error> {
+        let mut raw = RawSourceMap::default();
+        let mut mappings = None;
+        while let Some(key) = map.next_key::<String>()? {
+          match key.as_str() {
+            "version" => {
+              map.next_value::<IgnoredAny>()?;
+            }
+            "file" => raw.file = map.next_value()?,
+            "sources" => raw.sources = map.next_value()?,
+            "sourceRoot" => raw.source_root = map.next_value()?,
+            "sourcesContent" => raw.sources_content = map.next_value()?,
+            "names" => raw.names = map.next_value()?,
+            "mappings" => mappings = Some(map.next_value()?),
+            "debugId" => raw.debug_id = map.next_value()?,
+            "ignoreList" => raw.ignore_list = map.next_value()?,
+            "originalScopes" => raw.original_scopes = map.next_value()?,
+            "generatedRanges" => raw.generated_ranges = map.next_value()?,
+            "x_com_bloomberg_sourcesFunctionMappings" => {
+              raw.sources_function_mappings = map.next_value()?
+            }
+            _ => {
+              let value = map.next_value()?;
+              raw.extensions.insert(key, value);
+            }
+          }
+        }
+        raw.mappings =
+          mappings.ok_or_else(|| de::Error::missing_field("mappings"))?;
+        Ok(raw)
+      }
+    }
+
+    deserializer.deserialize_map(RawSourceMapVisitor)
+  }
 }
 
 impl RawSourceMap {
@@ -523,7 +1101,12 @@ impl SourceMap {
 
   /// Generate source map to writer.
   pub fn to_writer<W: std::io::Write>(self, w: W) -> Result<()> {
-    simd_json::serde::to_writer(w, &self)?;
+    self.write_json(w)
+  }
+
+  /// Generate source map to writer without consuming the [SourceMap].
+  pub fn write_json<W: std::io::Write>(&self, w: W) -> Result<()> {
+    simd_json::serde::to_writer(w, self)?;
     Ok(())
   }
 }
@@ -557,7 +1140,27 @@ impl TryFrom<RawSourceMap> for SourceMap {
       .into();
     let source_root = raw.source_root.map(Into::into);
     let debug_id = raw.debug_id.map(Into::into);
-    let ignore_list = raw.ignore_list.map(Into::into);
+    let ignore_list = raw
+      .ignore_list
+      .or_else(|| {
+        let value = raw.extensions.get("x_google_ignoreList")?;
+        serde_json::from_value(value.clone()).ok()
+      })
+      .map(Into::into);
+    let original_scopes = raw.original_scopes.map(|scopes| {
+      scopes
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect::<Vec<_>>()
+        .into()
+    });
+    let generated_ranges = raw.generated_ranges.map(Into::into);
+    let sources_function_mappings = raw.sources_function_mappings.map(|m| {
+      m.into_iter()
+        .map(Option::unwrap_or_default)
+        .collect::<Vec<_>>()
+        .into()
+    });
 
     Ok(Self {
       version: 3,
@@ -569,6 +1172,11 @@ impl TryFrom<RawSourceMap> for SourceMap {
       source_root,
       debug_id,
       ignore_list,
+      original_scopes,
+      generated_ranges,
+      sources_function_mappings,
+      extensions: (!raw.extensions.is_empty())
+        .then(|| Arc::new(raw.extensions)),
     })
   }
 }
@@ -637,7 +1245,8 @@ mod tests {
 
   use crate::{
     CachedSource, ConcatSource, OriginalSource, RawBufferSource,
-    RawStringSource, ReplaceSource, SourceMapSource, WithoutOriginalOptions,
+    RawStringSource, ReplaceSource, SourceMapSource, SourceMapSourceOptions,
+    WithoutOriginalOptions,
   };
 
   use super::*;
@@ -795,4 +1404,519 @@ mod tests {
       "ab"
     );
   }
+
+  #[test]
+  fn write_json_matches_to_json() {
+    let map = SourceMap::from_json(
+      r#"{"version":3,"file":"a.js","sources":["a.js"],"sourcesContent":["a\n\"b\""],"names":["x"],"mappings":"AAAAA"}"#,
+    )
+    .unwrap();
+    let mut json = vec![];
+    map.write_json(&mut json).unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(), map.to_json().unwrap());
+  }
+
+  #[test]
+  fn map_to_writer_matches_map() {
+    let object_pool = ObjectPool::default();
+    let mut replace = ReplaceSource::new(
+      OriginalSource::new("let a = \"\u{1F600}\";\nb();", "src/a.js")
+        .with_names(true),
+    );
+    replace.replace(0, 3, "var", Some("let"));
+    let source_map_source = SourceMapSource::new(WithoutOriginalOptions {
+      value: "c();",
+      name: "c.js",
+      source_map: OriginalSource::new("c();", "c.js")
+        .map(&object_pool, &MapOptions::default())
+        .unwrap(),
+    });
+    let sources: Vec<BoxSource> = vec![
+      replace.clone().boxed(),
+      source_map_source.clone().boxed(),
+      CachedSource::new(replace.clone()).boxed(),
+      ConcatSource::new([
+        RawStringSource::from_static("// header\n").boxed(),
+        replace.boxed(),
+        source_map_source.boxed(),
+      ])
+      .boxed(),
+    ];
+    for source in sources {
+      for columns in [true, false] {
+        let options = MapOptions::new(columns);
+        let mut json = vec![];
+        assert!(source
+          .map_to_writer(
+            &object_pool,
+            &options,
+            &SourceMapJsonOptions::default(),
+            &mut json
+          )
+          .unwrap());
+        assert_eq!(
+          SourceMap::from_slice(&json).unwrap(),
+          source.map(&object_pool, &options).unwrap()
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn map_to_writer_flushes_large_mappings() {
+    let object_pool = ObjectPool::default();
+    let line = "a;b;c;d;e;f;g;h;\n".repeat(10_000);
+    let source = ConcatSource::new([
+      OriginalSource::new(line.clone(), "a.js").boxed(),
+      OriginalSource::new(line, "b.js").boxed(),
+    ]);
+    let mut json = vec![];
+    assert!(source
+      .map_to_writer(
+        &object_pool,
+        &MapOptions::default(),
+        &SourceMapJsonOptions::default(),
+        &mut json
+      )
+      .unwrap());
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert!(map.mappings().len() > 64 * 1024);
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+  }
+
+  #[test]
+  fn map_to_writer_uses_json_options() {
+    let object_pool = ObjectPool::default();
+    let source = ConcatSource::new([
+      OriginalSource::new("const 魑 = 1;\n", "a.js").boxed(),
+      OriginalSource::new("b();", "b.js").boxed(),
+    ]);
+    let options = MapOptions::default().with_file("main.js");
+    let map = source.map(&object_pool, &options).unwrap();
+    for json_options in [
+      SourceMapJsonOptions {
+        pretty: true,
+        escape_non_ascii: true,
+        emit_empty_names: false,
+        ..Default::default()
+      },
+      SourceMapJsonOptions {
+        pretty: true,
+        canonical: true,
+        ..Default::default()
+      },
+    ] {
+      let mut json = vec![];
+      assert!(source
+        .map_to_writer(&object_pool, &options, &json_options, &mut json)
+        .unwrap());
+      let json = String::from_utf8(json).unwrap();
+      assert_eq!(SourceMap::from_json(&json).unwrap(), map);
+      if json_options.canonical {
+        assert_eq!(json, map.to_json_with_options(&json_options).unwrap());
+      } else {
+        assert!(json.starts_with("{\n  \"mappings\": \""));
+        assert!(json.contains("\\u9b51"));
+        assert!(!json.contains("\"names\""));
+      }
+    }
+  }
+
+  #[test]
+  fn scopes_fields_round_trip() {
+    let json = r#"{"version":3,"sources":["a.js"],"names":["f"],"mappings":"AAAA","originalScopes":["AACAA"],"generatedRanges":"AAAA","x_com_bloomberg_sourcesFunctionMappings":["AAAEC"]}"#;
+    let map = SourceMap::from_json(json).unwrap();
+    assert_eq!(map.original_scopes(), Some(&["AACAA".to_string()][..]));
+    assert_eq!(map.generated_ranges(), Some("AAAA"));
+    assert_eq!(
+      map.get_function_mappings(0),
+      Some(vec![FunctionMapping {
+        name_index: 0,
+        start_line: 0,
+        start_column: 0,
+        end_line: 2,
+        end_column: 1,
+      }])
+    );
+    assert_eq!(map.to_json().unwrap(), json);
+
+    let mut state = twox_hash::XxHash64::default();
+    map.hash(&mut state);
+    let mut without_scopes = map.clone();
+    without_scopes.set_original_scopes(None::<Vec<String>>);
+    let mut other_state = twox_hash::XxHash64::default();
+    without_scopes.hash(&mut other_state);
+    assert_ne!(state.finish(), other_state.finish());
+  }
+
+  #[test]
+  fn extension_fields_round_trip() {
+    let json = r#"{"version":3,"sources":["a.js","node_modules/b.js"],"names":[],"mappings":"AAAA","x_facebook_sources":[null,[{"names":["<global>"],"mappings":"AAA"}]],"x_google_ignoreList":[1]}"#;
+    let mut map = SourceMap::from_json(json).unwrap();
+    assert_eq!(map.ignore_list(), Some(&[1][..]));
+    assert_eq!(
+      map.get_extension("x_google_ignoreList"),
+      Some(&serde_json::json!([1]))
+    );
+    assert_eq!(
+      map.extensions().map(|(key, _)| key).collect::<Vec<_>>(),
+      ["x_facebook_sources", "x_google_ignoreList"]
+    );
+    assert_eq!(
+      SourceMap::from_json(&map.to_json().unwrap()).unwrap(),
+      map.clone()
+    );
+
+    map.set_extension("mappings", Some(serde_json::json!("")));
+    assert_eq!(map.mappings(), "AAAA");
+    assert_eq!(map.get_extension("mappings"), None);
+    map.set_extension("x_facebook_sources", None);
+    map.set_extension("x_google_ignoreList", None);
+    assert_eq!(map.extensions().count(), 0);
+    assert_eq!(
+      map.to_json().unwrap(),
+      r#"{"version":3,"sources":["a.js","node_modules/b.js"],"names":[],"mappings":"AAAA","ignoreList":[1]}"#
+    );
+
+    let map = SourceMap::from_json(
+      r#"{"mappings":"","ignoreList":[0],"x_google_ignoreList":[1]}"#,
+    )
+    .unwrap();
+    assert_eq!(map.ignore_list(), Some(&[0][..]));
+  }
+
+  #[test]
+  fn function_mappings_survive_composition() {
+    let object_pool = ObjectPool::default();
+    let function = |name_index, start_line, end_line| FunctionMapping {
+      name_index,
+      start_line,
+      start_column: 0,
+      end_line,
+      end_column: 1,
+    };
+    let module = |name: &str, names: &[&str], mappings: &[FunctionMapping]| {
+      let mut map = SourceMap::new(
+        "AAAA;AACA",
+        vec![name.to_string()],
+        vec![],
+        names.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+      );
+      map.set_sources_function_mappings(Some(vec![
+        crate::encode_function_mappings(mappings),
+      ]));
+      SourceMapSource::new(WithoutOriginalOptions {
+        value: "a();
+b();
+",
+        name,
+        source_map: map,
+      })
+    };
+    let source = ConcatSource::new([
+      module("a.js", &["a", "b"], &[function(1, 0, 3), function(0, 1, 2)])
+        .boxed(),
+      RawStringSource::from_static(
+        "// separator
+",
+      )
+      .boxed(),
+      module("b.js", &["c", "a"], &[function(0, 0, 1), function(1, 2, 3)])
+        .boxed(),
+    ]);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    let resolve = |index: usize| {
+      map
+        .get_function_mappings(index)
+        .unwrap()
+        .iter()
+        .map(|m| (map.get_name(m.name_index as usize).unwrap(), m.start_line))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(map.sources(), ["a.js", "b.js"]);
+    assert_eq!(resolve(0), [("b", 0), ("a", 1)]);
+    assert_eq!(resolve(1), [("c", 0), ("a", 2)]);
+
+    let mut json = vec![];
+    source
+      .map_to_writer(
+        &object_pool,
+        &MapOptions::default(),
+        &SourceMapJsonOptions::default(),
+        &mut json,
+      )
+      .unwrap();
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+
+    let combined = SourceMapSource::new(SourceMapSourceOptions {
+      value: "a();\nb();\n// separator\na();\nb();\n",
+      name: "bundle.js",
+      source_map: SourceMap::new(
+        "AAAA",
+        vec!["bundle.js".into()],
+        vec![],
+        vec![],
+      ),
+      original_source: Some(source.source().into_string_lossy().into()),
+      inner_source_map: Some(map.clone()),
+      remove_original_source: false,
+    });
+    let combined_map =
+      combined.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(combined_map.sources(), ["a.js"]);
+    let function_mappings = combined_map.get_function_mappings(0).unwrap();
+    assert_eq!(
+      combined_map.get_name(function_mappings[0].name_index as usize),
+      Some("b")
+    );
+  }
+
+  #[test]
+  fn scopes_survive_composition() {
+    use crate::{
+      decode_generated_ranges, decode_original_scopes, encode_generated_ranges,
+      encode_original_scopes, GeneratedRange, OriginalScope, ScopeDefinition,
+    };
+
+    let object_pool = ObjectPool::default();
+    let module = |name: &str, names: &[&str]| {
+      let mut map = SourceMap::new(
+        "AAAA;AACA",
+        vec![name.to_string()],
+        vec![],
+        names.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+      );
+      map.set_original_scopes(Some(vec![encode_original_scopes(&[
+        OriginalScope {
+          start_line: 0,
+          start_column: 0,
+          end_line: 2,
+          end_column: 0,
+          kind_index: 0,
+          name_index: Some(1),
+          is_stack_frame: true,
+          is_hidden: false,
+          variables: vec![1],
+          children: vec![],
+        },
+      ])]));
+      map.set_generated_ranges(Some(encode_generated_ranges(&[
+        GeneratedRange {
+          start_line: 0,
+          start_column: 0,
+          end_line: 1,
+          end_column: 4,
+          definition: Some(ScopeDefinition {
+            source_index: 0,
+            scope_index: 0,
+          }),
+          callsite: None,
+          is_stack_frame: true,
+          is_hidden: false,
+          bindings: vec![],
+          children: vec![],
+        },
+      ])));
+      SourceMapSource::new(WithoutOriginalOptions {
+        value: "a();\nb();\n",
+        name,
+        source_map: map,
+      })
+    };
+    let source = ConcatSource::new([
+      module("a.js", &["scope", "a"]).boxed(),
+      RawStringSource::from_static("// separator\n").boxed(),
+      module("b.js", &["scope", "b"]).boxed(),
+    ]);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(map.sources(), ["a.js", "b.js"]);
+
+    let original_scopes = map.original_scopes().unwrap();
+    assert_eq!(original_scopes.len(), 2);
+    let scope_names = original_scopes
+      .iter()
+      .map(|scopes| {
+        let scope = &decode_original_scopes(scopes).unwrap()[0];
+        (
+          map.get_name(scope.kind_index as usize).unwrap(),
+          map.get_name(scope.name_index.unwrap() as usize).unwrap(),
+          map.get_name(scope.variables[0] as usize).unwrap(),
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(scope_names, [("scope", "a", "a"), ("scope", "b", "b")]);
+
+    let ranges = decode_generated_ranges(map.generated_ranges().unwrap())
+      .unwrap()
+      .into_iter()
+      .map(|range| {
+        (
+          range.start_line,
+          range.end_line,
+          range.definition.unwrap().source_index,
+        )
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(ranges, [(0, 1, 0), (3, 4, 1)]);
+
+    let mut json = vec![];
+    source
+      .map_to_writer(
+        &object_pool,
+        &MapOptions::default(),
+        &SourceMapJsonOptions::default(),
+        &mut json,
+      )
+      .unwrap();
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+
+    let map = source
+      .map(&object_pool, &MapOptions::default().with_names(false))
+      .unwrap();
+    assert_eq!(map.original_scopes(), None);
+    assert_eq!(map.generated_ranges(), None);
+  }
+
+  #[test]
+  fn ignore_list_survives_composition() {
+    let object_pool = ObjectPool::default();
+    let mut vendor_map = SourceMap::new(
+      "AAAA;ACAA",
+      vec!["vendor/a.js".into(), "vendor/b.js".into()],
+      vec![],
+      vec![],
+    );
+    vendor_map.set_ignore_list(Some(vec![1]));
+    let source = ConcatSource::new([
+      OriginalSource::new("app();\n", "src/app.js").boxed(),
+      SourceMapSource::new(WithoutOriginalOptions {
+        value: "a();\nb();\n",
+        name: "vendor.js",
+        source_map: vendor_map,
+      })
+      .boxed(),
+      OriginalSource::new("lib();\n", "node_modules/lib.js").boxed(),
+    ]);
+    let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(
+      map.sources(),
+      [
+        "src/app.js",
+        "vendor/a.js",
+        "vendor/b.js",
+        "node_modules/lib.js"
+      ]
+    );
+    assert_eq!(map.ignore_list(), Some(&[2][..]));
+
+    let options = MapOptions::default()
+      .with_ignore_list(|source| source.starts_with("node_modules/"));
+    let map = source.map(&object_pool, &options).unwrap();
+    assert_eq!(map.ignore_list(), Some(&[2, 3][..]));
+    let mut json = vec![];
+    source
+      .map_to_writer(
+        &object_pool,
+        &options,
+        &SourceMapJsonOptions::default(),
+        &mut json,
+      )
+      .unwrap();
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+
+    let cached = CachedSource::new(source);
+    assert_eq!(cached.map(&object_pool, &options).unwrap(), map);
+    assert_eq!(
+      cached
+        .map(&object_pool, &MapOptions::default())
+        .unwrap()
+        .ignore_list(),
+      Some(&[2][..])
+    );
+    let mut json = vec![];
+    cached
+      .map_to_writer(
+        &object_pool,
+        &options,
+        &SourceMapJsonOptions::default(),
+        &mut json,
+      )
+      .unwrap();
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+  }
+
+  #[test]
+  fn map_options_leave_out_data() {
+    let object_pool = ObjectPool::default();
+    let vendor_map = SourceMap::from_json(
+      r#"{
+        "version": 3,
+        "sources": ["vendor/a.js", "vendor/b.js"],
+        "sourcesContent": ["a();", "b();"],
+        "names": ["a", "b"],
+        "mappings": "AAAAA;ACAAC",
+        "ignoreList": [1]
+      }"#,
+    )
+    .unwrap();
+    let source = ConcatSource::new([
+      OriginalSource::new("app();\n", "src/app.js").boxed(),
+      SourceMapSource::new(WithoutOriginalOptions {
+        value: "a();\nb();\n",
+        name: "vendor.js",
+        source_map: vendor_map,
+      })
+      .boxed(),
+    ]);
+    let options = MapOptions::default()
+      .with_names(false)
+      .with_sources_content(false)
+      .with_exclude_sources(|source| source == "vendor/a.js")
+      .with_file("main.js")
+      .with_source_root("/");
+    let map = source.map(&object_pool, &options).unwrap();
+    assert_eq!(map.sources(), ["src/app.js", "vendor/b.js"]);
+    assert!(map.sources_content().is_empty());
+    assert!(map.names().is_empty());
+    assert_eq!(map.ignore_list(), Some(&[1][..]));
+    assert_eq!(map.file(), Some("main.js"));
+    assert_eq!(map.source_root(), Some("/"));
+    assert_eq!(map.mappings(), "AAAA;;ACAA");
+
+    let full = source.map(&object_pool, &MapOptions::default()).unwrap();
+    let mut filtered = full.clone();
+    options.apply_to_map(&mut filtered);
+    assert_eq!(filtered, map);
+    let mut json = vec![];
+    source
+      .map_to_writer(
+        &object_pool,
+        &options,
+        &SourceMapJsonOptions::default(),
+        &mut json,
+      )
+      .unwrap();
+    assert_eq!(SourceMap::from_slice(&json).unwrap(), map);
+    let cached = CachedSource::new(source);
+    // Fill the cache with the full map first.
+    assert_eq!(
+      cached.map(&object_pool, &MapOptions::default()).unwrap(),
+      full
+    );
+    assert_eq!(cached.map(&object_pool, &options).unwrap(), map);
+  }
+
+  #[test]
+  fn map_to_writer_writes_nothing_without_map() {
+    let mut json = vec![];
+    assert!(!RawStringSource::from_static("a")
+      .map_to_writer(
+        &ObjectPool::default(),
+        &MapOptions::default(),
+        &SourceMapJsonOptions::default(),
+        &mut json
+      )
+      .unwrap());
+    assert!(json.is_empty());
+  }
 }
@@ -0,0 +1,590 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+  BoxSource, ConcatSourceBuilder, Error, RawStringSource, ReplaceSource,
+  ReplacementEnforce, Result, SliceSource, Source, SourceExt,
+};
+
+/// Edits a [Source] in the style of
+/// [magic-string](https://github.com/Rich-Harris/magic-string), with all
+/// offsets referring to the original code.
+///
+/// Content inserted "left" of an offset sticks to the code before it,
+/// content inserted "right" of an offset sticks to the code after it, which
+/// matters when that code is moved or removed. [SourceEditor::build] creates
+/// the edited source from [ReplaceSource]s over [SliceSource]s of the
+/// original, so the mappings of the original are kept, also for moved code.
+///
+/// ```
+/// use rspack_sources::{OriginalSource, Source, SourceEditor};
+///
+/// let mut editor =
+///   SourceEditor::new(OriginalSource::new("problems = 99", "file.js"));
+/// editor.overwrite(0, 8, "answer", None).unwrap();
+/// editor.prepend("var ").append(";");
+/// assert_eq!(editor.build().source().into_string_lossy(), "var answer = 99;");
+///
+/// let mut editor = SourceEditor::new(OriginalSource::new("abcdefghijkl", "a.js"));
+/// editor.move_range(0, 3, 6).unwrap();
+/// editor.remove(9, 12).unwrap();
+/// assert_eq!(editor.build().source().into_string_lossy(), "defabcghi");
+/// ```
+#[derive(Debug, Clone)]
+pub struct SourceEditor {
+  inner: BoxSource,
+  len: u32,
+  // The byte ranges of the chars of the original longer than one byte,
+  // offsets inside them are not char boundaries.
+  wide_chars: Vec<Range<u32>>,
+  intro: String,
+  outro: String,
+  insertions: BTreeMap<u32, Insertion>,
+  // Sorted and not overlapping.
+  edits: Vec<Edit>,
+  // Ranges of the original code in the order they are emitted.
+  segments: Vec<Range<u32>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Insertion {
+  left: String,
+  right: String,
+}
+
+#[derive(Debug, Clone)]
+struct Edit {
+  start: u32,
+  end: u32,
+  content: String,
+  name: Option<String>,
+}
+
+impl SourceEditor {
+  /// Create a [SourceEditor] of `source`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the code of `source` is larger than [u32::MAX] bytes.
+  pub fn new<T: SourceExt>(source: T) -> Self {
+    let inner = source.boxed();
+    let len = u32::try_from(inner.size())
+      .expect("source is too large to edit, its size must fit in a u32");
+    let mut wide_chars = Vec::new();
+    let mut pos = 0;
+    inner.rope(&mut |chunk| {
+      if !chunk.is_ascii() {
+        wide_chars.extend(chunk.char_indices().filter_map(|(index, c)| {
+          let start = pos + index as u32;
+          (c.len_utf8() > 1).then(|| start..start + c.len_utf8() as u32)
+        }));
+      }
+      pos += chunk.len() as u32;
+    });
+    Self {
+      inner,
+      len,
+      wide_chars,
+      intro: String::new(),
+      outro: String::new(),
+      insertions: BTreeMap::new(),
+      edits: Vec::new(),
+      #[allow(clippy::single_range_in_vec_init)]
+      segments: vec![0..len],
+    }
+  }
+
+  /// Insert `content` at the start of the generated code.
+  pub fn prepend(&mut self, content: &str) -> &mut Self {
+    self.intro.insert_str(0, content);
+    self
+  }
+
+  /// Insert `content` at the end of the generated code.
+  pub fn append(&mut self, content: &str) -> &mut Self {
+    self.outro.push_str(content);
+    self
+  }
+
+  /// Insert `content` at `index`, after content previously inserted left of
+  /// `index`. It moves and is removed with the code before `index`.
+  pub fn append_left(
+    &mut self,
+    index: u32,
+    content: &str,
+  ) -> Result<&mut Self> {
+    self.check_offset(index)?;
+    if index == 0 {
+      self.intro.push_str(content);
+    } else {
+      self.insertion(index).left.push_str(content);
+    }
+    Ok(self)
+  }
+
+  /// Insert `content` at `index`, before content previously inserted left of
+  /// `index`. It moves and is removed with the code before `index`.
+  pub fn prepend_left(
+    &mut self,
+    index: u32,
+    content: &str,
+  ) -> Result<&mut Self> {
+    self.check_offset(index)?;
+    if index == 0 {
+      self.intro.insert_str(0, content);
+    } else {
+      self.insertion(index).left.insert_str(0, content);
+    }
+    Ok(self)
+  }
+
+  /// Insert `content` at `index`, after content previously inserted right of
+  /// `index`. It moves and is removed with the code after `index`.
+  pub fn append_right(
+    &mut self,
+    index: u32,
+    content: &str,
+  ) -> Result<&mut Self> {
+    self.check_offset(index)?;
+    if index == self.len {
+      self.outro.push_str(content);
+    } else {
+      self.insertion(index).right.push_str(content);
+    }
+    Ok(self)
+  }
+
+  /// Insert `content` at `index`, before content previously inserted right
+  /// of `index`. It moves and is removed with the code after `index`.
+  pub fn prepend_right(
+    &mut self,
+    index: u32,
+    content: &str,
+  ) -> Result<&mut Self> {
+    self.check_offset(index)?;
+    if index == self.len {
+      self.outro.insert_str(0, content);
+    } else {
+      self.insertion(index).right.insert_str(0, content);
+    }
+    Ok(self)
+  }
+
+  /// Replace the code in `[start, end)` with `content`, removing content
+  /// previously inserted inside the range. `name` is added to the mapping of
+  /// `content`.
+  pub fn overwrite(
+    &mut self,
+    start: u32,
+    end: u32,
+    content: &str,
+    name: Option<&str>,
+  ) -> Result<&mut Self> {
+    self.check_range(start, end)?;
+    if start == end {
+      return Err(Error::InvalidEdit(
+        "cannot overwrite a zero-length range, use append_left or prepend_right instead".into(),
+      ));
+    }
+    // The content is emitted with the segment containing `start`, the
+    // following segments must follow it.
+    let overlapping = self
+      .segments
+      .iter()
+      .enumerate()
+      .filter(|(_, segment)| segment.start < end && segment.end > start)
+      .collect::<Vec<_>>();
+    if overlapping.windows(2).any(|pair| {
+      pair[0].0 + 1 != pair[1].0 || pair[0].1.end != pair[1].1.start
+    }) {
+      return Err(Error::InvalidEdit(format!(
+        "cannot overwrite {start}..{end} across moved code"
+      )));
+    }
+    self.edit(Edit {
+      start,
+      end,
+      content: content.into(),
+      name: name.map(Into::into),
+    })?;
+    Ok(self)
+  }
+
+  /// Remove the code in `[start, end)` and content previously inserted
+  /// inside the range.
+  pub fn remove(&mut self, start: u32, end: u32) -> Result<&mut Self> {
+    self.check_range(start, end)?;
+    if start < end {
+      self.edit(Edit {
+        start,
+        end,
+        content: String::new(),
+        name: None,
+      })?;
+    }
+    Ok(self)
+  }
+
+  /// Move the code in `[start, end)` to `index`, together with content
+  /// inserted right of `start` and left of `end`. Code moved to the same
+  /// `index` is emitted in the order it was moved, moving code to its own
+  /// `start` or `end` does nothing.
+  pub fn move_range(
+    &mut self,
+    start: u32,
+    end: u32,
+    index: u32,
+  ) -> Result<&mut Self> {
+    self.check_range(start, end)?;
+    self.check_offset(index)?;
+    if index == start || index == end {
+      return Ok(self);
+    }
+    if (start..end).contains(&index) {
+      return Err(Error::InvalidEdit(format!(
+        "cannot move {start}..{end} inside itself"
+      )));
+    }
+    for offset in [start, end, index] {
+      self.split(offset)?;
+    }
+    let (moved, mut segments): (Vec<_>, Vec<_>) =
+      std::mem::take(&mut self.segments)
+        .into_iter()
+        .partition(|segment| segment.start >= start && segment.end <= end);
+    let at = segments
+      .iter()
+      .position(|segment| segment.start == index)
+      .unwrap_or(segments.len());
+    segments.splice(at..at, moved);
+    self.segments = segments;
+    Ok(self)
+  }
+
+  /// Insert `indent` at the start of every line of the original code that
+  /// isn't empty, except in removed or overwritten code.
+  ///
+  /// Unlike magic-string, which also indents the lines of prepended,
+  /// appended and overwriting content, only lines of the original code are
+  /// indented. Indent inserted content before inserting it.
+  pub fn indent(&mut self, indent: &str) -> &mut Self {
+    if indent.is_empty() {
+      return self;
+    }
+    let mut line_starts = Vec::new();
+    let mut pos = 0;
+    let mut at_line_start = true;
+    self.inner.rope(&mut |chunk| {
+      for (index, byte) in chunk.bytes().enumerate() {
+        if at_line_start && byte != b'\n' && byte != b'\r' {
+          line_starts.push(pos + index as u32);
+        }
+        at_line_start = byte == b'\n';
+      }
+      pos += chunk.len() as u32;
+    });
+    for line_start in line_starts {
+      if !self
+        .edits
+        .iter()
+        .any(|edit| (edit.start..edit.end).contains(&line_start))
+      {
+        self.insertion(line_start).right.insert_str(0, indent);
+      }
+    }
+    self
+  }
+
+  /// Create the edited [Source].
+  ///
+  /// Every [SourceEditor::move_range] splits the original code into more
+  /// segments, each built as a [SliceSource] of the whole original. Unless
+  /// the original is a [ConcatSource](crate::ConcatSource), streaming the
+  /// built source streams the original once per segment, so `k` segments
+  /// cost `O(k * size)`.
+  pub fn build(&self) -> BoxSource {
+    let mut builder =
+      ConcatSourceBuilder::with_capacity(self.segments.len() + 2);
+    if !self.intro.is_empty() {
+      builder.add(RawStringSource::from(self.intro.clone()));
+    }
+    for segment in &self.segments {
+      if segment.start < segment.end {
+        builder.add(self.build_segment(segment));
+      }
+    }
+    if !self.outro.is_empty() {
+      builder.add(RawStringSource::from(self.outro.clone()));
+    }
+    builder.build().boxed()
+  }
+
+  fn build_segment(&self, segment: &Range<u32>) -> BoxSource {
+    let slice = if segment.start == 0 && segment.end == self.len {
+      self.inner.clone()
+    } else {
+      SliceSource::new(
+        self.inner.clone(),
+        segment.start as usize..segment.end as usize,
+      )
+      .boxed()
+    };
+    let mut replace = ReplaceSource::new(slice.clone());
+    for (&index, insertion) in
+      self.insertions.range(segment.start..=segment.end)
+    {
+      let offset = index - segment.start;
+      if index > segment.start && !insertion.left.is_empty() {
+        replace.insert_with_enforce(
+          offset,
+          &insertion.left,
+          None,
+          ReplacementEnforce::Pre,
+        );
+      }
+      if index < segment.end && !insertion.right.is_empty() {
+        replace.insert_with_enforce(
+          offset,
+          &insertion.right,
+          None,
+          ReplacementEnforce::Post,
+        );
+      }
+    }
+    for edit in &self.edits {
+      if edit.start >= segment.end || edit.end <= segment.start {
+        continue;
+      }
+      let start = edit.start.max(segment.start);
+      let end = edit.end.min(segment.end);
+      // Content of an edit spanning segments goes with its first segment.
+      if edit.start == start {
+        replace.replace(
+          start - segment.start,
+          end - segment.start,
+          &edit.content,
+          edit.name.as_deref(),
+        );
+      } else {
+        replace.replace(start - segment.start, end - segment.start, "", None);
+      }
+    }
+    if replace.replacements().is_empty() {
+      slice
+    } else {
+      replace.boxed()
+    }
+  }
+
+  fn insertion(&mut self, index: u32) -> &mut Insertion {
+    self.insertions.entry(index).or_default()
+  }
+
+  fn edit(&mut self, edit: Edit) -> Result<()> {
+    let Edit { start, end, .. } = edit;
+    if let Some(split) = self.edits.iter().find(|other| {
+      other.start < end
+        && other.end > start
+        && !(start <= other.start && other.end <= end)
+        && !other.content.is_empty()
+    }) {
+      return Err(Error::InvalidEdit(format!(
+        "cannot split {}..{} which was already overwritten",
+        split.start, split.end
+      )));
+    }
+    let mut edits = Vec::with_capacity(self.edits.len() + 2);
+    for other in std::mem::take(&mut self.edits) {
+      if other.end <= start || other.start >= end {
+        edits.push(other);
+        continue;
+      }
+      // Only the parts of a removal outside the new edit remain.
+      if other.start < start {
+        edits.push(Edit {
+          end: start,
+          ..other.clone()
+        });
+      }
+      if other.end > end {
+        edits.push(Edit {
+          start: end,
+          ..other
+        });
+      }
+    }
+    edits.push(edit);
+    edits.sort_by_key(|edit| edit.start);
+    self.edits = edits;
+    for (&index, insertion) in self.insertions.range_mut(start..=end) {
+      if index > start {
+        insertion.left.clear();
+      }
+      if index < end {
+        insertion.right.clear();
+      }
+    }
+    Ok(())
+  }
+
+  // Split the segment containing `offset` so a segment starts at `offset`.
+  fn split(&mut self, offset: u32) -> Result<()> {
+    if let Some(edit) = self.edits.iter().find(|edit| {
+      edit.start < offset && offset < edit.end && !edit.content.is_empty()
+    }) {
+      return Err(Error::InvalidEdit(format!(
+        "cannot split {}..{} which was already overwritten",
+        edit.start, edit.end
+      )));
+    }
+    if let Some(index) = self
+      .segments
+      .iter()
+      .position(|segment| segment.start < offset && offset < segment.end)
+    {
+      let end = self.segments[index].end;
+      self.segments[index].end = offset;
+      self.segments.insert(index + 1, offset..end);
+    }
+    Ok(())
+  }
+
+  fn check_offset(&self, offset: u32) -> Result<()> {
+    if offset > self.len {
+      return Err(Error::InvalidEdit(format!(
+        "offset {offset} is out of bounds"
+      )));
+    }
+    let index = self.wide_chars.partition_point(|c| c.end <= offset);
+    if let Some(c) = self.wide_chars.get(index) {
+      if c.start < offset {
+        return Err(Error::InvalidEdit(format!(
+          "offset {offset} is not a char boundary"
+        )));
+      }
+    }
+    Ok(())
+  }
+
+  fn check_range(&self, start: u32, end: u32) -> Result<()> {
+    self.check_offset(start)?;
+    self.check_offset(end)?;
+    if start > end {
+      return Err(Error::InvalidEdit(format!(
+        "range {start}..{end} ends before it starts"
+      )));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{MapOptions, ObjectPool, OriginalSource};
+
+  use super::*;
+
+  fn code(editor: &SourceEditor) -> String {
+    editor.build().source().into_string_lossy().into_owned()
+  }
+
+  #[test]
+  fn insertions_follow_their_side() {
+    let mut editor =
+      SourceEditor::new(OriginalSource::new("abcdefghijkl", "a.js"));
+    editor.append_left(3, "L").unwrap();
+    editor.prepend_left(3, "l").unwrap();
+    editor.append_right(3, "r").unwrap();
+    editor.prepend_right(3, "R").unwrap();
+    assert_eq!(code(&editor), "abclLRrdefghijkl");
+
+    editor.move_range(0, 3, 6).unwrap();
+    assert_eq!(code(&editor), "RrdefabclLghijkl");
+
+    editor.remove(3, 6).unwrap();
+    editor.append_left(12, "!").unwrap();
+    editor.prepend_left(0, "<").unwrap();
+    assert_eq!(code(&editor), "<abclLghijkl!");
+
+    assert!(matches!(
+      editor.move_range(0, 6, 3),
+      Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(editor.remove(4, 13), Err(Error::InvalidEdit(_))));
+
+    // Moving code to its own start or end does nothing.
+    editor.move_range(6, 9, 6).unwrap();
+    editor.move_range(6, 9, 9).unwrap();
+    assert_eq!(code(&editor), "<abclLghijkl!");
+  }
+
+  #[test]
+  fn rejects_offsets_inside_chars() {
+    let mut editor = SourceEditor::new(OriginalSource::new("é🙈abc", "a.js"));
+    assert!(matches!(
+      editor.overwrite(1, 4, "x", None),
+      Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+      editor.move_range(1, 3, 8),
+      Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+      editor.append_left(4, "x"),
+      Err(Error::InvalidEdit(_))
+    ));
+    editor.overwrite(2, 6, "x", None).unwrap();
+    editor.move_range(0, 2, 8).unwrap();
+    assert_eq!(code(&editor), "xabéc");
+  }
+
+  #[test]
+  fn overwrite_and_remove() {
+    let mut editor =
+      SourceEditor::new(OriginalSource::new("abcdefghijkl", "a.js"));
+    editor.append_right(2, "x").unwrap();
+    editor.overwrite(2, 5, "XYZ", None).unwrap();
+    editor.append_right(2, "y").unwrap();
+    assert_eq!(code(&editor), "abyXYZfghijkl");
+    assert!(matches!(
+      editor.overwrite(4, 6, "", None),
+      Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+      editor.overwrite(6, 6, "", None),
+      Err(Error::InvalidEdit(_))
+    ));
+
+    editor.remove(6, 10).unwrap();
+    editor.remove(8, 11).unwrap();
+    editor.overwrite(0, 5, "_", None).unwrap();
+    assert_eq!(code(&editor), "_fl");
+
+    editor.indent("  ");
+    assert_eq!(code(&editor), "_fl");
+  }
+
+  #[test]
+  fn indent() {
+    let mut editor =
+      SourceEditor::new(OriginalSource::new("a\n\nb\r\nc", "a.js"));
+    editor.prepend("{\n").append("\n}");
+    editor.remove(6, 7).unwrap();
+    editor.indent("  ");
+    assert_eq!(code(&editor), "{\n  a\n\n  b\r\n\n}");
+  }
+
+  #[test]
+  fn moved_code_keeps_mappings() {
+    let mut editor =
+      SourceEditor::new(OriginalSource::new("a = 1;\nb = 2;\n", "x.js"));
+    editor.move_range(7, 14, 0).unwrap();
+    editor.overwrite(11, 12, "3", None).unwrap();
+    let source = editor.build();
+    assert_eq!(source.source().into_string_lossy(), "b = 3;\na = 1;\n");
+    let map = source
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.sources(), ["x.js"]);
+    assert_eq!(map.mappings(), "AACA,IAAI,CAAC;AADL");
+  }
+}
@@ -0,0 +1,484 @@
+use std::io::Write;
+
+use crate::{Result, SourceMap};
+
+/// Options for [SourceMap::to_json_with_options],
+/// [SourceMap::write_json_with_options] and
+/// [Source::map_to_writer](crate::Source::map_to_writer).
+///
+/// The default options produce the same JSON as [SourceMap::to_json].
+///
+/// ```
+/// use rspack_sources::{SourceMap, SourceMapJsonOptions};
+///
+/// let map = SourceMap::new("AAAA", vec!["b.js".into()], vec![], vec![]);
+/// let options = SourceMapJsonOptions {
+///   pretty: true,
+///   canonical: true,
+///   emit_empty_names: false,
+///   ..Default::default()
+/// };
+/// assert_eq!(
+///   map.to_json_with_options(&options).unwrap(),
+///   "{\n  \"mappings\": \"AAAA\",\n  \"sources\": [\n    \"b.js\"\n  ],\n  \"version\": 3\n}",
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapJsonOptions {
+  /// Indent the JSON with two spaces.
+  pub pretty: bool,
+  /// Write the keys in lexicographic order instead of the default order.
+  pub canonical: bool,
+  /// Write `names` even when it is empty.
+  pub emit_empty_names: bool,
+  /// Write `sourcesContent` even when all contents are empty.
+  pub emit_empty_sources_content: bool,
+  /// Escape non-ASCII characters in strings as `\uXXXX`.
+  pub escape_non_ascii: bool,
+}
+
+impl Default for SourceMapJsonOptions {
+  fn default() -> Self {
+    Self {
+      pretty: false,
+      canonical: false,
+      emit_empty_names: true,
+      emit_empty_sources_content: false,
+      escape_non_ascii: false,
+    }
+  }
+}
+
+enum JsonValue<'a> {
+  Number(u32),
+  String(&'a str),
+  Strings(Vec<&'a str>),
+  Numbers(&'a [u32]),
+  Raw(&'a serde_json::Value),
+}
+
+impl SourceMap {
+  /// Generate source map to a json string with [SourceMapJsonOptions].
+  pub fn to_json_with_options(
+    &self,
+    options: &SourceMapJsonOptions,
+  ) -> Result<String> {
+    let mut json = Vec::new();
+    self.write_json_with_options(&mut json, options)?;
+    #[allow(unsafe_code)]
+    // SAFETY: the writer only writes valid UTF-8.
+    Ok(unsafe { String::from_utf8_unchecked(json) })
+  }
+
+  /// Generate source map to writer with [SourceMapJsonOptions].
+  pub fn write_json_with_options<W: Write>(
+    &self,
+    mut w: W,
+    options: &SourceMapJsonOptions,
+  ) -> Result<()> {
+    let w: &mut dyn Write = &mut w;
+    w.write_all(b"{")?;
+    for (index, (key, value)) in self.json_fields(options).iter().enumerate() {
+      write_field(w, index, key, value, options)?;
+    }
+    write_end(w, options)
+  }
+
+  // The fields of the JSON in the order `options` asks for.
+  fn json_fields(
+    &self,
+    options: &SourceMapJsonOptions,
+  ) -> Vec<(&str, JsonValue<'_>)> {
+    let mut fields: Vec<(&str, JsonValue)> = Vec::with_capacity(12);
+    fields.push(("version", JsonValue::Number(3)));
+    if let Some(file) = self.file() {
+      fields.push(("file", JsonValue::String(file)));
+    }
+    fields.push((
+      "sources",
+      JsonValue::Strings(self.sources().iter().map(|s| s.as_str()).collect()),
+    ));
+    if options.emit_empty_sources_content
+      || self.sources_content().iter().any(|s| !s.is_empty())
+    {
+      fields.push((
+        "sourcesContent",
+        JsonValue::Strings(
+          self.sources_content().iter().map(|s| s.as_ref()).collect(),
+        ),
+      ));
+    }
+    if options.emit_empty_names || !self.names().is_empty() {
+      fields.push((
+        "names",
+        JsonValue::Strings(self.names().iter().map(|s| s.as_str()).collect()),
+      ));
+    }
+    fields.push(("mappings", JsonValue::String(self.mappings())));
+    if let Some(source_root) = self.source_root() {
+      fields.push(("sourceRoot", JsonValue::String(source_root)));
+    }
+    if let Some(debug_id) = self.get_debug_id() {
+      fields.push(("debugId", JsonValue::String(debug_id)));
+    }
+    if let Some(ignore_list) = self.ignore_list() {
+      fields.push(("ignoreList", JsonValue::Numbers(ignore_list)));
+    }
+    if let Some(original_scopes) = self.original_scopes() {
+      fields.push((
+        "originalScopes",
+        JsonValue::Strings(
+          original_scopes.iter().map(|s| s.as_str()).collect(),
+        ),
+      ));
+    }
+    if let Some(generated_ranges) = self.generated_ranges() {
+      fields.push(("generatedRanges", JsonValue::String(generated_ranges)));
+    }
+    if let Some(function_mappings) = self.sources_function_mappings() {
+      fields.push((
+        "x_com_bloomberg_sourcesFunctionMappings",
+        JsonValue::Strings(
+          function_mappings.iter().map(|s| s.as_str()).collect(),
+        ),
+      ));
+    }
+    for (key, value) in self.extensions() {
+      fields.push((key, JsonValue::Raw(value)));
+    }
+    if options.canonical {
+      fields.sort_by_key(|(key, _)| *key);
+    }
+    fields
+  }
+}
+
+/// Opens the JSON of a map whose mappings are written in parts as they are
+/// encoded, the mappings come first and can't be in canonical order.
+pub(crate) fn write_mappings_start(
+  w: &mut dyn Write,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  debug_assert!(!options.canonical);
+  w.write_all(b"{")?;
+  write_key(w, 0, "mappings", options)?;
+  w.write_all(b"\"")?;
+  Ok(())
+}
+
+/// Closes the JSON opened by [write_mappings_start] with the fields of `map`
+/// besides its mappings.
+pub(crate) fn write_mappings_end(
+  w: &mut dyn Write,
+  map: &SourceMap,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  w.write_all(b"\"")?;
+  let fields = map.json_fields(options);
+  let fields = fields.iter().filter(|(key, _)| *key != "mappings");
+  for (index, (key, value)) in fields.enumerate() {
+    write_field(w, index + 1, key, value, options)?;
+  }
+  write_end(w, options)
+}
+
+fn layout(options: &SourceMapJsonOptions) -> (&'static str, &'static str) {
+  if options.pretty {
+    ("\n", "  ")
+  } else {
+    ("", "")
+  }
+}
+
+fn write_key(
+  w: &mut dyn Write,
+  index: usize,
+  key: &str,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  let (newline, indent) = layout(options);
+  if index > 0 {
+    w.write_all(b",")?;
+  }
+  write!(w, "{newline}{indent}")?;
+  write_string(w, key, options)?;
+  w.write_all(if options.pretty { b": " } else { b":" })?;
+  Ok(())
+}
+
+fn write_field(
+  w: &mut dyn Write,
+  index: usize,
+  key: &str,
+  value: &JsonValue,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  let (newline, indent) = layout(options);
+  write_key(w, index, key, options)?;
+  match value {
+    JsonValue::Number(n) => write!(w, "{n}")?,
+    JsonValue::String(s) => write_string(w, s, options)?,
+    JsonValue::Strings(items) => {
+      write_array(w, items, newline, indent, |w, s| {
+        write_string(w, s, options)
+      })?
+    }
+    JsonValue::Numbers(items) => {
+      write_array(w, items, newline, indent, |w, n| {
+        write!(w, "{n}")?;
+        Ok(())
+      })?
+    }
+    JsonValue::Raw(value) => {
+      write_value(w, value, 1, newline, indent, options)?
+    }
+  }
+  Ok(())
+}
+
+fn write_end(w: &mut dyn Write, options: &SourceMapJsonOptions) -> Result<()> {
+  let (newline, _) = layout(options);
+  write!(w, "{newline}}}")?;
+  Ok(())
+}
+
+fn write_array<T>(
+  w: &mut dyn Write,
+  items: &[T],
+  newline: &str,
+  indent: &str,
+  mut write_item: impl FnMut(&mut dyn Write, &T) -> Result<()>,
+) -> Result<()> {
+  w.write_all(b"[")?;
+  for (index, item) in items.iter().enumerate() {
+    if index > 0 {
+      w.write_all(b",")?;
+    }
+    write!(w, "{newline}{indent}{indent}")?;
+    write_item(w, item)?;
+  }
+  if !items.is_empty() {
+    write!(w, "{newline}{indent}")?;
+  }
+  w.write_all(b"]")?;
+  Ok(())
+}
+
+fn write_value(
+  w: &mut dyn Write,
+  value: &serde_json::Value,
+  depth: usize,
+  newline: &str,
+  indent: &str,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  let (open, close, items): (_, _, Vec<(Option<&str>, _)>) = match value {
+    serde_json::Value::String(s) => return write_string(w, s, options),
+    serde_json::Value::Array(items) => {
+      ("[", "]", items.iter().map(|item| (None, item)).collect())
+    }
+    serde_json::Value::Object(entries) => (
+      "{",
+      "}",
+      entries
+        .iter()
+        .map(|(key, item)| (Some(key.as_str()), item))
+        .collect(),
+    ),
+    _ => {
+      serde_json::to_writer(&mut *w, value).map_err(std::io::Error::from)?;
+      return Ok(());
+    }
+  };
+  w.write_all(open.as_bytes())?;
+  for (index, (key, item)) in items.iter().enumerate() {
+    if index > 0 {
+      w.write_all(b",")?;
+    }
+    write!(w, "{newline}{}", indent.repeat(depth + 1))?;
+    if let Some(key) = key {
+      write_string(w, key, options)?;
+      w.write_all(if options.pretty { b": " } else { b":" })?;
+    }
+    write_value(w, item, depth + 1, newline, indent, options)?;
+  }
+  if !items.is_empty() {
+    write!(w, "{newline}{}", indent.repeat(depth))?;
+  }
+  w.write_all(close.as_bytes())?;
+  Ok(())
+}
+
+fn write_string(
+  w: &mut dyn Write,
+  s: &str,
+  options: &SourceMapJsonOptions,
+) -> Result<()> {
+  if !options.escape_non_ascii || s.is_ascii() {
+    serde_json::to_writer(&mut *w, s).map_err(std::io::Error::from)?;
+    return Ok(());
+  }
+  w.write_all(b"\"")?;
+  let mut start = 0;
+  for (index, c) in s.char_indices() {
+    let escaped = match c {
+      '"' => Some("\\\""),
+      '\\' => Some("\\\\"),
+      '\n' => Some("\\n"),
+      '\r' => Some("\\r"),
+      '\t' => Some("\\t"),
+      '\u{8}' => Some("\\b"),
+      '\u{c}' => Some("\\f"),
+      c if c.is_ascii_control() || !c.is_ascii() => None,
+      _ => continue,
+    };
+    w.write_all(&s.as_bytes()[start..index])?;
+    start = index + c.len_utf8();
+    match escaped {
+      Some(escaped) => w.write_all(escaped.as_bytes())?,
+      None => {
+        for unit in c.encode_utf16(&mut [0; 2]) {
+          write!(w, "\\u{unit:04x}")?;
+        }
+      }
+    }
+  }
+  w.write_all(&s.as_bytes()[start..])?;
+  w.write_all(b"\"")?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn source_map() -> SourceMap {
+    SourceMap::from_json(
+      r#"{
+        "version": 3,
+        "file": "a.js",
+        "sources": ["a.js", "b.js"],
+        "sourcesContent": ["const 魑 = \"😀\";\n\u0001", ""],
+        "names": [],
+        "mappings": "AAAA,GAAG",
+        "sourceRoot": "/",
+        "ignoreList": [1]
+      }"#,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn default_options_match_to_json() {
+    let map = source_map();
+    let json = map
+      .to_json_with_options(&SourceMapJsonOptions::default())
+      .unwrap();
+    assert_eq!(json, map.to_json().unwrap());
+
+    let mut map = map;
+    map.set_original_scopes(Some(vec!["AACAA".to_string(), String::new()]));
+    map.set_generated_ranges(Some("AAAA"));
+    map.set_sources_function_mappings(Some(vec![
+      "AAAEC".to_string(),
+      String::new(),
+    ]));
+    map.set_extension(
+      "x_facebook_sources",
+      Some(serde_json::json!([null, [{ "names": ["<global>"] }]])),
+    );
+    let json = map
+      .to_json_with_options(&SourceMapJsonOptions::default())
+      .unwrap();
+    assert_eq!(json, map.to_json().unwrap());
+
+    let empty = SourceMap::new("", vec![], vec![], vec![]);
+    assert_eq!(
+      empty
+        .to_json_with_options(&SourceMapJsonOptions::default())
+        .unwrap(),
+      empty.to_json().unwrap()
+    );
+  }
+
+  #[test]
+  fn writes_canonical_pretty_json() {
+    let options = SourceMapJsonOptions {
+      pretty: true,
+      canonical: true,
+      emit_empty_names: false,
+      escape_non_ascii: true,
+      ..Default::default()
+    };
+    let json = source_map().to_json_with_options(&options).unwrap();
+    assert_eq!(
+      json,
+      r#"{
+  "file": "a.js",
+  "ignoreList": [
+    1
+  ],
+  "mappings": "AAAA,GAAG",
+  "sourceRoot": "/",
+  "sources": [
+    "a.js",
+    "b.js"
+  ],
+  "sourcesContent": [
+    "const \u9b51 = \"\ud83d\ude00\";\n\u0001",
+    ""
+  ],
+  "version": 3
+}"#
+    );
+    assert_eq!(SourceMap::from_json(&json).unwrap(), source_map());
+  }
+
+  #[test]
+  fn writes_pretty_extension_fields() {
+    let mut map = SourceMap::new("", vec![], vec![], vec![]);
+    map.set_extension(
+      "x_facebook_sources",
+      Some(serde_json::json!([null, [{ "names": ["<global>"] }], []])),
+    );
+    let options = SourceMapJsonOptions {
+      pretty: true,
+      ..Default::default()
+    };
+    let json = map.to_json_with_options(&options).unwrap();
+    assert_eq!(
+      json,
+      r#"{
+  "version": 3,
+  "sources": [],
+  "names": [],
+  "mappings": "",
+  "x_facebook_sources": [
+    null,
+    [
+      {
+        "names": [
+          "<global>"
+        ]
+      }
+    ],
+    []
+  ]
+}"#
+    );
+    assert_eq!(SourceMap::from_json(&json).unwrap(), map);
+  }
+
+  #[test]
+  fn emits_empty_fields_on_request() {
+    let map = SourceMap::new("AAAA", vec!["a.js".into()], vec![], vec![]);
+    let options = SourceMapJsonOptions {
+      emit_empty_sources_content: true,
+      ..Default::default()
+    };
+    assert_eq!(
+      map.to_json_with_options(&options).unwrap(),
+      r#"{"version":3,"sources":["a.js"],"sourcesContent":[],"names":[],"mappings":"AAAA"}"#
+    );
+  }
+}
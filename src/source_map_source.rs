@@ -1,18 +1,161 @@
 use std::{
   borrow::Cow,
   hash::{Hash, Hasher},
-  sync::Arc,
+  sync::{Arc, OnceLock},
 };
 
 use crate::{
   helpers::{
-    get_map, stream_chunks_of_combined_source_map, stream_chunks_of_source_map,
-    Chunks, StreamChunks,
+    get_map, report_generated_ranges, stream_chunks_of_combined_source_map,
+    stream_chunks_of_raw_source, stream_chunks_of_source_map, write_map_json,
+    Chunks, OnSourceWithExtras, StreamChunks,
   },
   object_pool::ObjectPool,
-  MapOptions, Source, SourceMap, SourceValue,
+  Error, MapOptions, Source, SourceKind, SourceMap, SourceMapJsonOptions,
+  SourceValue,
 };
 
+/// Loader of a [LazySourceMap], see [LazySourceMap::from_loader].
+pub type SourceMapLoader =
+  Arc<dyn Fn() -> crate::Result<SourceMap> + Send + Sync>;
+
+/// A [SourceMap] which is parsed or loaded when it is first needed.
+///
+/// ```
+/// use rspack_sources::{LazySourceMap, SourceMap};
+///
+/// let map = LazySourceMap::from_slice(
+///   br#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#.to_vec(),
+/// );
+/// assert_eq!(map.get().unwrap().sources(), ["a.js"]);
+///
+/// let broken = LazySourceMap::from_slice(b"{".to_vec());
+/// assert!(broken.get().is_err());
+/// ```
+#[derive(Clone)]
+pub struct LazySourceMap(LazySourceMapRepr);
+
+#[derive(Clone)]
+enum LazySourceMapRepr {
+  Parsed(SourceMap),
+  Lazy(Arc<LazyState>),
+}
+
+struct LazyState {
+  raw: LazyRaw,
+  parsed: OnceLock<crate::Result<SourceMap>>,
+}
+
+enum LazyRaw {
+  Slice(Vec<u8>),
+  Loader(SourceMapLoader),
+}
+
+impl LazySourceMap {
+  /// Create a [LazySourceMap] parsing the JSON with [SourceMap::from_slice]
+  /// when it is first needed.
+  pub fn from_slice(json: impl Into<Vec<u8>>) -> Self {
+    Self::lazy(LazyRaw::Slice(json.into()))
+  }
+
+  /// Create a [LazySourceMap] calling `loader` when it is first needed.
+  pub fn from_loader(
+    loader: impl Fn() -> crate::Result<SourceMap> + Send + Sync + 'static,
+  ) -> Self {
+    Self::lazy(LazyRaw::Loader(Arc::new(loader)))
+  }
+
+  fn lazy(raw: LazyRaw) -> Self {
+    Self(LazySourceMapRepr::Lazy(Arc::new(LazyState {
+      raw,
+      parsed: OnceLock::new(),
+    })))
+  }
+
+  /// Get the [SourceMap], parsing or loading it if needed. A failure is
+  /// cached and returned by every later call.
+  pub fn get(&self) -> std::result::Result<&SourceMap, &Error> {
+    match &self.0 {
+      LazySourceMapRepr::Parsed(source_map) => Ok(source_map),
+      LazySourceMapRepr::Lazy(state) => state
+        .parsed
+        .get_or_init(|| match &state.raw {
+          LazyRaw::Slice(json) => SourceMap::from_slice(json),
+          LazyRaw::Loader(loader) => loader(),
+        })
+        .as_ref(),
+    }
+  }
+
+  /// Whether the [SourceMap] was parsed or loaded already.
+  pub fn is_loaded(&self) -> bool {
+    match &self.0 {
+      LazySourceMapRepr::Parsed(_) => true,
+      LazySourceMapRepr::Lazy(state) => state.parsed.get().is_some(),
+    }
+  }
+}
+
+impl From<SourceMap> for LazySourceMap {
+  fn from(source_map: SourceMap) -> Self {
+    Self(LazySourceMapRepr::Parsed(source_map))
+  }
+}
+
+impl Hash for LazySourceMap {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    match &self.0 {
+      LazySourceMapRepr::Parsed(source_map) => source_map.hash(state),
+      LazySourceMapRepr::Lazy(lazy) => match &lazy.raw {
+        LazyRaw::Slice(json) => {
+          "LazySourceMap::from_slice".hash(state);
+          json.hash(state);
+        }
+        LazyRaw::Loader(_) => "LazySourceMap::from_loader".hash(state),
+      },
+    }
+  }
+}
+
+/// Parsed maps are compared by value, lazy maps by their JSON or loader
+/// without parsing them.
+impl PartialEq for LazySourceMap {
+  fn eq(&self, other: &Self) -> bool {
+    match (&self.0, &other.0) {
+      (LazySourceMapRepr::Parsed(a), LazySourceMapRepr::Parsed(b)) => a == b,
+      (LazySourceMapRepr::Lazy(a), LazySourceMapRepr::Lazy(b)) => {
+        Arc::ptr_eq(a, b)
+          || match (&a.raw, &b.raw) {
+            (LazyRaw::Slice(a), LazyRaw::Slice(b)) => a == b,
+            (LazyRaw::Loader(a), LazyRaw::Loader(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+          }
+      }
+      _ => false,
+    }
+  }
+}
+
+impl Eq for LazySourceMap {}
+
+impl std::fmt::Debug for LazySourceMap {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.0 {
+      LazySourceMapRepr::Parsed(source_map) => {
+        write!(f, "LazySourceMap::from({source_map:?})")
+      }
+      LazySourceMapRepr::Lazy(state) => match &state.raw {
+        LazyRaw::Slice(json) => write!(
+          f,
+          "LazySourceMap::from_slice({:?})",
+          String::from_utf8_lossy(json)
+        ),
+        LazyRaw::Loader(_) => write!(f, "LazySourceMap::from_loader(..)"),
+      },
+    }
+  }
+}
+
 /// Options for [SourceMapSource::new].
 #[derive(Debug, Clone)]
 pub struct SourceMapSourceOptions<V, N> {
@@ -42,6 +185,39 @@ pub struct WithoutOriginalOptions<V, N> {
   pub source_map: SourceMap,
 }
 
+/// Options for [SourceMapSource::new_lazy], like [SourceMapSourceOptions]
+/// but the source map is a [LazySourceMap].
+#[derive(Debug, Clone)]
+pub struct LazySourceMapSourceOptions<V, N> {
+  /// The source code.
+  pub value: V,
+  /// Name of the file.
+  pub name: N,
+  /// The source map of the source code, parsed on first use.
+  pub source_map: LazySourceMap,
+  /// The original source code.
+  pub original_source: Option<Arc<str>>,
+  /// The original source map.
+  pub inner_source_map: Option<SourceMap>,
+  /// Whether remove the original source.
+  pub remove_original_source: bool,
+}
+
+impl<V, N> From<SourceMapSourceOptions<V, N>>
+  for LazySourceMapSourceOptions<V, N>
+{
+  fn from(options: SourceMapSourceOptions<V, N>) -> Self {
+    Self {
+      value: options.value,
+      name: options.name,
+      source_map: options.source_map.into(),
+      original_source: options.original_source,
+      inner_source_map: options.inner_source_map,
+      remove_original_source: options.remove_original_source,
+    }
+  }
+}
+
 impl<V, N> From<WithoutOriginalOptions<V, N>> for SourceMapSourceOptions<V, N> {
   fn from(options: WithoutOriginalOptions<V, N>) -> Self {
     Self {
@@ -63,7 +239,7 @@ impl<V, N> From<WithoutOriginalOptions<V, N>> for SourceMapSourceOptions<V, N> {
 pub struct SourceMapSource {
   value: Arc<str>,
   name: Box<str>,
-  source_map: SourceMap,
+  source_map: LazySourceMap,
   original_source: Option<Arc<str>>,
   inner_source_map: Option<SourceMap>,
   remove_original_source: bool,
@@ -77,7 +253,39 @@ impl SourceMapSource {
     N: Into<String>,
     O: Into<SourceMapSourceOptions<V, N>>,
   {
-    let options = options.into();
+    Self::new_lazy(LazySourceMapSourceOptions::from(options.into()))
+  }
+
+  /// Create a [SourceMapSource] whose source map is only parsed when it is
+  /// first needed.
+  ///
+  /// When the source map fails to parse, [Source::map] returns `None` and
+  /// the source is streamed as if it had no source map, use
+  /// [SourceMapSource::try_map] to get the error.
+  ///
+  /// ```
+  /// use rspack_sources::{
+  ///   LazySourceMap, LazySourceMapSourceOptions, MapOptions, ObjectPool,
+  ///   Source, SourceMapSource,
+  /// };
+  ///
+  /// let source = SourceMapSource::new_lazy(LazySourceMapSourceOptions {
+  ///   value: "a;",
+  ///   name: "a.js",
+  ///   source_map: LazySourceMap::from_slice(b"not json".to_vec()),
+  ///   original_source: None,
+  ///   inner_source_map: None,
+  ///   remove_original_source: false,
+  /// });
+  /// let object_pool = ObjectPool::default();
+  /// assert!(source.map(&object_pool, &MapOptions::default()).is_none());
+  /// assert!(source.try_map(&object_pool, &MapOptions::default()).is_err());
+  /// ```
+  pub fn new_lazy<V, N>(options: LazySourceMapSourceOptions<V, N>) -> Self
+  where
+    V: Into<String>,
+    N: Into<String>,
+  {
     Self {
       value: Arc::from(options.value.into()),
       name: Box::from(options.name.into()),
@@ -87,6 +295,35 @@ impl SourceMapSource {
       remove_original_source: options.remove_original_source,
     }
   }
+
+  /// The source code.
+  pub fn value(&self) -> &Arc<str> {
+    &self.value
+  }
+
+  /// Name of the file.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Like [Source::map], but returns the error when the source map fails
+  /// to parse or load.
+  pub fn try_map(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+  ) -> std::result::Result<Option<SourceMap>, &Error> {
+    let source_map = self.source_map.get()?;
+    if self.inner_source_map.is_none() {
+      // Cloning only shares the fields, the mappings are re-encoded only when
+      // names or sources are left out.
+      let mut source_map = source_map.clone();
+      options.apply_to_map(&mut source_map);
+      return Ok(Some(source_map));
+    }
+    let chunks = self.stream_chunks();
+    Ok(get_map(object_pool, chunks.as_ref(), options))
+  }
 }
 
 impl Source for SourceMapSource {
@@ -111,16 +348,44 @@ impl Source for SourceMapSource {
     object_pool: &ObjectPool,
     options: &MapOptions,
   ) -> Option<SourceMap> {
-    if self.inner_source_map.is_none() {
-      return Some(self.source_map.clone());
+    self.try_map(object_pool, options).ok().flatten()
+  }
+
+  fn map_to_writer(
+    &self,
+    object_pool: &ObjectPool,
+    options: &MapOptions,
+    json_options: &SourceMapJsonOptions,
+    writer: &mut dyn std::io::Write,
+  ) -> crate::Result<bool> {
+    match self.source_map.get() {
+      Ok(source_map) if self.inner_source_map.is_none() => {
+        if options.changes_map() {
+          let mut source_map = source_map.clone();
+          options.apply_to_map(&mut source_map);
+          source_map.write_json_with_options(writer, json_options)?;
+        } else {
+          source_map.write_json_with_options(writer, json_options)?;
+        }
+        Ok(true)
+      }
+      _ => write_map_json(
+        object_pool,
+        self.stream_chunks().as_ref(),
+        options,
+        json_options,
+        writer,
+      ),
     }
-    let chunks = self.stream_chunks();
-    get_map(object_pool, chunks.as_ref(), options)
   }
 
   fn to_writer(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
     writer.write_all(self.value.as_bytes())
   }
+
+  fn kind(&self) -> SourceKind {
+    SourceKind::SourceMap
+  }
 }
 
 impl Hash for SourceMapSource {
@@ -153,13 +418,26 @@ impl std::fmt::Debug for SourceMapSource {
     let indent = f.width().unwrap_or(0);
     let indent_str = format!("{:indent$}", "", indent = indent);
 
-    writeln!(
-      f,
-      "{indent_str}SourceMapSource::new(SourceMapSourceOptions {{"
-    )?;
-    writeln!(f, "{indent_str}  value: {:?},", self.value)?;
-    writeln!(f, "{indent_str}  name: {:?},", self.name)?;
-    writeln!(f, "{indent_str}  source_map: {:?},", self.source_map)?;
+    match &self.source_map.0 {
+      LazySourceMapRepr::Parsed(source_map) => {
+        writeln!(
+          f,
+          "{indent_str}SourceMapSource::new(SourceMapSourceOptions {{"
+        )?;
+        writeln!(f, "{indent_str}  value: {:?},", self.value)?;
+        writeln!(f, "{indent_str}  name: {:?},", self.name)?;
+        writeln!(f, "{indent_str}  source_map: {:?},", source_map)?;
+      }
+      LazySourceMapRepr::Lazy(_) => {
+        writeln!(
+          f,
+          "{indent_str}SourceMapSource::new_lazy(LazySourceMapSourceOptions {{"
+        )?;
+        writeln!(f, "{indent_str}  value: {:?},", self.value)?;
+        writeln!(f, "{indent_str}  name: {:?},", self.name)?;
+        writeln!(f, "{indent_str}  source_map: {:?},", self.source_map)?;
+      }
+    }
     match &self.original_source {
       Some(original_source) => {
         writeln!(
@@ -199,30 +477,67 @@ impl Chunks for SourceMapSourceChunks<'_> {
     on_source: crate::helpers::OnSource<'_, 'a>,
     on_name: crate::helpers::OnName<'_, 'a>,
   ) -> crate::helpers::GeneratedInfo {
+    self.stream_with_extras(
+      object_pool,
+      options,
+      on_chunk,
+      on_source,
+      on_name,
+      &mut |_, _| {},
+      &mut |_, _| {},
+    )
+  }
+
+  fn stream_with_extras<'a>(
+    &'a self,
+    object_pool: &'a ObjectPool,
+    options: &MapOptions,
+    on_chunk: crate::helpers::OnChunk<'_, 'a>,
+    on_source: crate::helpers::OnSource<'_, 'a>,
+    on_name: crate::helpers::OnName<'_, 'a>,
+    on_source_extras: crate::helpers::OnSourceExtras<'_, 'a>,
+    on_generated_ranges: crate::helpers::OnGeneratedRanges<'_, 'a>,
+  ) -> crate::helpers::GeneratedInfo {
+    let Ok(source_map) = self.0.source_map.get() else {
+      return stream_chunks_of_raw_source(
+        &self.0.value,
+        options,
+        on_chunk,
+        on_source,
+        on_name,
+      );
+    };
+    let on_source_with_extras: OnSourceWithExtras<'_, 'a> =
+      &mut |source_index, source, source_content, extras| {
+        on_source_extras(source_index, extras);
+        on_source(source_index, source, source_content);
+      };
     if let Some(inner_source_map) = &self.0.inner_source_map {
       stream_chunks_of_combined_source_map(
         options,
         object_pool,
         &self.0.value,
-        &self.0.source_map,
+        source_map,
         &self.0.name,
         self.0.original_source.as_ref(),
         inner_source_map,
         self.0.remove_original_source,
         on_chunk,
-        on_source,
+        on_source_with_extras,
         on_name,
       )
     } else {
-      stream_chunks_of_source_map(
+      let generated_info = stream_chunks_of_source_map(
         options,
         object_pool,
         self.0.value.as_ref(),
-        &self.0.source_map,
+        source_map,
         on_chunk,
-        on_source,
+        on_source_with_extras,
         on_name,
-      )
+      );
+      report_generated_ranges(source_map, on_generated_ranges);
+      generated_info
     }
   }
 }
@@ -853,4 +1168,108 @@ mod tests {
 }).boxed()"#
     );
   }
+
+  #[test]
+  fn parses_lazy_source_map_on_first_use() {
+    let json = r#"{"version":3,"sources":["a.js"],"sourcesContent":["a;\nb;"],"names":[],"mappings":"AAAA;AACA"}"#;
+    let lazy = LazySourceMap::from_slice(json);
+    let source = SourceMapSource::new_lazy(LazySourceMapSourceOptions {
+      value: "a;\nb;",
+      name: "a.js",
+      source_map: lazy.clone(),
+      original_source: None,
+      inner_source_map: None,
+      remove_original_source: false,
+    });
+    assert_eq!(source.source().into_string_lossy(), "a;\nb;");
+    assert!(!lazy.is_loaded());
+
+    let eager = SourceMapSource::new(WithoutOriginalOptions {
+      value: "a;\nb;",
+      name: "a.js",
+      source_map: SourceMap::from_json(json).unwrap(),
+    });
+    let object_pool = ObjectPool::default();
+    assert_eq!(
+      source.map(&object_pool, &MapOptions::default()),
+      eager.map(&object_pool, &MapOptions::default())
+    );
+    assert!(lazy.is_loaded());
+
+    let concat = ConcatSource::new([source.clone().boxed(), eager.boxed()]);
+    assert_eq!(
+      concat
+        .map(&object_pool, &MapOptions::default())
+        .unwrap()
+        .mappings(),
+      "AAAA;AACA,EADA;AACA"
+    );
+  }
+
+  #[test]
+  fn loads_source_map_with_loader() {
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let loader_calls = calls.clone();
+    let lazy = LazySourceMap::from_loader(move || {
+      loader_calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+      Ok(
+        OriginalSource::new("a;", "a.js")
+          .map(&ObjectPool::default(), &MapOptions::default())
+          .unwrap(),
+      )
+    });
+    let source = SourceMapSource::new_lazy(LazySourceMapSourceOptions {
+      value: "a;",
+      name: "a.js",
+      source_map: lazy,
+      original_source: None,
+      inner_source_map: None,
+      remove_original_source: false,
+    });
+    let object_pool = ObjectPool::default();
+    for _ in 0..2 {
+      let map = source.map(&object_pool, &MapOptions::default()).unwrap();
+      assert_eq!(map.mappings(), "AAAA");
+    }
+    assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+  }
+
+  #[test]
+  fn broken_lazy_source_map_streams_as_raw_source() {
+    let source = SourceMapSource::new_lazy(LazySourceMapSourceOptions {
+      value: "a;\nb;",
+      name: "a.js",
+      source_map: LazySourceMap::from_slice("{\"version\":3,"),
+      original_source: None,
+      inner_source_map: None,
+      remove_original_source: false,
+    });
+    let object_pool = ObjectPool::default();
+    assert!(matches!(
+      source.try_map(&object_pool, &MapOptions::default()),
+      Err(Error::BadJson(_))
+    ));
+    let concat = ConcatSource::new([
+      source.clone().boxed(),
+      OriginalSource::new("c;", "c.js").boxed(),
+    ]);
+    let map = concat.map(&object_pool, &MapOptions::default()).unwrap();
+    assert_eq!(map.sources(), ["c.js"]);
+    assert_eq!(map.mappings(), ";EAAA");
+  }
+
+  #[test]
+  fn lazy_source_maps_compare_without_parsing() {
+    let a = LazySourceMap::from_slice("{");
+    let b = LazySourceMap::from_slice("{");
+    assert_eq!(a, b);
+    assert!(!a.is_loaded());
+    let parsed = LazySourceMap::from(
+      SourceMap::from_json(
+        r#"{"version":3,"sources":[],"names":[],"mappings":""}"#,
+      )
+      .unwrap(),
+    );
+    assert_ne!(a, parsed);
+  }
 }
@@ -0,0 +1,292 @@
+use std::fmt;
+
+use crate::{BoxSource, Source};
+
+/// The kind of a [Source] node, see [Source::kind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SourceKind {
+  /// [RawStringSource](crate::RawStringSource).
+  RawString,
+  /// [RawBufferSource](crate::RawBufferSource).
+  RawBuffer,
+  /// [OriginalSource](crate::OriginalSource).
+  Original,
+  /// [SourceMapSource](crate::SourceMapSource).
+  SourceMap,
+  /// [ConcatSource](crate::ConcatSource).
+  Concat,
+  /// [ReplaceSource](crate::ReplaceSource).
+  Replace,
+  /// [CachedSource](crate::CachedSource).
+  Cached,
+  /// [SliceSource](crate::SliceSource).
+  Slice,
+  /// [FileSource](crate::FileSource).
+  File,
+  /// `MmapSource`, available with the `mmap` feature.
+  Mmap,
+  /// A [Source] implemented outside of this crate.
+  Other,
+}
+
+impl SourceKind {
+  /// Whether sources of this kind carry mappings of their own, instead of
+  /// only passing through the mappings of their children.
+  pub fn carries_map(&self) -> bool {
+    matches!(self, SourceKind::Original | SourceKind::SourceMap)
+  }
+}
+
+/// Call `f` with every node of the tree rooted at `source` and its depth,
+/// parents before their children.
+pub fn walk_source_tree(
+  source: &dyn Source,
+  f: &mut dyn FnMut(&dyn Source, usize),
+) {
+  fn walk(
+    source: &dyn Source,
+    depth: usize,
+    f: &mut dyn FnMut(&dyn Source, usize),
+  ) {
+    f(source, depth);
+    source.visit_children(&mut |child| walk(child.as_ref(), depth + 1, f));
+  }
+  walk(source, 0, f)
+}
+
+/// Rebuild the tree rooted at `source` bottom-up, calling `f` with every
+/// node after its children were rebuilt. A node that can't be rebuilt from
+/// its children, see [Source::with_children], is passed to `f` as it was.
+///
+/// ```
+/// use rspack_sources::{
+///   transform_source_tree, BoxSource, ConcatSource, OriginalSource,
+///   RawStringSource, Source, SourceExt, SourceKind,
+/// };
+///
+/// let source = ConcatSource::new([
+///   RawStringSource::from_static("a();\n").boxed(),
+///   OriginalSource::new("b();\n", "b.js").boxed(),
+/// ])
+/// .boxed();
+/// // Drop the mappings of every OriginalSource.
+/// let source = transform_source_tree(&source, &mut |node: BoxSource| {
+///   if node.kind() == SourceKind::Original {
+///     RawStringSource::from(node.source().into_string_lossy().into_owned())
+///       .boxed()
+///   } else {
+///     node
+///   }
+/// });
+/// assert_eq!(source.source().into_string_lossy(), "a();\nb();\n");
+/// assert!(source.map(&Default::default(), &Default::default()).is_none());
+/// ```
+pub fn transform_source_tree(
+  source: &BoxSource,
+  f: &mut dyn FnMut(BoxSource) -> BoxSource,
+) -> BoxSource {
+  let mut children = Vec::new();
+  let mut changed = false;
+  source.visit_children(&mut |child| {
+    let new_child = transform_source_tree(child, f);
+    changed |= !BoxSource::ptr_eq(child, &new_child);
+    children.push(new_child);
+  });
+  let node = if changed {
+    source
+      .with_children(children)
+      .unwrap_or_else(|| source.clone())
+  } else {
+    source.clone()
+  };
+  f(node)
+}
+
+/// Display the tree rooted at a [Source] with the kind and size of every
+/// node, and `map` for nodes that carry mappings, see [source_tree].
+pub struct SourceTree<'a>(&'a dyn Source);
+
+/// Display the tree rooted at `source`, for diagnosing how a source is
+/// composed.
+///
+/// ```
+/// use rspack_sources::{
+///   source_tree, CachedSource, ConcatSource, OriginalSource, RawStringSource,
+///   SourceExt,
+/// };
+///
+/// let source = CachedSource::new(ConcatSource::new([
+///   RawStringSource::from_static("a();\n").boxed(),
+///   OriginalSource::new("b();\n", "b.js").boxed(),
+/// ]));
+/// assert_eq!(
+///   source_tree(&source).to_string(),
+///   "Cached (10 bytes)\n  Concat (10 bytes)\n    RawString (5 bytes)\n    Original (5 bytes, map)\n",
+/// );
+/// ```
+pub fn source_tree(source: &dyn Source) -> SourceTree<'_> {
+  SourceTree(source)
+}
+
+impl fmt::Display for SourceTree<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut result = Ok(());
+    walk_source_tree(self.0, &mut |node, depth| {
+      if result.is_err() {
+        return;
+      }
+      let kind = node.kind();
+      let map = if kind.carries_map() { ", map" } else { "" };
+      result = writeln!(
+        f,
+        "{:indent$}{kind:?} ({} bytes{map})",
+        "",
+        node.size(),
+        indent = depth * 2
+      );
+    });
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::borrow::Cow;
+
+  use crate::{
+    stream_chunks::{Chunks, StreamChunks},
+    ConcatSource, MapOptions, ObjectPool, OriginalSource, RawStringSource,
+    ReplaceSource, SourceExt, SourceMap, SourceValue,
+  };
+
+  use super::*;
+
+  #[test]
+  fn rebuilds_changed_nodes_only() {
+    let mut replace = ReplaceSource::new(OriginalSource::new("a();", "a.js"));
+    replace.replace(0, 1, "x", None);
+    let untouched = OriginalSource::new("b();", "b.js").boxed();
+    let source =
+      ConcatSource::new([replace.boxed(), untouched.clone()]).boxed();
+
+    let mut kinds = vec![];
+    walk_source_tree(source.as_ref(), &mut |node, depth| {
+      kinds.push((node.kind(), depth));
+    });
+    assert_eq!(
+      kinds,
+      [
+        (SourceKind::Concat, 0),
+        (SourceKind::Replace, 1),
+        (SourceKind::Original, 2),
+        (SourceKind::Original, 1),
+      ]
+    );
+
+    let transformed = transform_source_tree(&source, &mut |node| {
+      if node.source().into_string_lossy() == "a();" {
+        OriginalSource::new("a();", "renamed.js").boxed()
+      } else {
+        node
+      }
+    });
+    assert_eq!(transformed.source().into_string_lossy(), "x();b();");
+    let map = transformed
+      .map(&ObjectPool::default(), &MapOptions::default())
+      .unwrap();
+    assert_eq!(map.sources(), ["renamed.js", "b.js"]);
+    let mut children = vec![];
+    transformed.visit_children(&mut |child| children.push(child.clone()));
+    assert!(BoxSource::ptr_eq(&children[1], &untouched));
+
+    let raw = ConcatSource::new([
+      RawStringSource::from_static("a").boxed(),
+      RawStringSource::from_static("b").boxed(),
+    ]);
+    let mut visited = vec![];
+    raw.visit_children(&mut |child| visited.push(child.clone()));
+    assert_eq!(visited.len(), 1);
+    assert_eq!(visited[0].source().into_string_lossy(), "ab");
+
+    let unchanged = transform_source_tree(&source, &mut |node| node);
+    assert!(BoxSource::ptr_eq(&unchanged, &source));
+    assert!(RawStringSource::from_static("a")
+      .with_children(vec![])
+      .is_none());
+  }
+
+  // Visits its inner source but can't be rebuilt with other children.
+  #[derive(Debug, Clone)]
+  struct Opaque(BoxSource);
+
+  impl std::hash::Hash for Opaque {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+      self.0.hash(state)
+    }
+  }
+
+  impl PartialEq for Opaque {
+    fn eq(&self, other: &Self) -> bool {
+      self.0.as_ref() == other.0.as_ref()
+    }
+  }
+
+  impl Eq for Opaque {}
+
+  impl StreamChunks for Opaque {
+    fn stream_chunks<'a>(&'a self) -> Box<dyn Chunks + 'a> {
+      self.0.stream_chunks()
+    }
+  }
+
+  impl Source for Opaque {
+    fn source(&self) -> SourceValue<'_> {
+      self.0.source()
+    }
+
+    fn rope<'a>(&'a self, on_chunk: &mut dyn FnMut(&'a str)) {
+      self.0.rope(on_chunk)
+    }
+
+    fn buffer(&self) -> Cow<'_, [u8]> {
+      self.0.buffer()
+    }
+
+    fn size(&self) -> usize {
+      self.0.size()
+    }
+
+    fn map(
+      &self,
+      object_pool: &ObjectPool,
+      options: &MapOptions,
+    ) -> Option<SourceMap> {
+      self.0.map(object_pool, options)
+    }
+
+    fn to_writer(
+      &self,
+      writer: &mut dyn std::io::Write,
+    ) -> std::io::Result<()> {
+      self.0.to_writer(writer)
+    }
+
+    fn visit_children(&self, on_child: &mut dyn FnMut(&BoxSource)) {
+      on_child(&self.0)
+    }
+  }
+
+  #[test]
+  fn keeps_nodes_that_cannot_be_rebuilt() {
+    let source = Opaque(OriginalSource::new("a();", "a.js").boxed()).boxed();
+    let transformed = transform_source_tree(&source, &mut |node| {
+      if node.kind() == SourceKind::Original {
+        RawStringSource::from_static("b();").boxed()
+      } else {
+        node
+      }
+    });
+    assert!(BoxSource::ptr_eq(&transformed, &source));
+  }
+}
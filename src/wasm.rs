@@ -0,0 +1,273 @@
+use std::ops::Range;
+
+use crate::{Error, RawBufferSource, Result};
+
+/// Name of the custom section holding the URL of the source map of a
+/// WebAssembly module.
+pub const WASM_SOURCE_MAPPING_URL: &str = "sourceMappingURL";
+
+/// Name of the custom section holding the URL of the external DWARF debug
+/// info of a WebAssembly module.
+pub const WASM_EXTERNAL_DEBUG_INFO: &str = "external_debug_info";
+
+const WASM_HEADER: &[u8; 8] = b"\0asm\x01\0\0\0";
+
+/// A custom section of a WebAssembly module, see [wasm_custom_sections].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmCustomSection<'a> {
+  /// Name of the section.
+  pub name: &'a str,
+  /// Content of the section after its name.
+  pub payload: &'a [u8],
+  /// Bytes of the whole section in the module, including its id and size.
+  pub range: Range<usize>,
+}
+
+/// List the custom sections of the WebAssembly `module`, validating its
+/// header and the sizes of all sections.
+pub fn wasm_custom_sections(
+  module: &[u8],
+) -> Result<Vec<WasmCustomSection<'_>>> {
+  if !module.starts_with(WASM_HEADER) {
+    return Err(bad_wasm("missing WebAssembly header"));
+  }
+  let mut sections = Vec::new();
+  let mut pos = WASM_HEADER.len();
+  while pos < module.len() {
+    let start = pos;
+    let id = module[pos];
+    pos += 1;
+    let size = read_u32(module, &mut pos)? as usize;
+    let end = pos
+      .checked_add(size)
+      .filter(|end| *end <= module.len())
+      .ok_or_else(|| bad_wasm("section exceeds the module"))?;
+    if id == 0 {
+      let content = &module[pos..end];
+      let mut name_pos = 0;
+      let name = read_str(content, &mut name_pos)?;
+      sections.push(WasmCustomSection {
+        name,
+        payload: &content[name_pos..],
+        range: start..end,
+      });
+    }
+    pos = end;
+  }
+  Ok(sections)
+}
+
+/// Read the URL of the custom section `name` of the WebAssembly `module`,
+/// such as [WASM_SOURCE_MAPPING_URL] or [WASM_EXTERNAL_DEBUG_INFO].
+///
+/// ```
+/// use rspack_sources::{
+///   read_wasm_url_section, set_wasm_url_section, Source,
+///   WASM_SOURCE_MAPPING_URL,
+/// };
+///
+/// let module = b"\0asm\x01\0\0\0";
+/// assert_eq!(read_wasm_url_section(module, WASM_SOURCE_MAPPING_URL).unwrap(), None);
+/// let source =
+///   set_wasm_url_section(module, WASM_SOURCE_MAPPING_URL, "main.wasm.map").unwrap();
+/// let buffer = source.buffer();
+/// assert_eq!(
+///   read_wasm_url_section(&buffer, WASM_SOURCE_MAPPING_URL).unwrap(),
+///   Some("main.wasm.map"),
+/// );
+/// ```
+pub fn read_wasm_url_section<'a>(
+  module: &'a [u8],
+  name: &str,
+) -> Result<Option<&'a str>> {
+  let sections = wasm_custom_sections(module)?;
+  let Some(section) = sections.iter().find(|section| section.name == name)
+  else {
+    return Ok(None);
+  };
+  let mut pos = 0;
+  let url = read_str(section.payload, &mut pos)?;
+  Ok(Some(url))
+}
+
+/// Remove all custom sections `name` from the WebAssembly `module`.
+pub fn strip_wasm_custom_section(
+  module: &[u8],
+  name: &str,
+) -> Result<RawBufferSource> {
+  let sections = wasm_custom_sections(module)?;
+  let mut buffer = Vec::with_capacity(module.len());
+  let mut pos = 0;
+  for section in sections.iter().filter(|section| section.name == name) {
+    buffer.extend_from_slice(&module[pos..section.range.start]);
+    pos = section.range.end;
+  }
+  buffer.extend_from_slice(&module[pos..]);
+  Ok(RawBufferSource::from(buffer))
+}
+
+/// Set the URL of the custom section `name` of the WebAssembly `module`,
+/// such as [WASM_SOURCE_MAPPING_URL] or [WASM_EXTERNAL_DEBUG_INFO].
+///
+/// An existing section is rewritten in place and further sections with the
+/// same name are removed, otherwise the section is appended to the module.
+pub fn set_wasm_url_section(
+  module: &[u8],
+  name: &str,
+  url: &str,
+) -> Result<RawBufferSource> {
+  let sections = wasm_custom_sections(module)?;
+  let mut content = Vec::with_capacity(name.len() + url.len() + 10);
+  write_str(&mut content, name);
+  write_str(&mut content, url);
+  let mut section = vec![0];
+  write_u32(&mut section, content.len() as u32);
+  section.extend_from_slice(&content);
+
+  let mut buffer = Vec::with_capacity(module.len() + section.len());
+  let mut pos = 0;
+  let mut written = false;
+  for existing in sections.iter().filter(|existing| existing.name == name) {
+    buffer.extend_from_slice(&module[pos..existing.range.start]);
+    if !written {
+      buffer.extend_from_slice(&section);
+      written = true;
+    }
+    pos = existing.range.end;
+  }
+  buffer.extend_from_slice(&module[pos..]);
+  if !written {
+    buffer.extend_from_slice(&section);
+  }
+  Ok(RawBufferSource::from(buffer))
+}
+
+fn bad_wasm(message: &str) -> Error {
+  Error::BadWasm(message.into())
+}
+
+// Read an unsigned LEB128 encoded u32.
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32> {
+  let mut result = 0u32;
+  for shift in (0..35).step_by(7) {
+    let byte = *bytes
+      .get(*pos)
+      .ok_or_else(|| bad_wasm("unexpected end of module"))?;
+    *pos += 1;
+    if shift == 28 && byte > 0x0f {
+      return Err(bad_wasm("integer too large"));
+    }
+    result |= ((byte & 0x7f) as u32) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(result);
+    }
+  }
+  Err(bad_wasm("integer too large"))
+}
+
+fn read_str<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a str> {
+  let len = read_u32(bytes, pos)? as usize;
+  let value = pos
+    .checked_add(len)
+    .and_then(|end| bytes.get(*pos..end))
+    .ok_or_else(|| bad_wasm("name exceeds its section"))?;
+  *pos += len;
+  std::str::from_utf8(value).map_err(|_| bad_wasm("name is not UTF-8"))
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+  write_u32(out, value.len() as u32);
+  out.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Source;
+
+  use super::*;
+
+  // A module with a type section around a `name` custom section.
+  fn module() -> Vec<u8> {
+    let mut module = WASM_HEADER.to_vec();
+    module.extend_from_slice(&[1, 1, 0]);
+    module.extend_from_slice(&[0, 5, 4, b'n', b'a', b'm', b'e']);
+    module.extend_from_slice(&[1, 1, 0]);
+    module
+  }
+
+  #[test]
+  fn sets_and_strips_url_sections() {
+    let module = module();
+    let with_url =
+      set_wasm_url_section(&module, WASM_SOURCE_MAPPING_URL, "a.map").unwrap();
+    let with_url = with_url.buffer();
+    assert_eq!(&with_url[..module.len()], module.as_slice());
+    let with_urls =
+      set_wasm_url_section(&with_url, WASM_EXTERNAL_DEBUG_INFO, "a.dwp")
+        .unwrap();
+    let rewritten = set_wasm_url_section(
+      &with_urls.buffer(),
+      WASM_SOURCE_MAPPING_URL,
+      "b.map",
+    )
+    .unwrap();
+    let rewritten = rewritten.buffer();
+
+    let names = wasm_custom_sections(&rewritten)
+      .unwrap()
+      .into_iter()
+      .map(|section| section.name)
+      .collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      ["name", WASM_SOURCE_MAPPING_URL, WASM_EXTERNAL_DEBUG_INFO]
+    );
+    assert_eq!(
+      read_wasm_url_section(&rewritten, WASM_SOURCE_MAPPING_URL).unwrap(),
+      Some("b.map")
+    );
+    assert_eq!(
+      read_wasm_url_section(&rewritten, WASM_EXTERNAL_DEBUG_INFO).unwrap(),
+      Some("a.dwp")
+    );
+
+    let stripped =
+      strip_wasm_custom_section(&rewritten, WASM_SOURCE_MAPPING_URL).unwrap();
+    let stripped =
+      strip_wasm_custom_section(&stripped.buffer(), WASM_EXTERNAL_DEBUG_INFO)
+        .unwrap();
+    assert_eq!(stripped.buffer().as_ref(), module.as_slice());
+  }
+
+  #[test]
+  fn rejects_malformed_modules() {
+    assert!(matches!(
+      wasm_custom_sections(b"\0asm\x02\0\0\0"),
+      Err(Error::BadWasm(_))
+    ));
+    let mut module = module();
+    module.extend_from_slice(&[0, 9, 1]);
+    assert!(matches!(
+      wasm_custom_sections(&module),
+      Err(Error::BadWasm(_))
+    ));
+    let mut module = WASM_HEADER.to_vec();
+    module.extend_from_slice(&[0, 0x80, 0x80, 0x80, 0x80, 0x80, 0]);
+    assert!(matches!(
+      wasm_custom_sections(&module),
+      Err(Error::BadWasm(_))
+    ));
+  }
+}